@@ -1 +1,385 @@
+use crate::game::Game;
+use std::sync::Arc;
 
+use serde::{ Deserialize, Serialize };
+
+use crate::midi::MidiPlayer;
+use crate::rix::RixPlayer;
+use crate::utils::Result;
+use crate::voc::Voc;
+
+// 输出的采样率
+pub const SAMPLE_RATE: u32 = 44100;
+
+// 同时播放的音效数
+pub const SOUND_CHANNELS: usize = 8;
+
+// 淡入淡出，gain 从 from 线性变化到 to
+#[derive(Debug, Clone, Copy)]
+struct Fade {
+    from: f32,
+    to: f32,
+    total: usize,
+    elapsed: usize,
+}
+
+impl Fade {
+    fn gain(&self) -> f32 {
+        if self.elapsed >= self.total {
+            return self.to;
+        }
+        self.from + (self.to - self.from) * (self.elapsed as f32) / (self.total as f32)
+    }
+}
+
+// 背景音乐的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MusicType {
+    #[default]
+    Rix, // MUS.MKF，OPL2 合成
+    Midi, // MIDI.MKF，内置波表合成
+}
+
+pub enum Music {
+    Rix(Box<RixPlayer>),
+    Midi(Box<MidiPlayer>),
+}
+
+impl Music {
+    fn render(&mut self, out: &mut [i16]) -> usize {
+        match self {
+            Music::Rix(player) => player.render(out),
+            Music::Midi(player) => player.render(out),
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        match self {
+            Music::Rix(player) => player.is_finished(),
+            Music::Midi(player) => player.is_finished(),
+        }
+    }
+}
+
+// 音量，取值 0.0 ~ 1.0
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Volume {
+    pub master: f32,
+    pub music: f32,
+    pub sound: f32,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self { master: 1.0, music: 1.0, sound: 1.0 }
+    }
+}
+
+// 正在播放的音效
+#[derive(Debug, Clone)]
+struct Sound {
+    samples: Arc<[i16]>, // 已重采样到输出采样率
+    pos: usize,
+    volume: f32,
+    started: u64, // 开始播放的顺序，通道不够时替换最早的
+}
+
+// 游戏的声音输出，由混音方按需拉取采样
+pub struct Audio {
+    pub sample_rate: u32,
+    pub volume: Volume,
+    music: Option<Music>,
+    fade: Option<Fade>,
+    sounds: [Option<Sound>; SOUND_CHANNELS],
+    sounds_started: u64,
+}
+
+impl Audio {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            volume: Volume::default(),
+            music: None,
+            fade: None,
+            sounds: Default::default(),
+            sounds_started: 0,
+        }
+    }
+
+    pub fn is_sound_playing(&self, channel: usize) -> bool {
+        self.sounds.get(channel).is_some_and(|sound| sound.is_some())
+    }
+
+    // 在空闲通道上播放音效，没有空闲通道时替换最早开始的，返回所用的通道
+    pub fn play_sound(&mut self, samples: Arc<[i16]>, volume: f32) -> usize {
+        let channel = self.sounds
+            .iter()
+            .position(|sound| sound.is_none())
+            .unwrap_or_else(|| {
+                (0..SOUND_CHANNELS)
+                    .min_by_key(|&i| self.sounds[i].as_ref().map_or(0, |sound| sound.started))
+                    .unwrap_or(0)
+            });
+
+        self.sounds_started += 1;
+        self.sounds[channel] = Some(Sound { samples, pos: 0, volume: volume.clamp(0.0, 1.0), started: self.sounds_started });
+
+        channel
+    }
+
+    pub fn set_sound_volume(&mut self, channel: usize, volume: f32) {
+        if let Some(Some(sound)) = self.sounds.get_mut(channel) {
+            sound.volume = volume.clamp(0.0, 1.0);
+        }
+    }
+
+    pub fn stop_sound(&mut self, channel: usize) {
+        if let Some(sound) = self.sounds.get_mut(channel) {
+            *sound = None;
+        }
+    }
+
+    pub fn is_music_playing(&self) -> bool {
+        self.music.is_some()
+    }
+
+    fn seconds_to_samples(&self, seconds: f32) -> usize {
+        ((seconds.max(0.0) as f64) * (self.sample_rate as f64)) as usize
+    }
+
+    // 立即切换到新的音乐，fade 秒内淡入
+    pub fn play_music(&mut self, player: Music, fade: f32) {
+        self.music = Some(player);
+        let total = self.seconds_to_samples(fade);
+        self.fade = if total > 0 { Some(Fade { from: 0.0, to: 1.0, total, elapsed: 0 }) } else { None };
+    }
+
+    // fade 秒内淡出后停止，fade 为 0 时立即停止
+    pub fn stop_music(&mut self, fade: f32) {
+        let total = self.seconds_to_samples(fade);
+        if total == 0 || self.music.is_none() {
+            self.music = None;
+            self.fade = None;
+            return;
+        }
+
+        let from = self.fade.map_or(1.0, |fade| fade.gain());
+        self.fade = Some(Fade { from, to: 0.0, total, elapsed: 0 });
+    }
+
+    // 生成单声道采样，音效叠加在音乐上，没有声音的部分填 0
+    pub fn render(&mut self, out: &mut [i16]) {
+        self.render_music(out);
+
+        let gain = (self.volume.master * self.volume.music).clamp(0.0, 1.0);
+        if gain < 1.0 {
+            for sample in out.iter_mut() {
+                *sample = ((*sample as f32) * gain) as i16;
+            }
+        }
+
+        self.mix_sounds(out);
+    }
+
+    fn mix_sounds(&mut self, out: &mut [i16]) {
+        if self.sounds.iter().all(|sound| sound.is_none()) {
+            return;
+        }
+
+        let gain = (self.volume.master * self.volume.sound).clamp(0.0, 1.0);
+        // 先在 i32 上累加，最后再截断，避免多个音效叠加时过早溢出
+        let mut mix: Vec<i32> = out.iter().map(|&sample| sample as i32).collect();
+        for slot in self.sounds.iter_mut() {
+            let sound = match slot.as_mut() {
+                Some(sound) => sound,
+                None => {
+                    continue;
+                }
+            };
+
+            let n = std::cmp::min(out.len(), sound.samples.len() - sound.pos);
+            for (acc, &sample) in mix.iter_mut().zip(&sound.samples[sound.pos..sound.pos + n]) {
+                *acc += ((sample as f32) * sound.volume * gain) as i32;
+            }
+            sound.pos += n;
+            if sound.pos >= sound.samples.len() {
+                *slot = None;
+            }
+        }
+
+        for (sample, acc) in out.iter_mut().zip(mix) {
+            *sample = acc.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        }
+    }
+
+    fn render_music(&mut self, out: &mut [i16]) {
+        out.fill(0);
+
+        let music = match self.music.as_mut() {
+            Some(music) => music,
+            None => {
+                return;
+            }
+        };
+
+        let n = music.render(out);
+        if let Some(fade) = self.fade.as_mut() {
+            for sample in out[..n].iter_mut() {
+                *sample = ((*sample as f32) * fade.gain()) as i16;
+                fade.elapsed += 1;
+            }
+        }
+
+        let faded_out = self.fade.is_some_and(|fade| fade.to == 0.0 && fade.elapsed >= fade.total);
+        if music.is_finished() || faded_out {
+            self.music = None;
+            self.fade = None;
+        } else if self.fade.is_some_and(|fade| fade.elapsed >= fade.total) {
+            self.fade = None;
+        }
+    }
+}
+
+// 把 16 位 PCM 采样封装成 WAV 文件
+pub fn encode_wav(samples: &[i16], sample_rate: u32, channels: u16) -> Vec<u8> {
+    let data_size = (samples.len() * 2) as u32;
+    let block_align = channels * 2;
+
+    let mut buf = Vec::with_capacity(44 + samples.len() * 2);
+    buf.extend_from_slice(b"RIFF");
+    buf.extend_from_slice(&(36 + data_size).to_le_bytes());
+    buf.extend_from_slice(b"WAVE");
+    buf.extend_from_slice(b"fmt ");
+    buf.extend_from_slice(&16u32.to_le_bytes());
+    buf.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    buf.extend_from_slice(&channels.to_le_bytes());
+    buf.extend_from_slice(&sample_rate.to_le_bytes());
+    buf.extend_from_slice(&(sample_rate * (block_align as u32)).to_le_bytes());
+    buf.extend_from_slice(&block_align.to_le_bytes());
+    buf.extend_from_slice(&16u16.to_le_bytes());
+    buf.extend_from_slice(b"data");
+    buf.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        buf.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    buf
+}
+
+// 离线渲染音乐的前 seconds 秒
+fn render_offline(mut music: Music, seconds: f32, sample_rate: u32) -> Vec<i16> {
+    let mut samples = vec![0; ((seconds.max(0.0) as f64) * (sample_rate as f64)) as usize];
+    let n = music.render(&mut samples);
+    samples.truncate(n);
+
+    samples
+}
+
+// 离线渲染 RIX 音乐的前 seconds 秒，不循环
+pub fn render_rix(data: Vec<u8>, seconds: f32, sample_rate: u32) -> Result<Vec<i16>> {
+    let player = RixPlayer::new(data, false, sample_rate)?;
+
+    Ok(render_offline(Music::Rix(Box::new(player)), seconds, sample_rate))
+}
+
+// 离线渲染 MIDI 音乐的前 seconds 秒，不循环
+pub fn render_midi(data: &[u8], seconds: f32, sample_rate: u32) -> Result<Vec<i16>> {
+    let player = MidiPlayer::new(data, false, sample_rate)?;
+
+    Ok(render_offline(Music::Midi(Box::new(player)), seconds, sample_rate))
+}
+
+impl Game {
+    // 读取第 num 首音乐，根据 music_type 选择 MUS.MKF 或 MIDI.MKF。没有 MUS.MKF 时为空
+    fn read_music_chunk(&mut self, num: u16) -> Result<Vec<u8>> {
+        let mkf = match self.config.music_type {
+            MusicType::Rix => match self.mkf.mus.as_mut() {
+                Some(mkf) => mkf,
+                None => {
+                    return Ok(Vec::new());
+                }
+            },
+            MusicType::Midi => &mut self.mkf.midi,
+        };
+
+        Ok(mkf.read_chunk(num as u32)?)
+    }
+
+    // 播放第 num 首音乐，num 为 0 时停止
+    pub fn play_music(&mut self, num: u16, looping: bool, fade: f32) -> Result<()> {
+        if num == 0 || !self.config.music_enabled {
+            self.audio.lock().stop_music(fade);
+            return Ok(());
+        }
+
+        let data = self.read_music_chunk(num)?;
+        if data.is_empty() {
+            self.audio.lock().stop_music(fade);
+            return Ok(());
+        }
+        let music = match self.config.music_type {
+            MusicType::Rix => Music::Rix(Box::new(RixPlayer::new(data, looping, self.audio.sample_rate())?)),
+            MusicType::Midi => Music::Midi(Box::new(MidiPlayer::new(&data, looping, self.audio.sample_rate())?)),
+        };
+        self.audio.lock().play_music(music, fade);
+
+        Ok(())
+    }
+
+    // 读取并重采样第 num 个音效。出错时记录下来并当作没有声音，音效不影响游戏进行
+    fn load_sound(&mut self, num: u16) -> Arc<[i16]> {
+        let data = match self.mkf.voc.read_chunk(num as u32) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("cannot read sound {}: {}", num, e);
+                return Arc::from([]);
+            }
+        };
+        if data.is_empty() {
+            return Arc::from([]);
+        }
+
+        match Voc::parse(&data) {
+            Ok(voc) => voc.resample(self.audio.sample_rate()).into(),
+            Err(e) => {
+                eprintln!("cannot parse sound {}: {}", num, e);
+                Arc::from([])
+            }
+        }
+    }
+
+    // 播放 VOC.MKF 中的第 num 个音效，num 为负数时不播放
+    pub fn play_sound(&mut self, num: i16) -> Result<()> {
+        if num < 0 || !self.config.sound_enabled {
+            return Ok(());
+        }
+
+        let num = num as u16;
+        let samples = match self.sound_cache.get(&num) {
+            Some(samples) => samples.clone(),
+            None => {
+                let samples = self.load_sound(num);
+                self.sound_cache.insert(num, samples.clone());
+                samples
+            }
+        };
+        if !samples.is_empty() {
+            self.audio.lock().play_sound(samples, 1.0);
+        }
+
+        Ok(())
+    }
+
+    // 把第 num 首音乐渲染为 WAV
+    pub fn render_music_to_wav(&mut self, num: u16, seconds: f32) -> Result<Vec<u8>> {
+        let data = self.read_music_chunk(num)?;
+        let samples = match self.config.music_type {
+            MusicType::Rix => render_rix(data, seconds, self.audio.sample_rate())?,
+            MusicType::Midi => render_midi(&data, seconds, self.audio.sample_rate())?,
+        };
+
+        Ok(encode_wav(&samples, self.audio.sample_rate(), 1))
+    }
+}
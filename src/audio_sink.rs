@@ -0,0 +1,235 @@
+use std::fs::File;
+use std::io::{ BufWriter, Seek, SeekFrom, Write };
+use std::path::Path;
+
+use crate::audio::encode_wav;
+use crate::config::AudioOutput;
+use crate::utils::Result;
+
+// 混音后的单声道采样的去处，在混音线程上使用
+pub trait AudioSink: Send {
+    fn sample_rate(&self) -> u32;
+
+    fn write(&mut self, samples: &[i16]) -> Result<()>;
+
+    // 停止输出时调用，写完剩余的数据
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// 丢弃所有采样，用于没有声卡的环境
+pub struct NullSink {
+    sample_rate: u32,
+}
+
+impl NullSink {
+    pub fn new(sample_rate: u32) -> Self {
+        Self { sample_rate }
+    }
+}
+
+impl AudioSink for NullSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn write(&mut self, _samples: &[i16]) -> Result<()> {
+        Ok(())
+    }
+}
+
+// 把采样写入 WAV 文件，结束时回填文件头中的长度
+pub struct WavSink<W: Write + Seek + Send> {
+    writer: W,
+    sample_rate: u32,
+    data_size: u32,
+}
+
+impl WavSink<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P, sample_rate: u32) -> Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), sample_rate)
+    }
+}
+
+impl<W: Write + Seek + Send> WavSink<W> {
+    pub fn new(mut writer: W, sample_rate: u32) -> Result<Self> {
+        writer.write_all(&encode_wav(&[], sample_rate, 1))?;
+
+        Ok(Self { writer, sample_rate, data_size: 0 })
+    }
+
+    pub fn into_inner(mut self) -> Result<W> {
+        self.finish()?;
+
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write + Seek + Send> AudioSink for WavSink<W> {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn write(&mut self, samples: &[i16]) -> Result<()> {
+        let bytes: Vec<u8> = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+        self.writer.write_all(&bytes)?;
+        self.data_size = self.data_size.saturating_add(bytes.len() as u32);
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer.write_all(&(36 + self.data_size).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(40))?;
+        self.writer.write_all(&self.data_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+// 按设置打开声音输出
+pub fn open_audio_sink(output: &AudioOutput, sample_rate: u32) -> Result<Box<dyn AudioSink>> {
+    match output {
+        #[cfg(feature = "audio-device")]
+        AudioOutput::Device => {
+            // 打不开声卡时静音运行，不影响游戏
+            match DeviceSink::open(sample_rate) {
+                Ok(sink) => Ok(Box::new(sink)),
+                Err(e) => {
+                    eprintln!("cannot open audio device, running without sound: {}", e);
+                    Ok(Box::new(NullSink::new(sample_rate)))
+                }
+            }
+        }
+        #[cfg(not(feature = "audio-device"))]
+        AudioOutput::Device => Err("built without the audio-device feature".into()),
+        AudioOutput::Null => Ok(Box::new(NullSink::new(sample_rate))),
+        AudioOutput::Wav(path) => Ok(Box::new(WavSink::create(path, sample_rate)?)),
+    }
+}
+
+#[cfg(feature = "audio-device")]
+pub use device::DeviceSink;
+
+#[cfg(feature = "audio-device")]
+mod device {
+    use std::collections::VecDeque;
+    use std::sync::{ mpsc, Arc, Mutex };
+    use std::thread;
+
+    use cpal::traits::{ DeviceTrait, HostTrait, StreamTrait };
+
+    use super::AudioSink;
+    use crate::utils::Result;
+
+    // 缓冲最多保留的时长，超出时丢弃最早的采样
+    const MAX_BUFFERED_SECONDS: f32 = 0.25;
+
+    // 系统默认声卡
+    pub struct DeviceSink {
+        sample_rate: u32,
+        buffer: Arc<Mutex<VecDeque<i16>>>,
+        _stop: mpsc::Sender<()>, // 丢弃时结束持有 Stream 的线程
+    }
+
+    // 按声卡的采样格式输出，16 位采样在回调中转换
+    fn build_stream<T: cpal::SizedSample + cpal::FromSample<i16>>(
+        device: &cpal::Device,
+        config: &cpal::StreamConfig,
+        buffer: Arc<Mutex<VecDeque<i16>>>
+    ) -> Result<cpal::Stream> {
+        let channels = config.channels as usize;
+        let stream = device.build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                let mut buffer = buffer.lock().unwrap_or_else(|e| e.into_inner());
+                // 单声道复制到所有声道，缓冲不够时补静音
+                for frame in data.chunks_mut(channels) {
+                    frame.fill(T::from_sample(buffer.pop_front().unwrap_or(0)));
+                }
+            },
+            |_| {},
+            None
+        )?;
+
+        Ok(stream)
+    }
+
+    // cpal 的 Stream 不一定能跨线程移动，所以在单独的线程上创建和持有，直到 stop 被丢弃
+    fn run_stream(
+        sample_rate: u32,
+        buffer: Arc<Mutex<VecDeque<i16>>>,
+        stop: mpsc::Receiver<()>,
+        opened: mpsc::Sender<std::result::Result<(), String>>
+    ) {
+        let open = || -> Result<cpal::Stream> {
+            let host = cpal::default_host();
+            let device = host.default_output_device().ok_or("no audio output device")?;
+            // 混音器的采样率是固定的，选一个支持它的输出设置，采样格式不限
+            let rate = cpal::SampleRate(sample_rate);
+            let supported = device
+                .supported_output_configs()?
+                .find(|range| range.min_sample_rate() <= rate && rate <= range.max_sample_rate())
+                .ok_or(format!("audio device does not support {} Hz", sample_rate))?
+                .with_sample_rate(rate);
+            let config = supported.config();
+
+            let stream = match supported.sample_format() {
+                cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, buffer)?,
+                cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, buffer)?,
+                cpal::SampleFormat::I32 => build_stream::<i32>(&device, &config, buffer)?,
+                cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, buffer)?,
+                format => {
+                    return Err(format!("unsupported audio sample format: {}", format).into());
+                }
+            };
+            stream.play()?;
+
+            Ok(stream)
+        };
+
+        match open() {
+            Ok(_stream) => {
+                let _ = opened.send(Ok(()));
+                // 等待 DeviceSink 被丢弃
+                let _ = stop.recv();
+            }
+            Err(e) => {
+                let _ = opened.send(Err(e.to_string()));
+            }
+        }
+    }
+
+    impl DeviceSink {
+        pub fn open(sample_rate: u32) -> Result<Self> {
+            let buffer = Arc::new(Mutex::new(VecDeque::<i16>::new()));
+            let (stop, stop_rx) = mpsc::channel();
+            let (opened, opened_rx) = mpsc::channel();
+            let stream_buffer = buffer.clone();
+            thread::spawn(move || run_stream(sample_rate, stream_buffer, stop_rx, opened));
+            opened_rx.recv()??;
+
+            Ok(Self { sample_rate, buffer, _stop: stop })
+        }
+    }
+
+    impl AudioSink for DeviceSink {
+        fn sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+
+        fn write(&mut self, samples: &[i16]) -> Result<()> {
+            let max = ((self.sample_rate as f32) * MAX_BUFFERED_SECONDS) as usize;
+            let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+            buffer.extend(samples);
+            let excess = buffer.len().saturating_sub(max);
+            buffer.drain(..excess);
+
+            Ok(())
+        }
+    }
+}
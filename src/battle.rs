@@ -0,0 +1,1658 @@
+use crate::data::*;
+use crate::game::Game;
+use crate::input::PalKey;
+use crate::inventory::*;
+use crate::party::*;
+use crate::sprite::{ draw_sprite_frame, sprite_get_frames, Sprite };
+use crate::ui::*;
+use crate::utils::*;
+
+// 战斗指令图标，在 UI 精灵中的序号
+const SPRITENUM_BATTLEICON_ATTACK: usize = 40;
+const SPRITENUM_BATTLEICON_MAGIC: usize = 41;
+const SPRITENUM_BATTLEICON_COOPMAGIC: usize = 42;
+const SPRITENUM_BATTLEICON_MISCMENU: usize = 43;
+const SPRITENUM_BATTLE_ARROW_CURRENTPLAYER: usize = 69;
+
+const BATTLEUI_LABEL_AUTO: u32 = 56;
+const BATTLEUI_LABEL_INVENTORY: u32 = 57;
+const BATTLEUI_LABEL_DEFEND: u32 = 58;
+const BATTLEUI_LABEL_FLEE: u32 = 59;
+const BATTLEUI_LABEL_STATUS: u32 = 60;
+const BATTLEUI_LABEL_USEITEM: u32 = 23;
+const BATTLEUI_LABEL_THROWITEM: u32 = 24;
+
+const BATTLEWIN_GETEXP_LABEL: u32 = 30;
+const BATTLEWIN_BEATENEMY_LABEL: u32 = 9;
+const BATTLEWIN_DOLLAR_LABEL: u32 = 10;
+const BATTLEWIN_LEVELUP_LABEL: u32 = 32;
+const BATTLEWIN_ADDMAGIC_LABEL: u32 = 33;
+
+// 指令图标的位置：攻击、法术、合体法术、其他
+const BATTLEICON_POS: [(isize, isize); 4] = [(27, 140), (0, 155), (54, 155), (27, 170)];
+
+// 角色在战场上的位置 [队伍人数 - 1][序号]
+const PLAYER_POS: [[(isize, isize); 3]; 3] = [
+    [(240, 170), (0, 0), (0, 0)],
+    [(200, 176), (256, 152), (0, 0)],
+    [(180, 180), (234, 170), (270, 146)],
+];
+
+// 法术伤害计算中抗性的除数，敌人的抗性为 0～10，角色的抗性为百分比
+const ENEMY_RESISTANCE_MULTIPLIER: u16 = 1;
+const PLAYER_RESISTANCE_MULTIPLIER: u16 = 10;
+
+/*
+typedef enum tagBATTLERESULT
+{
+   kBattleResultWon        = 3,      // player won the battle
+   kBattleResultLost       = 1,      // player lost the battle
+   kBattleResultFleed      = 0xFFFF, // player fleed from the battle
+   kBattleResultTerminated = 0,      // battle terminated with scripts
+} BATTLERESULT;
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BattleResult {
+    Won,
+    Lost,
+    Fled,
+    Terminated,
+}
+
+impl BattleResult {
+    pub fn from_u16(value: u16) -> Self {
+        match value {
+            3 => BattleResult::Won,
+            1 => BattleResult::Lost,
+            0xffff => BattleResult::Fled,
+            _ => BattleResult::Terminated,
+        }
+    }
+}
+
+// 角色的战斗指令。敌人目标为战场上敌人的序号，队友目标为队伍中的位置，None 表示全体
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BattleAction {
+    Pass,
+    Defend,
+    Attack(Option<usize>),
+    AttackMate,
+    Magic {
+        magic: u16,
+        target: Option<usize>,
+    },
+    UseItem {
+        item: u16,
+        target: Option<usize>,
+    },
+    ThrowItem {
+        item: u16,
+        target: Option<usize>,
+    },
+    Flee,
+}
+
+// 行动者：队伍中的位置或战场上敌人的序号
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Actor {
+    Player(usize),
+    Enemy(usize),
+}
+
+// 指令选择界面的结果
+enum BattleCommand {
+    Action(BattleAction),
+    Back,
+    Repeat,
+    Auto,
+    Force,
+}
+
+pub struct BattleEnemy {
+    pub object_id: u16, // 为 0 时表示空位或已被击倒
+    pub enemy: Enemy, // 战斗中会被修改的属性副本
+    pub script_on_turn_start: u16,
+    pub script_on_battle_end: u16,
+    pub script_on_ready: u16,
+    pub status: [u16; STATUS_ALL],
+    pub poisons: Vec<PoisonStatus>,
+    pub pos: Pos, // 精灵底部中点
+    pub sprite: Sprite,
+}
+
+impl BattleEnemy {
+    pub fn is_alive(&self) -> bool {
+        self.object_id != 0 && self.enemy.health > 0
+    }
+
+    // 能否行动，睡眠和定身时不能行动
+    fn can_act(&self) -> bool {
+        self.is_alive() &&
+            self.status[Status::Sleep as usize] == 0 &&
+            self.status[Status::Paralyzed as usize] == 0
+    }
+
+    pub fn has_poison(&self, poison_id: u16) -> bool {
+        self.poisons.iter().any(|poison| poison.poison_id == poison_id)
+    }
+
+    // 未中同种毒且有空位时才能中毒
+    pub fn can_add_poison(&self, poison_id: u16) -> bool {
+        poison_id != 0 && !self.has_poison(poison_id) && self.poisons.iter().any(|poison| poison.poison_id == 0)
+    }
+
+    // 中毒，已中同种毒或没有空位时返回 false
+    pub fn add_poison(&mut self, poison_id: u16, poison_script: u16) -> bool {
+        if !self.can_add_poison(poison_id) {
+            return false;
+        }
+
+        match self.poisons.iter_mut().find(|poison| poison.poison_id == 0) {
+            Some(slot) => {
+                *slot = PoisonStatus { poison_id, poison_script };
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove_poison(&mut self, poison_id: u16) {
+        for poison in self.poisons.iter_mut().filter(|poison| poison.poison_id == poison_id) {
+            *poison = PoisonStatus::default();
+        }
+    }
+
+    // 显示伤害数字的位置
+    fn top(&self) -> Pos {
+        let height = self.sprite.first().map_or(0, |frame| frame.height as isize);
+        Pos { x: self.pos.x, y: self.pos.y - height }
+    }
+}
+
+pub struct BattlePlayer {
+    pub player_role: usize,
+    pub action: BattleAction,
+    pub prev_action: BattleAction, // 重复指令时使用
+    pub defending: bool,
+    pub frame: Option<usize>, // 施法等动作的帧，None 时按 HP 显示站立或虚弱的帧
+    pub pos: Pos, // 精灵底部中点
+    pub sprite: Sprite,
+}
+
+pub struct Battle {
+    pub enemy_team: u16,
+    pub enemies: Vec<BattleEnemy>,
+    pub players: Vec<BattlePlayer>,
+    pub background: Vec<u8>,
+    pub screen_wave: u16, // 战场的屏幕波动
+    pub is_boss: bool, // 不能逃跑
+    pub auto_battle: bool,
+    pub result: Option<BattleResult>, // 战斗结束时设置，脚本也可以设置
+    pub current_actor: Option<Actor>,
+    pub blow: i16, // 敌人被击退的距离
+    pub exp_gained: u32,
+    pub cash_gained: u32,
+}
+
+pub fn calc_base_damage(attack: u16, defense: u16) -> i32 {
+    let (attack, defense) = (attack as f32, defense as f32);
+    if attack > defense {
+        (attack * 2.0 - defense * 1.6 + 0.5) as i32
+    } else if attack > defense * 0.6 {
+        (attack - defense * 0.6 + 0.5) as i32
+    } else {
+        0
+    }
+}
+
+pub fn calc_physical_attack_damage(attack: u16, defense: u16, resistance: u16) -> i32 {
+    let damage = calc_base_damage(attack, defense);
+    if resistance != 0 { damage / (resistance as i32) } else { damage }
+}
+
+// 法术伤害：基础伤害的四分之一加上法术本身的伤害，再按属性抗性和战场的属性加成调整
+pub fn calc_magic_damage(
+    magic_strength: u16,
+    defense: u16,
+    elemental_resistance: &[u16; NUM_MAGIC_ELEMENTAL],
+    poison_resistance: u16,
+    resistance_multiplier: u16,
+    magic: &Magic,
+    battle_field: &BattleField
+) -> i32 {
+    let mut damage = calc_base_damage(magic_strength, defense) / 4 + (magic.base_damage as i16 as i32);
+
+    let elemental = magic.elemental as usize;
+    if elemental != 0 {
+        // 属性序号超过元素数量时为毒系
+        let resistance = if elemental > NUM_MAGIC_ELEMENTAL {
+            poison_resistance
+        } else {
+            elemental_resistance[elemental - 1]
+        };
+        let factor = 10.0 - (resistance as f32) / (std::cmp::max(resistance_multiplier, 1) as f32);
+        damage = (((damage as f32) * factor.max(0.0)) as i32) / 5;
+
+        if elemental <= NUM_MAGIC_ELEMENTAL {
+            damage = (damage * (10 + (battle_field.magic_effect[elemental - 1] as i32))) / 10;
+        }
+    }
+
+    damage
+}
+
+// 按速度从快到慢排列行动顺序，速度相同时保持原来的顺序
+pub fn turn_order(speeds: &[(Actor, i32)]) -> Vec<Actor> {
+    let mut speeds = speeds.to_vec();
+    speeds.sort_by_key(|&(_, speed)| std::cmp::Reverse(speed));
+    speeds
+        .into_iter()
+        .map(|(actor, _)| actor)
+        .collect()
+}
+
+impl Game {
+    pub fn battle_ref(&self) -> &Battle {
+        self.battle.as_ref().expect("not in battle")
+    }
+
+    pub fn battle_mut(&mut self) -> &mut Battle {
+        self.battle.as_mut().expect("not in battle")
+    }
+
+    pub fn battle_result(&self) -> Option<BattleResult> {
+        self.battle.as_ref().and_then(|battle| battle.result)
+    }
+
+    // 角色能否行动：存活（或处于傀儡状态），且未睡眠、定身
+    fn battle_player_can_act(&self, player_role: usize) -> bool {
+        let status = &self.state.party_state.status[player_role];
+        (self.state.player_roles.hp[player_role] > 0 || status[Status::Puppet as usize] > 0) &&
+            status[Status::Sleep as usize] == 0 &&
+            status[Status::Paralyzed as usize] == 0
+    }
+
+    fn battle_alive_enemies(&self) -> Vec<usize> {
+        self.battle_ref()
+            .enemies.iter()
+            .enumerate()
+            .filter(|(_, enemy)| enemy.is_alive())
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    fn battle_alive_players(&self) -> Vec<usize> {
+        self.battle_ref()
+            .players.iter()
+            .enumerate()
+            .filter(|(_, player)| self.state.player_roles.hp[player.player_role] > 0)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // 指令的目标已被击倒时，改为攻击第一个存活的敌人
+    fn battle_enemy_targets(&self, target: Option<usize>) -> Vec<usize> {
+        let alive = self.battle_alive_enemies();
+        match target {
+            Some(target) if alive.contains(&target) => vec![target],
+            Some(_) => alive.into_iter().take(1).collect(),
+            None => alive,
+        }
+    }
+
+    pub fn battle_enemy(&self, index: u16) -> Option<&BattleEnemy> {
+        self.battle.as_ref()?.enemies.get(index as usize)
+    }
+
+    pub fn battle_enemy_mut(&mut self, index: u16) -> Option<&mut BattleEnemy> {
+        self.battle.as_mut()?.enemies.get_mut(index as usize)
+    }
+
+    // 脚本作用的敌人：all 不为 0 时为所有存活的敌人，否则为当前敌人
+    pub fn battle_script_targets(&self, all: u16, event_object_id: u16) -> Vec<usize> {
+        if self.battle.is_none() {
+            return Vec::new();
+        }
+
+        if all != 0 {
+            self.battle_alive_enemies()
+        } else if self.battle_enemy(event_object_id).is_some_and(|enemy| enemy.is_alive()) {
+            vec![event_object_id as usize]
+        } else {
+            Vec::new()
+        }
+    }
+
+    // 敌人对巫术和毒的抗性，0～10
+    pub fn battle_enemy_resistance(&self, index: usize) -> u16 {
+        self.battle_enemy(index as u16)
+            .and_then(|enemy| self.state.object_enemy(enemy.object_id))
+            .map_or(0, |object| object.resistance_to_sorcery)
+    }
+
+    // 敌人的最大 HP，即 DATA.MKF 中的初始 HP
+    pub fn battle_enemy_max_health(&self, index: usize) -> u16 {
+        self.battle_enemy(index as u16)
+            .and_then(|enemy| self.state.object_enemy(enemy.object_id))
+            .and_then(|object| self.data.enemy(object.enemy_id as usize))
+            .map_or(0, |enemy| enemy.health)
+    }
+
+    fn new_battle_enemy(&mut self, object_id: u16) -> Result<BattleEnemy> {
+        let object = self.state.object_enemy(object_id);
+        let (enemy, sprite) = match object {
+            Some(object) => {
+                let enemy = self.data.enemy(object.enemy_id as usize).copied().unwrap_or_default();
+                let chunk = self.mkf.abc.read_chunk_decompressed(object.enemy_id as u32)?;
+                (enemy, sprite_get_frames(&chunk)?)
+            }
+            None => (Enemy::default(), Vec::new()),
+        };
+
+        Ok(BattleEnemy {
+            object_id: if object.is_some() { object_id } else { 0 },
+            enemy,
+            script_on_turn_start: object.map_or(0, |object| object.script_on_turn_start),
+            script_on_battle_end: object.map_or(0, |object| object.script_on_battle_end),
+            script_on_ready: object.map_or(0, |object| object.script_on_ready),
+            status: [0; STATUS_ALL],
+            poisons: vec![PoisonStatus::default(); MAX_POISONS],
+            pos: Pos { x: 0, y: 0 },
+            sprite,
+        })
+    }
+
+    fn enemy_battle_pos(&self, count: usize, index: usize, enemy: &Enemy) -> Pos {
+        let position = self.data.enemy_position(count, index).copied().unwrap_or_default();
+        Pos {
+            x: position.x as isize,
+            y: (position.y as isize) + (enemy.y_pos_offset as i16 as isize),
+        }
+    }
+
+    // 敌人数量变化后重新排列位置
+    fn battle_update_enemy_positions(&mut self) {
+        let count = self.battle_ref().enemies.len();
+        let positions: Vec<Pos> = self.battle_ref()
+            .enemies.iter()
+            .enumerate()
+            .map(|(i, enemy)| self.enemy_battle_pos(count, i, &enemy.enemy))
+            .collect();
+        for (enemy, pos) in self.battle_mut().enemies.iter_mut().zip(positions) {
+            enemy.pos = pos;
+        }
+    }
+
+    // 可以放置新敌人的位置：已被击倒的敌人和队伍中剩余的空位
+    fn battle_free_slots(&self) -> Vec<usize> {
+        let enemies = &self.battle_ref().enemies;
+        enemies
+            .iter()
+            .enumerate()
+            .filter(|(_, enemy)| enemy.object_id == 0)
+            .map(|(i, _)| i)
+            .chain(enemies.len()..MAX_ENEMIES_IN_TEAM)
+            .collect()
+    }
+
+    fn battle_place_enemy(&mut self, slot: usize, enemy: BattleEnemy) {
+        let enemies = &mut self.battle_mut().enemies;
+        if slot < enemies.len() {
+            enemies[slot] = enemy;
+        } else {
+            enemies.push(enemy);
+        }
+    }
+
+    // 敌人处于睡眠、定身或混乱时不能分裂、召唤或变身
+    fn battle_enemy_is_sane(&self, index: usize) -> bool {
+        self.battle_enemy(index as u16).is_some_and(|enemy| {
+            enemy.can_act() && enemy.status[Status::Confused as usize] == 0
+        })
+    }
+
+    // 敌人分裂为 num 个，只剩一个敌人且 HP 大于 1 时才能分裂
+    pub fn battle_split_enemy(&mut self, index: usize, num: u16) -> Result<bool> {
+        let alive = self.battle_alive_enemies();
+        let (object_id, health) = match self.battle_enemy(index as u16) {
+            Some(enemy) if alive == [index] && enemy.enemy.health > 1 => (enemy.object_id, enemy.enemy.health),
+            _ => {
+                return Ok(false);
+            }
+        };
+
+        let slots = self.battle_free_slots();
+        let count = std::cmp::min(std::cmp::max(num, 1) as usize, slots.len());
+        let health = std::cmp::max(health / ((count as u16) + 1), 1);
+        for slot in slots.into_iter().take(count) {
+            let mut enemy = self.new_battle_enemy(object_id)?;
+            enemy.enemy.health = health;
+            self.battle_place_enemy(slot, enemy);
+        }
+        self.battle_mut().enemies[index].enemy.health = health;
+        self.battle_update_enemy_positions();
+
+        Ok(true)
+    }
+
+    // 敌人召唤 num 个 object_id 敌人，object_id 为 0 时召唤同类。空位不足时返回 false
+    pub fn battle_summon_enemies(&mut self, index: usize, object_id: u16, num: u16) -> Result<bool> {
+        let object_id = match object_id {
+            0 | 0xffff => self.battle_enemy(index as u16).map_or(0, |enemy| enemy.object_id),
+            _ => object_id,
+        };
+        // 数量按有符号数处理，不大于 0 时为 1
+        let num = std::cmp::max(num as i16, 1) as usize;
+
+        let slots = self.battle_free_slots();
+        if slots.len() < num || !self.battle_enemy_is_sane(index) {
+            return Ok(false);
+        }
+
+        for slot in slots.into_iter().take(num) {
+            let enemy = self.new_battle_enemy(object_id)?;
+            self.battle_place_enemy(slot, enemy);
+        }
+        self.battle_update_enemy_positions();
+
+        Ok(true)
+    }
+
+    // 敌人变身为 object_id，保留当前的 HP。回合开始、行动和战斗结束的脚本都换成新形态的
+    pub fn battle_transform_enemy(&mut self, index: usize, object_id: u16) -> Result<()> {
+        if !self.battle_enemy_is_sane(index) {
+            return Ok(());
+        }
+
+        let health = self.battle_ref().enemies[index].enemy.health;
+        let mut enemy = self.new_battle_enemy(object_id)?;
+        enemy.enemy.health = health;
+        self.battle_place_enemy(index, enemy);
+        self.battle_update_enemy_positions();
+
+        Ok(())
+    }
+
+    // 偷窃。敌人没有可偷的物品时偷钱
+    pub fn battle_steal(&mut self, index: usize, steal_rate: u16) {
+        let enemy = match self.battle.as_mut().and_then(|battle| battle.enemies.get_mut(index)) {
+            Some(enemy) if enemy.is_alive() => &mut enemy.enemy,
+            _ => {
+                return;
+            }
+        };
+
+        if enemy.num_steal_item == 0 || (steal_rate != 0 && self.state.rng.random_long(0, 10) > (steal_rate as i32)) {
+            return;
+        }
+
+        if enemy.steal_item == 0 {
+            let cash = enemy.num_steal_item / (self.state.rng.random_long(2, 3) as u16);
+            enemy.num_steal_item -= cash;
+            self.state.cash = self.state.cash.saturating_add(cash as u32);
+        } else {
+            enemy.num_steal_item -= 1;
+            let item = enemy.steal_item;
+            self.state.add_item_to_inventory(item, 1);
+        }
+    }
+
+    // 所有敌人逃走，战斗结束
+    pub fn battle_enemy_escape(&mut self) {
+        if let Some(battle) = self.battle.as_mut() {
+            for enemy in battle.enemies.iter_mut() {
+                enemy.object_id = 0;
+            }
+            battle.result = Some(BattleResult::Terminated);
+        }
+    }
+
+    // 当前行动的角色
+    pub fn battle_moving_player_role(&self) -> Option<usize> {
+        match self.battle.as_ref()?.current_actor? {
+            Actor::Player(index) => self.battle_ref().players.get(index).map(|player| player.player_role),
+            Actor::Enemy(_) => None,
+        }
+    }
+
+    // 以 strength 为灵力对敌人施放法术。作用于全体的法术攻击所有敌人，
+    // 否则攻击 target，target 为 0xffff 或已被击倒时攻击第一个存活的敌人
+    pub fn battle_simulate_magic(&mut self, target: u16, magic: u16, strength: i32) -> Result<()> {
+        let object = match self.state.object_magic(magic) {
+            Some(object) => object,
+            None => {
+                return Ok(());
+            }
+        };
+        let data = match self.data.magic(object.magic_number as usize) {
+            Some(data) => *data,
+            None => {
+                return Ok(());
+            }
+        };
+        if strength <= 0 {
+            return Ok(());
+        }
+
+        let targets = if object.flags & MAGIC_FLAG_APPLY_TO_ALL != 0 {
+            self.battle_enemy_targets(None)
+        } else {
+            self.battle_enemy_targets(Some(target as usize))
+        };
+        let battle_field = self.data.battle_field(self.state.num_battle_field as usize).copied().unwrap_or_default();
+        for index in targets {
+            let enemy = self.battle_ref().enemies[index].enemy;
+            let defense = enemy.defense.saturating_add((enemy.level + 6).saturating_mul(4));
+            let damage = calc_magic_damage(
+                std::cmp::min(strength, u16::MAX as i32) as u16,
+                defense,
+                &enemy.elem_resistance,
+                enemy.poison_resistance,
+                ENEMY_RESISTANCE_MULTIPLIER,
+                &data,
+                &battle_field
+            );
+            self.battle_damage_enemy(index, std::cmp::max(damage, 0))?;
+        }
+
+        Ok(())
+    }
+
+    fn load_battle(&mut self, enemy_team: u16, is_boss: bool) -> Result<Battle> {
+        let team = self.data.enemy_team(enemy_team as usize).copied().unwrap_or_default();
+        let count = team.enemies
+            .iter()
+            .rposition(|&object_id| object_id != 0xffff)
+            .map_or(0, |i| i + 1);
+
+        let mut enemies = Vec::new();
+        for &object_id in team.enemies.iter().take(count) {
+            enemies.push(self.new_battle_enemy(object_id)?);
+        }
+        for (i, enemy) in enemies.iter_mut().enumerate() {
+            enemy.pos = self.enemy_battle_pos(count, i, &enemy.enemy);
+        }
+
+        let roles = self.state.party_roles();
+        let num = roles.len().clamp(1, PLAYER_POS.len());
+        let mut players = Vec::new();
+        for (i, &role) in roles.iter().enumerate() {
+            let sprite_num = self.state.player_roles.sprite_num_in_battle[role];
+            let chunk = self.mkf.f.read_chunk_decompressed(sprite_num as u32)?;
+            let (x, y) = PLAYER_POS[num - 1][std::cmp::min(i, num - 1)];
+            players.push(BattlePlayer {
+                player_role: role,
+                action: BattleAction::Pass,
+                prev_action: BattleAction::Attack(None),
+                defending: false,
+                frame: None,
+                pos: Pos { x, y },
+                sprite: sprite_get_frames(&chunk)?,
+            });
+        }
+
+        // 背景和属性加成都来自当前设置的战场
+        let background = self.mkf.fbp.read_chunk_decompressed(self.state.num_battle_field as u32)?;
+        let battle_field = self.data.battle_field(self.state.num_battle_field as usize).copied().unwrap_or_default();
+
+        Ok(Battle {
+            enemy_team,
+            enemies,
+            players,
+            background,
+            screen_wave: battle_field.screen_wave,
+            is_boss,
+            auto_battle: self.auto_battle,
+            result: None,
+            current_actor: None,
+            blow: 0,
+            exp_gained: 0,
+            cash_gained: 0,
+        })
+    }
+
+    // 开始一场战斗，战斗结束后清除战斗中的状态和临时效果
+    pub fn start_battle(&mut self, enemy_team: u16, is_boss: bool) -> Result<BattleResult> {
+        let battle = self.load_battle(enemy_team, is_boss)?;
+
+        self.play_music(self.state.num_battle_music, true, 0.0)?;
+        self.fade_out(1)?;
+        self.battle = Some(battle);
+        self.battle_make_scene();
+        self.blit_to_screen()?;
+        self.fade_in(1)?;
+
+        let result = self.battle_main()?;
+        if result == BattleResult::Won {
+            self.battle_won()?;
+        }
+
+        for role in 0..MAX_PLAYER_ROLES {
+            self.state.clear_player_status(role);
+            self.state.cure_poison_by_level(role, 3);
+            self.state.remove_equipment_effect(role, BODY_PART_EXTRA);
+        }
+
+        self.fade_out(1)?;
+        self.battle = None;
+        self.auto_battle = false;
+        self.state.need_to_fade_in = true;
+        self.play_music(self.state.num_music, true, 1.0)?;
+
+        Ok(result)
+    }
+
+    pub fn battle_make_scene(&mut self) {
+        let ticks = self.ticks();
+        let battle = self.battle.as_ref().expect("not in battle");
+        let blow = battle.blow as isize;
+        let state = &self.state;
+        self.canvas.effects.wave.level = battle.screen_wave;
+
+        self.canvas.set_pixels(|pixels: &mut [u8]| {
+            pixels.copy_from_slice(&battle.background);
+
+            for enemy in battle.enemies.iter().filter(|enemy| enemy.is_alive()) {
+                // 待机动画
+                let frames = (enemy.enemy.idle_frames as usize).clamp(1, std::cmp::max(enemy.sprite.len(), 1));
+                let speed = std::cmp::max(enemy.enemy.idle_anim_speed as u32, 1) * 100;
+                if let Some(frame) = enemy.sprite.get(((ticks / speed) as usize) % frames) {
+                    let x = enemy.pos.x + blow - (frame.width as isize) / 2;
+                    let y = enemy.pos.y - (frame.height as isize);
+                    draw_sprite_frame(frame, pixels, 320, 200, x, y);
+                }
+            }
+
+            for player in battle.players.iter() {
+                // HP 不足五分之一时显示虚弱的动作
+                let role = player.player_role;
+                let weak = state.player_roles.hp[role] < state.player_roles.max_hp[role] / 5;
+                let frame = player.frame.filter(|&frame| frame < player.sprite.len()).unwrap_or(if weak { 1 } else { 0 });
+                if let Some(frame) = player.sprite.get(frame) {
+                    let x = player.pos.x - (frame.width as isize) / 2;
+                    let y = player.pos.y - (frame.height as isize);
+                    draw_sprite_frame(frame, pixels, 320, 200, x, y);
+                }
+            }
+        });
+
+        let roles: Vec<usize> = battle.players
+            .iter()
+            .map(|player| player.player_role)
+            .collect();
+        for (i, role) in roles.into_iter().enumerate() {
+            self.draw_player_hp_mp(role, Pos { x: 2 + 106 * (i as isize), y: 2 });
+        }
+    }
+
+    // 刷新战斗画面并等待 ms 毫秒
+    pub fn battle_delay(&mut self, ms: u32) -> Result<()> {
+        for _ in 0..std::cmp::max(ms / 30, 1) {
+            self.battle_make_scene();
+            self.blit_to_screen()?;
+            self.process_event()?;
+            self.delay(30);
+        }
+
+        Ok(())
+    }
+
+    // 队伍中第 index 个角色的施法动作：举手后停在施法的帧上，直到这次行动结束
+    pub fn battle_show_player_pre_magic(&mut self, index: usize) -> Result<()> {
+        for (frame, ms) in [(5, 150), (6, 100)] {
+            if let Some(player) = self.battle_mut().players.get_mut(index) {
+                player.frame = Some(frame);
+            }
+            self.battle_delay(ms)?;
+        }
+
+        Ok(())
+    }
+
+    // 在 pos 上方显示向上飘动的数字
+    fn battle_show_number(&mut self, value: u32, pos: Pos, color: NumColor) -> Result<()> {
+        for i in 0..10 {
+            self.battle_make_scene();
+            self.draw_number(value, 5, Pos { x: pos.x, y: pos.y - 10 - i * 2 }, color, NumAlign::Mid);
+            self.blit_to_screen()?;
+            self.process_event()?;
+            self.delay(30);
+        }
+
+        Ok(())
+    }
+
+    // 对战场上第 index 个敌人造成伤害
+    pub fn battle_damage_enemy(&mut self, index: usize, damage: i32) -> Result<()> {
+        let damage = damage.clamp(0, u16::MAX as i32) as u16;
+        let pos = match self.battle_mut().enemies.get_mut(index) {
+            Some(enemy) if enemy.is_alive() => {
+                enemy.enemy.health = enemy.enemy.health.saturating_sub(damage);
+                enemy.top()
+            }
+            _ => {
+                return Ok(());
+            }
+        };
+
+        self.battle_show_number(damage as u32, pos, NumColor::Yellow)
+    }
+
+    // 对队伍中第 index 个角色造成伤害
+    pub fn battle_damage_player(&mut self, index: usize, damage: i32) -> Result<()> {
+        let damage = damage.clamp(0, u16::MAX as i32) as u16;
+        let (role, pos) = match self.battle_ref().players.get(index) {
+            Some(player) => (player.player_role, player.pos),
+            None => {
+                return Ok(());
+            }
+        };
+
+        let hp = &mut self.state.player_roles.hp[role];
+        *hp = hp.saturating_sub(damage);
+        self.battle_show_number(damage as u32, Pos { x: pos.x, y: pos.y - 60 }, NumColor::Blue)
+    }
+
+    fn battle_main(&mut self) -> Result<BattleResult> {
+        loop {
+            self.battle_start_turn()?;
+            if let Some(result) = self.battle_result() {
+                return Ok(result);
+            }
+
+            self.battle_select_actions()?;
+            for actor in self.battle_turn_queue() {
+                match actor {
+                    Actor::Player(index) => self.battle_player_perform_action(index)?,
+                    Actor::Enemy(index) => self.battle_enemy_perform_action(index)?,
+                }
+                self.battle_post_action_check();
+                if let Some(result) = self.battle_result() {
+                    return Ok(result);
+                }
+            }
+
+            self.battle_end_turn()?;
+            self.battle_post_action_check();
+            if let Some(result) = self.battle_result() {
+                return Ok(result);
+            }
+        }
+    }
+
+    // 回合开始，执行敌人的回合开始脚本
+    fn battle_start_turn(&mut self) -> Result<()> {
+        for player in self.battle_mut().players.iter_mut() {
+            player.defending = false;
+            player.action = BattleAction::Pass;
+        }
+
+        for i in 0..self.battle_ref().enemies.len() {
+            let enemy = &self.battle_ref().enemies[i];
+            if !enemy.is_alive() {
+                continue;
+            }
+
+            let (script, object_id) = (enemy.script_on_turn_start, enemy.object_id);
+            let next = self.run_trigger_script(script, i as u16)?;
+            if let Some(enemy) = self.battle_mut().enemies.get_mut(i).filter(|enemy| enemy.object_id == object_id) {
+                enemy.script_on_turn_start = next;
+            }
+            if self.battle_result().is_some() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    // 为每个能行动的角色选择指令
+    fn battle_select_actions(&mut self) -> Result<()> {
+        self.process_event()?;
+        if self.input.is_pressed(PalKey::Menu) {
+            self.battle_mut().auto_battle = false;
+        }
+
+        let count = self.battle_ref().players.len();
+        let mut index = 0;
+        while index < count {
+            let role = self.battle_ref().players[index].player_role;
+            if !self.battle_player_can_act(role) {
+                self.battle_mut().players[index].action = BattleAction::Pass;
+                index += 1;
+                continue;
+            }
+
+            if self.state.party_state.status[role][Status::Confused as usize] > 0 {
+                self.battle_mut().players[index].action = BattleAction::AttackMate;
+                index += 1;
+                continue;
+            }
+
+            let command = if self.battle_ref().auto_battle {
+                BattleCommand::Force
+            } else {
+                self.battle_select_action(index)?
+            };
+
+            match command {
+                BattleCommand::Action(action) => {
+                    let player = &mut self.battle_mut().players[index];
+                    player.action = action;
+                    player.prev_action = action;
+                    index += 1;
+                }
+                BattleCommand::Back => {
+                    // 回到上一个能行动的角色
+                    while index > 0 {
+                        index -= 1;
+                        let role = self.battle_ref().players[index].player_role;
+                        if self.battle_player_can_act(role) {
+                            break;
+                        }
+                    }
+                }
+                BattleCommand::Repeat => {
+                    for player in self.battle_mut().players[index..].iter_mut() {
+                        player.action = player.prev_action;
+                    }
+                    break;
+                }
+                BattleCommand::Auto | BattleCommand::Force => {
+                    if matches!(command, BattleCommand::Auto) {
+                        self.battle_mut().auto_battle = true;
+                    }
+                    for player in self.battle_mut().players[index..].iter_mut() {
+                        player.action = BattleAction::Attack(None);
+                    }
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // 战斗指令界面：上为攻击，左为法术，下为其他
+    fn battle_select_action(&mut self, index: usize) -> Result<BattleCommand> {
+        let role = self.battle_ref().players[index].player_role;
+        let player_pos = self.battle_ref().players[index].pos;
+        let mut selected = 0;
+
+        loop {
+            self.battle_make_scene();
+
+            let blink = (self.ticks() / 300) % 2 == 1;
+            let sprite = &self.ui.sprite;
+            self.canvas.set_pixels(|pixels: &mut [u8]| {
+                let icons = [
+                    SPRITENUM_BATTLEICON_ATTACK,
+                    SPRITENUM_BATTLEICON_MAGIC,
+                    SPRITENUM_BATTLEICON_COOPMAGIC,
+                    SPRITENUM_BATTLEICON_MISCMENU,
+                ];
+                for (i, (&icon, &(x, y))) in icons.iter().zip(BATTLEICON_POS.iter()).enumerate() {
+                    if i == selected && blink {
+                        continue;
+                    }
+                    if let Some(frame) = sprite.get(icon) {
+                        draw_sprite_frame(frame, pixels, 320, 200, x, y);
+                    }
+                }
+                if let Some(frame) = sprite.get(SPRITENUM_BATTLE_ARROW_CURRENTPLAYER) {
+                    draw_sprite_frame(frame, pixels, 320, 200, player_pos.x - 8, player_pos.y - 74);
+                }
+            });
+
+            self.blit_to_screen()?;
+            self.process_event()?;
+
+            if self.input.is_pressed(PalKey::Up) {
+                selected = 0;
+            } else if self.input.is_pressed(PalKey::Left) {
+                selected = 1;
+            } else if self.input.is_pressed(PalKey::Down) {
+                selected = 3;
+            }
+
+            let action = if self.input.is_pressed(PalKey::Search) {
+                match selected {
+                    0 => self.battle_select_attack(role)?,
+                    1 => self.battle_select_magic(index)?,
+                    3 => {
+                        match self.battle_misc_menu()? {
+                            Some(command) => {
+                                return Ok(command);
+                            }
+                            None => None,
+                        }
+                    }
+                    _ => None,
+                }
+            } else if self.input.is_pressed(PalKey::Menu) {
+                return Ok(BattleCommand::Back);
+            } else if self.input.is_pressed(PalKey::Repeat) {
+                return Ok(BattleCommand::Repeat);
+            } else if self.input.is_pressed(PalKey::Auto) {
+                return Ok(BattleCommand::Auto);
+            } else if self.input.is_pressed(PalKey::Force) {
+                return Ok(BattleCommand::Force);
+            } else if self.input.is_pressed(PalKey::Defend) {
+                Some(BattleAction::Defend)
+            } else if self.input.is_pressed(PalKey::Flee) {
+                Some(BattleAction::Flee)
+            } else if self.input.is_pressed(PalKey::UseItem) {
+                self.battle_select_use_item()?
+            } else if self.input.is_pressed(PalKey::ThrowItem) {
+                self.battle_select_throw_item()?
+            } else if self.input.is_pressed(PalKey::Status) {
+                self.player_status()?;
+                None
+            } else {
+                None
+            };
+
+            if let Some(action) = action {
+                return Ok(BattleCommand::Action(action));
+            }
+
+            self.delay(30);
+        }
+    }
+
+    fn battle_select_attack(&mut self, player_role: usize) -> Result<Option<BattleAction>> {
+        // 可以攻击全体的角色不需要选择目标
+        if self.state.player_roles.attack_all[player_role] != 0 {
+            return Ok(Some(BattleAction::Attack(None)));
+        }
+
+        Ok(self.battle_select_enemy()?.map(|target| BattleAction::Attack(Some(target))))
+    }
+
+    fn battle_select_magic(&mut self, index: usize) -> Result<Option<BattleAction>> {
+        let role = self.battle_ref().players[index].player_role;
+        if self.state.party_state.status[role][Status::Silence as usize] > 0 {
+            return Ok(None);
+        }
+
+        let default_magic = match self.battle_ref().players[index].prev_action {
+            BattleAction::Magic { magic, .. } => magic,
+            _ => 0,
+        };
+
+        self.battle_make_scene();
+        let magic = self.magic_selection_menu(role, true, default_magic)?;
+        if magic == 0 {
+            return Ok(None);
+        }
+
+        let flags = self.state.object_magic(magic).map_or(0, |object| object.flags);
+        let target = if flags & MAGIC_FLAG_APPLY_TO_ALL != 0 {
+            None
+        } else {
+            let target = if flags & MAGIC_FLAG_USABLE_TO_ENEMY != 0 {
+                self.battle_select_enemy()?
+            } else {
+                self.battle_make_scene();
+                self.select_party_member(false)?
+            };
+            match target {
+                Some(target) => Some(target),
+                None => {
+                    return Ok(None);
+                }
+            }
+        };
+
+        Ok(Some(BattleAction::Magic { magic, target }))
+    }
+
+    fn battle_select_use_item(&mut self) -> Result<Option<BattleAction>> {
+        self.battle_make_scene();
+        let item = self.item_select_menu(ITEM_FLAG_USABLE, |_, _| Ok(()))?;
+        if item == 0 {
+            return Ok(None);
+        }
+
+        let flags = self.state.object_item(item).map_or(0, |object| object.flags);
+        if flags & ITEM_FLAG_APPLY_TO_ALL != 0 {
+            return Ok(Some(BattleAction::UseItem { item, target: None }));
+        }
+
+        self.battle_make_scene();
+        Ok(self.select_party_member(false)?.map(|target| BattleAction::UseItem { item, target: Some(target) }))
+    }
+
+    fn battle_select_throw_item(&mut self) -> Result<Option<BattleAction>> {
+        self.battle_make_scene();
+        let item = self.item_select_menu(ITEM_FLAG_THROWABLE, |_, _| Ok(()))?;
+        if item == 0 {
+            return Ok(None);
+        }
+
+        let flags = self.state.object_item(item).map_or(0, |object| object.flags);
+        if flags & ITEM_FLAG_APPLY_TO_ALL != 0 {
+            return Ok(Some(BattleAction::ThrowItem { item, target: None }));
+        }
+
+        Ok(self.battle_select_enemy()?.map(|target| BattleAction::ThrowItem { item, target: Some(target) }))
+    }
+
+    // 其他指令：自动、物品、防御、逃跑、状态
+    fn battle_misc_menu(&mut self) -> Result<Option<BattleCommand>> {
+        let menu_items = [
+            MenuItem { value: 1, num_word: BATTLEUI_LABEL_AUTO, enabled: true, x: 16, y: 32 },
+            MenuItem { value: 2, num_word: BATTLEUI_LABEL_INVENTORY, enabled: true, x: 16, y: 32 + 18 },
+            MenuItem { value: 3, num_word: BATTLEUI_LABEL_DEFEND, enabled: true, x: 16, y: 32 + 36 },
+            MenuItem { value: 4, num_word: BATTLEUI_LABEL_FLEE, enabled: true, x: 16, y: 32 + 54 },
+            MenuItem { value: 5, num_word: BATTLEUI_LABEL_STATUS, enabled: true, x: 16, y: 32 + 72 },
+        ];
+
+        self.battle_make_scene();
+        self.draw_box(Pos { x: 2, y: 20 }, 4, 1);
+        let action = match self.read_menu_with(&menu_items, 0, |_, _| Ok(()))? {
+            1 => {
+                return Ok(Some(BattleCommand::Auto));
+            }
+            2 => {
+                let menu_items = [
+                    MenuItem { value: 1, num_word: BATTLEUI_LABEL_USEITEM, enabled: true, x: 58, y: 74 },
+                    MenuItem { value: 2, num_word: BATTLEUI_LABEL_THROWITEM, enabled: true, x: 58, y: 74 + 18 },
+                ];
+                self.draw_box(Pos { x: 44, y: 62 }, 1, 1);
+                match self.read_menu_with(&menu_items, 0, |_, _| Ok(()))? {
+                    1 => self.battle_select_use_item()?,
+                    2 => self.battle_select_throw_item()?,
+                    _ => None,
+                }
+            }
+            3 => Some(BattleAction::Defend),
+            4 => Some(BattleAction::Flee),
+            5 => {
+                self.player_status()?;
+                None
+            }
+            _ => None,
+        };
+
+        Ok(action.map(BattleCommand::Action))
+    }
+
+    // 选择一个存活的敌人，取消时返回 None
+    fn battle_select_enemy(&mut self) -> Result<Option<usize>> {
+        let alive = self.battle_alive_enemies();
+        if alive.is_empty() {
+            return Ok(None);
+        }
+
+        let mut k = 0;
+        loop {
+            let enemy = &self.battle_ref().enemies[alive[k]];
+            let (object_id, top) = (enemy.object_id, enemy.top());
+
+            self.battle_make_scene();
+            let color = self.menu_color_selected();
+            self.draw_word_at(object_id as u32, Pos { x: top.x - 20, y: top.y - 16 }, color);
+            self.blit_to_screen()?;
+            self.process_event()?;
+
+            if self.input.is_pressed(PalKey::Left) || self.input.is_pressed(PalKey::Up) {
+                k = (k + alive.len() - 1) % alive.len();
+            } else if self.input.is_pressed(PalKey::Right) || self.input.is_pressed(PalKey::Down) {
+                k = (k + 1) % alive.len();
+            } else if self.input.is_pressed(PalKey::Search) {
+                return Ok(Some(alive[k]));
+            } else if self.input.is_pressed(PalKey::Menu) {
+                return Ok(None);
+            }
+
+            self.delay(30);
+        }
+    }
+
+    // 按身法决定本回合的行动顺序，防御总是最先生效
+    fn battle_turn_queue(&mut self) -> Vec<Actor> {
+        let battle = self.battle.as_ref().expect("not in battle");
+        let state = &mut self.state;
+        let mut speeds = Vec::new();
+
+        for (i, enemy) in battle.enemies.iter().enumerate() {
+            if !enemy.can_act() {
+                continue;
+            }
+
+            let dexterity = (enemy.enemy.level as i32 + 6) * 3 + (enemy.enemy.dexterity as i32);
+            let speed = ((dexterity as f32) * state.rng.random_float(0.9, 1.1)) as i32;
+            speeds.push((Actor::Enemy(i), speed));
+            if enemy.enemy.dual_move != 0 {
+                speeds.push((Actor::Enemy(i), speed / 2));
+            }
+        }
+
+        for (i, player) in battle.players.iter().enumerate() {
+            let role = player.player_role;
+            let speed = match player.action {
+                BattleAction::Pass => {
+                    continue;
+                }
+                BattleAction::Defend => i32::MAX,
+                _ => {
+                    let mut dexterity = state.player_stat(ATTR_DEXTERITY, role);
+                    if state.party_state.status[role][Status::Haste as usize] > 0 {
+                        dexterity = std::cmp::min(dexterity.saturating_mul(3), 999);
+                    }
+                    ((dexterity as f32) * state.rng.random_float(0.9, 1.1)) as i32
+                }
+            };
+            speeds.push((Actor::Player(i), speed));
+        }
+
+        turn_order(&speeds)
+    }
+
+    fn battle_player_attack_damage(&mut self, player_role: usize, enemy_index: usize) -> i32 {
+        let enemy = self.battle_ref().enemies[enemy_index].enemy;
+        let attack = self.state.player_stat(ATTR_ATTACK_STRENGTH, player_role);
+        let defense = enemy.defense.saturating_add((enemy.level + 6).saturating_mul(4));
+
+        let mut damage = calc_physical_attack_damage(attack, defense, enemy.physical_resistance) + self.state.rng.random_long(1, 2);
+
+        // 会心一击
+        if self.state.rng.random_long(0, 5) == 0 || self.state.party_state.status[player_role][Status::Bravery as usize] > 0 {
+            damage *= 3;
+        }
+        if player_role == 0 && self.state.rng.random_long(0, 11) == 0 {
+            damage *= 2;
+        }
+
+        std::cmp::max(((damage as f32) * self.state.rng.random_float(1.0, 1.125)) as i32, 1)
+    }
+
+    fn battle_player_perform_action(&mut self, index: usize) -> Result<()> {
+        let (role, action) = {
+            let player = &self.battle_ref().players[index];
+            (player.player_role, player.action)
+        };
+        if !self.battle_player_can_act(role) {
+            return Ok(());
+        }
+        self.battle_mut().current_actor = Some(Actor::Player(index));
+
+        match action {
+            BattleAction::Pass => {}
+            BattleAction::Defend => {
+                self.battle_mut().players[index].defending = true;
+            }
+            BattleAction::Attack(target) => {
+                let times = if self.state.party_state.status[role][Status::DualAttack as usize] > 0 { 2 } else { 1 };
+                for _ in 0..times {
+                    for enemy_index in self.battle_enemy_targets(target) {
+                        let damage = self.battle_player_attack_damage(role, enemy_index);
+                        self.battle_damage_enemy(enemy_index, damage)?;
+                    }
+                }
+            }
+            BattleAction::AttackMate => {
+                // 混乱时随机攻击一名队友
+                let mates: Vec<usize> = self
+                    .battle_alive_players()
+                    .into_iter()
+                    .filter(|&i| i != index)
+                    .collect();
+                if !mates.is_empty() {
+                    let target = mates[self.state.rng.random_long(0, (mates.len() as i32) - 1) as usize];
+                    let target_role = self.battle_ref().players[target].player_role;
+                    let attack = self.state.player_stat(ATTR_ATTACK_STRENGTH, role);
+                    let defense = self.state.player_stat(ATTR_DEFENSE, target_role);
+                    let damage = std::cmp::max(calc_physical_attack_damage(attack, defense, 2), 1);
+                    self.battle_damage_player(target, damage)?;
+                }
+            }
+            BattleAction::Magic { magic, target } => {
+                self.battle_player_use_magic(index, magic, target)?;
+            }
+            BattleAction::UseItem { item, target } => {
+                let target_role = target
+                    .and_then(|target| self.battle_ref().players.get(target))
+                    .map_or(role, |player| player.player_role);
+                self.use_item(item, target_role as u16)?;
+            }
+            BattleAction::ThrowItem { item, target } => {
+                let target = match target {
+                    Some(_) => self.battle_enemy_targets(target).first().map_or(0xffff, |&i| i as u16),
+                    None => 0xffff,
+                };
+                self.throw_item(item, target)?;
+            }
+            BattleAction::Flee => {
+                let flee_rate = self.state.player_stat(ATTR_FLEE_RATE, role) as i32;
+                let enemies = self.battle_alive_enemies();
+                let defense: i32 = enemies
+                    .iter()
+                    .map(|&i| {
+                        let enemy = &self.battle_ref().enemies[i].enemy;
+                        (enemy.dexterity as i32) + (enemy.level as i32 + 6) * 4
+                    })
+                    .sum();
+
+                if !self.battle_ref().is_boss && self.state.rng.random_long(0, flee_rate) >= self.state.rng.random_long(0, defense) {
+                    self.battle_mut().result = Some(BattleResult::Fled);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn battle_magic_damage_to_enemy(&mut self, player_role: usize, magic: &Magic, enemy_index: usize) -> i32 {
+        let enemy = self.battle_ref().enemies[enemy_index].enemy;
+        let strength = self.state.player_stat(ATTR_MAGIC_STRENGTH, player_role);
+        let strength = ((strength as f32) * self.state.rng.random_float(1.0, 1.1)) as u16;
+        let defense = enemy.defense.saturating_add((enemy.level + 6).saturating_mul(4));
+        let battle_field = self.data.battle_field(self.state.num_battle_field as usize).copied().unwrap_or_default();
+
+        std::cmp::max(
+            calc_magic_damage(
+                strength,
+                defense,
+                &enemy.elem_resistance,
+                enemy.poison_resistance,
+                ENEMY_RESISTANCE_MULTIPLIER,
+                magic,
+                &battle_field
+            ),
+            0
+        )
+    }
+
+    // 角色在战斗中施法：先执行使用脚本，成功后对目标执行成功脚本并造成伤害
+    fn battle_player_use_magic(&mut self, index: usize, magic: u16, target: Option<usize>) -> Result<()> {
+        let role = self.battle_ref().players[index].player_role;
+        let object = match self.state.object_magic(magic) {
+            Some(object) => object,
+            None => {
+                return Ok(());
+            }
+        };
+
+        let cost = self.magic_cost(magic);
+        if self.state.player_roles.mp[role] < cost {
+            return Ok(());
+        }
+
+        let to_enemy = object.flags & MAGIC_FLAG_USABLE_TO_ENEMY != 0;
+        let target_id = match target {
+            Some(_) if to_enemy => self.battle_enemy_targets(target).first().map_or(0xffff, |&i| i as u16),
+            Some(target) => self.battle_ref().players.get(target).map_or(role, |player| player.player_role) as u16,
+            None if to_enemy => 0xffff,
+            None => 0,
+        };
+
+        let script_on_use = self.run_trigger_script(object.script_on_use, role as u16)?;
+        self.update_magic(magic, |object| object.script_on_use = script_on_use);
+        if !self.script_success {
+            return Ok(());
+        }
+
+        let object = self.state.object_magic(magic).unwrap_or(object);
+        let script_on_success = self.run_trigger_script(object.script_on_success, target_id)?;
+        self.update_magic(magic, |object| object.script_on_success = script_on_success);
+
+        if self.script_success && to_enemy {
+            if let Some(data) = self.data.magic(object.magic_number as usize).copied() {
+                if (data.base_damage as i16) > 0 {
+                    for enemy_index in self.battle_enemy_targets(target) {
+                        let damage = self.battle_magic_damage_to_enemy(role, &data, enemy_index);
+                        self.battle_damage_enemy(enemy_index, damage)?;
+                    }
+                }
+            }
+        }
+
+        let mp = &mut self.state.player_roles.mp[role];
+        *mp = mp.saturating_sub(cost);
+
+        Ok(())
+    }
+
+    fn battle_enemy_perform_action(&mut self, index: usize) -> Result<()> {
+        if !self.battle_ref().enemies.get(index).is_some_and(|enemy| enemy.can_act()) {
+            return Ok(());
+        }
+        self.battle_mut().current_actor = Some(Actor::Enemy(index));
+
+        let enemy = &self.battle_ref().enemies[index];
+        let (script, object_id) = (enemy.script_on_ready, enemy.object_id);
+        let next = self.run_trigger_script(script, index as u16)?;
+        match self.battle_mut().enemies.get_mut(index) {
+            Some(enemy) if enemy.is_alive() => {
+                // 脚本中变身时保留新形态的脚本
+                if enemy.object_id == object_id {
+                    enemy.script_on_ready = next;
+                }
+            }
+            _ => {
+                return Ok(());
+            }
+        }
+        if self.battle_result().is_some() {
+            return Ok(());
+        }
+
+        let enemy = self.battle_ref().enemies[index].enemy;
+        let status = self.battle_ref().enemies[index].status;
+
+        if status[Status::Confused as usize] > 0 {
+            // 混乱时随机攻击其他敌人
+            let others: Vec<usize> = self
+                .battle_alive_enemies()
+                .into_iter()
+                .filter(|&i| i != index)
+                .collect();
+            if !others.is_empty() {
+                let target = others[self.state.rng.random_long(0, (others.len() as i32) - 1) as usize];
+                let target_enemy = self.battle_ref().enemies[target].enemy;
+                let attack = enemy.attack_strength.saturating_add((enemy.level + 6).saturating_mul(6));
+                let defense = target_enemy.defense.saturating_add((target_enemy.level + 6).saturating_mul(4));
+                let damage = calc_physical_attack_damage(attack, defense, target_enemy.physical_resistance);
+                self.battle_damage_enemy(target, std::cmp::max(damage, 1))?;
+            }
+            return Ok(());
+        }
+
+        if
+            enemy.magic != 0 &&
+            self.state.rng.random_long(0, 9) < (enemy.magic_rate as i32) &&
+            status[Status::Silence as usize] == 0
+        {
+            self.battle_enemy_use_magic(index, enemy.magic)
+        } else {
+            self.battle_enemy_attack(index)
+        }
+    }
+
+    fn battle_enemy_attack(&mut self, index: usize) -> Result<()> {
+        let alive = self.battle_alive_players();
+        if alive.is_empty() {
+            return Ok(());
+        }
+
+        let target = alive[self.state.rng.random_long(0, (alive.len() as i32) - 1) as usize];
+        let (role, defending) = {
+            let player = &self.battle_ref().players[target];
+            (player.player_role, player.defending)
+        };
+        let enemy = self.battle_ref().enemies[index].enemy;
+
+        let attack = enemy.attack_strength.saturating_add((enemy.level + 6).saturating_mul(6));
+        let mut defense = self.state.player_stat(ATTR_DEFENSE, role);
+        if defending {
+            defense = defense.saturating_mul(2);
+        }
+
+        let attack = attack.saturating_add(self.state.rng.random_long(0, 2) as u16);
+        let mut damage = calc_physical_attack_damage(attack, defense, 2) + self.state.rng.random_long(0, 1);
+        if self.state.party_state.status[role][Status::Protect as usize] > 0 {
+            damage /= 2;
+        }
+        self.battle_damage_player(target, std::cmp::max(damage, 1))?;
+
+        // 普通攻击附带的物品效果，例如中毒
+        if enemy.attack_equiv_item != 0 && (enemy.attack_equiv_item_rate as i32) >= self.state.rng.random_long(1, 10) {
+            if let Some(item) = self.state.object_item(enemy.attack_equiv_item) {
+                let script_on_use = self.run_trigger_script(item.script_on_use, role as u16)?;
+                self.update_item(enemy.attack_equiv_item, |item| item.script_on_use = script_on_use);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn battle_enemy_use_magic(&mut self, index: usize, magic: u16) -> Result<()> {
+        let object = match self.state.object_magic(magic) {
+            Some(object) => object,
+            None => {
+                return Ok(());
+            }
+        };
+        let data = match self.data.magic(object.magic_number as usize).copied() {
+            Some(data) => data,
+            None => {
+                return Ok(());
+            }
+        };
+
+        let alive = self.battle_alive_players();
+        if alive.is_empty() {
+            return Ok(());
+        }
+
+        // 法术类型 1～3 作用于全体
+        let targets = if object.flags & MAGIC_FLAG_APPLY_TO_ALL != 0 || (1..=3).contains(&data.magic_type) {
+            alive
+        } else {
+            vec![alive[self.state.rng.random_long(0, (alive.len() as i32) - 1) as usize]]
+        };
+
+        let script_on_use = self.run_trigger_script(object.script_on_use, index as u16)?;
+        self.update_magic(magic, |object| object.script_on_use = script_on_use);
+        if !self.script_success {
+            return Ok(());
+        }
+
+        let enemy = self.battle_ref().enemies[index].enemy;
+        let battle_field = self.data.battle_field(self.state.num_battle_field as usize).copied().unwrap_or_default();
+        for target in targets {
+            let (role, defending) = {
+                let player = &self.battle_ref().players[target];
+                (player.player_role, player.defending)
+            };
+
+            let object = self.state.object_magic(magic).unwrap_or(object);
+            let script_on_success = self.run_trigger_script(object.script_on_success, role as u16)?;
+            self.update_magic(magic, |object| object.script_on_success = script_on_success);
+
+            if (data.base_damage as i16) <= 0 {
+                continue;
+            }
+
+            let strength = enemy.magic_strength.saturating_add((enemy.level + 6).saturating_mul(6));
+            let strength = ((strength as f32) * self.state.rng.random_float(1.0, 1.1)) as u16;
+            let defense = self.state.player_stat(ATTR_DEFENSE, role);
+            let elemental_resistance = std::array::from_fn(|i| {
+                self.state.player_stat(ATTR_ELEMENTAL_RESISTANCE + i, role)
+            });
+            let poison_resistance = self.state.player_stat(ATTR_POISON_RESISTANCE, role);
+
+            let mut damage = calc_magic_damage(
+                strength,
+                defense,
+                &elemental_resistance,
+                poison_resistance,
+                PLAYER_RESISTANCE_MULTIPLIER,
+                &data,
+                &battle_field
+            );
+            if defending {
+                damage /= 2;
+            }
+            if self.state.party_state.status[role][Status::Protect as usize] > 0 {
+                damage /= 2;
+            }
+            self.battle_damage_player(target, std::cmp::max(damage, 0))?;
+        }
+
+        Ok(())
+    }
+
+    // 回合结束，执行毒的脚本并减少状态的剩余回合数
+    fn battle_end_turn(&mut self) -> Result<()> {
+        let roles: Vec<usize> = self.battle_ref()
+            .players.iter()
+            .map(|player| player.player_role)
+            .collect();
+        for (i, &role) in roles.iter().enumerate() {
+            for k in 0..MAX_POISONS {
+                let poison = self.state.poison_status[k][i];
+                if poison.poison_id == 0 || self.state.player_roles.hp[role] == 0 {
+                    continue;
+                }
+                let next = self.run_trigger_script(poison.poison_script, role as u16)?;
+                self.state.poison_status[k][i].poison_script = next;
+            }
+            self.state.decrease_player_status_rounds(role);
+        }
+
+        for i in 0..self.battle_ref().enemies.len() {
+            for k in 0..MAX_POISONS {
+                let poison = match self.battle_ref().enemies.get(i) {
+                    Some(enemy) if enemy.is_alive() => enemy.poisons[k],
+                    _ => {
+                        break;
+                    }
+                };
+                if poison.poison_id == 0 {
+                    continue;
+                }
+                let next = self.run_trigger_script(poison.poison_script, i as u16)?;
+                if let Some(enemy) = self.battle_mut().enemies.get_mut(i) {
+                    enemy.poisons[k].poison_script = next;
+                }
+            }
+
+            if let Some(enemy) = self.battle_mut().enemies.get_mut(i) {
+                for value in enemy.status.iter_mut() {
+                    *value = value.saturating_sub(1);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // 清理被击倒的敌人并判断胜负
+    fn battle_post_action_check(&mut self) {
+        let battle = self.battle_mut();
+        battle.blow = 0;
+        for player in battle.players.iter_mut() {
+            player.frame = None;
+        }
+        for enemy in battle.enemies.iter_mut() {
+            if enemy.object_id != 0 && enemy.enemy.health == 0 {
+                battle.exp_gained += enemy.enemy.exp as u32;
+                battle.cash_gained += enemy.enemy.cash as u32;
+                enemy.object_id = 0;
+            }
+        }
+
+        if self.battle_result().is_some() {
+            return;
+        }
+
+        if self.battle_alive_enemies().is_empty() {
+            self.battle_mut().result = Some(BattleResult::Won);
+        } else if self.battle_alive_players().is_empty() {
+            self.battle_mut().result = Some(BattleResult::Lost);
+        }
+    }
+
+    fn battle_wait_for_key(&mut self) -> Result<()> {
+        self.blit_to_screen()?;
+        loop {
+            self.process_event()?;
+            if self.input.is_pressed(PalKey::Search) || self.input.is_pressed(PalKey::Menu) {
+                break;
+            }
+            self.delay(30);
+        }
+
+        Ok(())
+    }
+
+    // 战斗胜利：获得金钱和经验，升级并学会新的法术，最后执行敌人的战斗结束脚本
+    fn battle_won(&mut self) -> Result<()> {
+        let (exp_gained, cash_gained) = {
+            let battle = self.battle_ref();
+            (battle.exp_gained, battle.cash_gained)
+        };
+        self.state.cash = self.state.cash.saturating_add(cash_gained);
+
+        self.battle_make_scene();
+        self.draw_signle_linebox_with_shadow(Pos { x: 83, y: 60 }, 8);
+        self.draw_word_at(BATTLEWIN_GETEXP_LABEL, Pos { x: 95, y: 70 }, MENUITEM_COLOR);
+        self.draw_number(exp_gained, 5, Pos { x: 182, y: 74 }, NumColor::Yellow, NumAlign::Right);
+        self.draw_signle_linebox_with_shadow(Pos { x: 65, y: 105 }, 10);
+        self.draw_word_at(BATTLEWIN_BEATENEMY_LABEL, Pos { x: 77, y: 115 }, MENUITEM_COLOR);
+        self.draw_number(cash_gained, 5, Pos { x: 162, y: 119 }, NumColor::Yellow, NumAlign::Mid);
+        self.draw_word_at(BATTLEWIN_DOLLAR_LABEL, Pos { x: 180, y: 115 }, MENUITEM_COLOR);
+        self.battle_wait_for_key()?;
+
+        for role in self.state.party_roles() {
+            if self.state.player_roles.hp[role] == 0 {
+                continue;
+            }
+
+            let mut exp = (self.state.exp.primary_exp[role].exp as u32) + exp_gained;
+            let mut level_up = false;
+            loop {
+                let level = self.state.player_roles.level[role] as usize;
+                let needed = self.data.exp_for_level(level).unwrap_or(0) as u32;
+                if level >= MAX_LEVELS || needed == 0 || exp < needed {
+                    break;
+                }
+
+                exp -= needed;
+                self.state.player_level_up(role, 1);
+                level_up = true;
+            }
+            self.state.exp.primary_exp[role].exp = std::cmp::min(exp, u16::MAX as u32) as u16;
+
+            if !level_up {
+                continue;
+            }
+
+            let roles = &mut self.state.player_roles;
+            roles.hp[role] = roles.max_hp[role];
+            roles.mp[role] = roles.max_mp[role];
+
+            let name = roles.name[role] as u32;
+            self.battle_make_scene();
+            self.draw_signle_linebox_with_shadow(Pos { x: 80, y: 0 }, 10);
+            self.draw_word_at(name, Pos { x: 95, y: 10 }, MENUITEM_COLOR);
+            self.draw_word_at(BATTLEWIN_LEVELUP_LABEL, Pos { x: 145, y: 10 }, MENUITEM_COLOR);
+            self.battle_wait_for_key()?;
+
+            // 学会新的法术
+            let level = self.state.player_roles.level[role];
+            let magics: Vec<u16> = self.data.level_up_magic
+                .as_slice()
+                .iter()
+                .filter_map(|entry| entry.m.get(role))
+                .filter(|entry| entry.level != 0 && entry.level <= level && entry.magic != 0)
+                .map(|entry| entry.magic)
+                .collect();
+            for magic in magics {
+                if !self.state.add_magic(role, magic) {
+                    continue;
+                }
+                self.battle_make_scene();
+                self.draw_signle_linebox_with_shadow(Pos { x: 65, y: 105 }, 10);
+                self.draw_word_at(name, Pos { x: 75, y: 115 }, MENUITEM_COLOR);
+                self.draw_word_at(BATTLEWIN_ADDMAGIC_LABEL, Pos { x: 75 + 48, y: 115 }, MENUITEM_COLOR);
+                self.draw_word_at(magic as u32, Pos { x: 75 + 96, y: 115 }, 0x1b);
+                self.battle_wait_for_key()?;
+            }
+        }
+
+        for i in 0..self.battle_ref().enemies.len() {
+            let enemy = &self.battle_ref().enemies[i];
+            let (script, object_id) = (enemy.script_on_battle_end, enemy.object_id);
+            let next = self.run_trigger_script(script, i as u16)?;
+            if let Some(enemy) = self.battle_mut().enemies.get_mut(i).filter(|enemy| enemy.object_id == object_id) {
+                enemy.script_on_battle_end = next;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,4 +1,6 @@
-#[derive(Clone, Copy)]
+use crate::video::PostEffects;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color(pub u32);
 impl Color {
     /// Create a new Color from RGB values.
@@ -23,6 +25,12 @@ pub struct Palette {
     pub colors: Vec<Color>,
 }
 
+impl Default for Palette {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Palette {
     pub fn new() -> Self {
         Self { colors: vec![Color(0); 256] }
@@ -31,12 +39,55 @@ impl Palette {
     pub fn with_colors(colors: Vec<Color>) -> Self {
         Self { colors }
     }
+
+    // 所有颜色都相同的调色板
+    pub fn filled(color: Color) -> Self {
+        Self { colors: vec![color; 256] }
+    }
+
+    // 按 num / den 的比例从当前调色板混合到 other
+    pub fn blend(&self, other: &Palette, num: u32, den: u32) -> Self {
+        let mix = |a: u8, b: u8| (((a as u32) * (den - num) + (b as u32) * num) / den) as u8;
+        let colors = self.colors
+            .iter()
+            .zip(other.colors.iter())
+            .map(|(c1, c2)| {
+                let (r1, g1, b1) = c1.to_rgb();
+                let (r2, g2, b2) = c2.to_rgb();
+                Color::from_rgb(mix(r1, r2), mix(g1, g2), mix(b1, b2))
+            })
+            .collect();
+
+        Self { colors }
+    }
+
+    // 每个颜色分量乘以 num / den，用于渐变
+    pub fn scaled(&self, num: u32, den: u32) -> Self {
+        let colors = self.colors
+            .iter()
+            .map(|c| {
+                let (r, g, b) = c.to_rgb();
+                Color::from_rgb(
+                    (((r as u32) * num) / den) as u8,
+                    (((g as u32) * num) / den) as u8,
+                    (((b as u32) * num) / den) as u8
+                )
+            })
+            .collect();
+
+        Self { colors }
+    }
 }
 
 pub struct Canvas {
     palette: Palette,
     pixels: Vec<u8>,
+    backup: Vec<u8>, // 备份的画面
+    post: Vec<u8>, // 后期处理后的画面
     buffer: Vec<u32>,
+    width: usize,
+    height: usize,
+    pub effects: PostEffects,
 }
 
 // INDEX8 canvas
@@ -45,7 +96,12 @@ impl Canvas {
         Self {
             palette: Palette::new(),
             pixels: vec![0; width * height],
+            backup: vec![0; width * height],
+            post: vec![0; width * height],
             buffer: vec![0; width * height],
+            width,
+            height,
+            effects: PostEffects::default(),
         }
     }
 
@@ -53,6 +109,10 @@ impl Canvas {
         self.palette = palette.clone();
     }
 
+    pub fn get_palette(&self) -> &Palette {
+        &self.palette
+    }
+
     pub fn set_pixels<F: FnOnce(&mut [u8])>(&mut self, f: F) {
         f(&mut self.pixels);
     }
@@ -61,9 +121,30 @@ impl Canvas {
         &self.pixels
     }
 
+    // 备份当前画面
+    pub fn backup_screen(&mut self) {
+        self.backup.copy_from_slice(&self.pixels);
+    }
+
+    // 恢复备份的画面
+    pub fn restore_screen(&mut self) {
+        self.pixels.copy_from_slice(&self.backup);
+    }
+
+    pub fn get_backup(&self) -> &[u8] {
+        &self.backup
+    }
+
     pub fn get_buffer(&mut self) -> &[u32] {
-        for i in 0..self.pixels.len() {
-            self.buffer[i] = self.palette.colors[self.pixels[i] as usize].0;
+        let pixels = if self.effects.is_active() {
+            self.effects.apply(&self.pixels, &mut self.post, self.width, self.height);
+            &self.post
+        } else {
+            &self.pixels
+        };
+
+        for (color, &index) in self.buffer.iter_mut().zip(pixels.iter()) {
+            *color = self.palette.colors[index as usize].0;
         }
 
         &self.buffer
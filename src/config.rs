@@ -0,0 +1,312 @@
+use std::collections::BTreeMap;
+use std::path::{ Path, PathBuf };
+
+use serde::{ Deserialize, Deserializer, Serialize };
+
+use crate::audio::{ MusicType, Volume };
+use crate::encoding::TextEncoding;
+use crate::input::{ default_gamepad_names, default_key_names, gamepad_bindings, key_bindings };
+use crate::utils::Result;
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+// 声音输出到哪里
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioOutput {
+    Device, // 系统声卡，需要 audio-device 特性
+    Null, // 丢弃，用于没有声卡的环境
+    Wav(PathBuf), // 录制到 WAV 文件
+}
+
+impl Default for AudioOutput {
+    fn default() -> Self {
+        if cfg!(feature = "audio-device") {
+            AudioOutput::Device
+        } else {
+            AudioOutput::Null
+        }
+    }
+}
+
+// 游戏的设置，保存在用户配置目录的 config.toml 中，命令行参数可以覆盖
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub data_dir: PathBuf, // 游戏数据文件和存档所在的目录
+    pub window_scale: u32, // 窗口放大倍数：1、2、4、8、16、32，0 表示适应屏幕
+    pub skip_intro: bool, // 跳过开场动画
+    pub music_enabled: bool,
+    pub sound_enabled: bool,
+    pub music_type: MusicType,
+    pub audio_output: AudioOutput,
+    pub volume: Volume,
+    pub text_encoding: TextEncoding, // 文字数据的编码，auto 表示自动识别
+    pub fallback_font: Option<PathBuf>, // 补充 WOR16 中没有的字形的 BDF 或 TTF 字体
+    #[serde(deserialize_with = "merge_key_names")]
+    pub keys: BTreeMap<String, Vec<String>>, // 按键名 -> 键盘按键
+    #[serde(deserialize_with = "merge_gamepad_names")]
+    pub gamepad: BTreeMap<String, Vec<String>>, // 按键名 -> 手柄按钮
+    #[serde(skip)]
+    pub headless: bool, // 不打开窗口
+    #[serde(skip)]
+    pub record: Option<PathBuf>, // 录像保存到这里
+    #[serde(skip)]
+    pub replay: Option<PathBuf>, // 回放这个录像
+    #[serde(skip)]
+    pub check_text: bool, // 只输出文字编码的诊断报告
+    #[serde(skip)]
+    pub export_text: Option<PathBuf>, // 导出翻译文件
+    #[serde(skip)]
+    pub import_text: Option<PathBuf>, // 导入翻译文件
+    #[serde(skip)]
+    pub import_encoding: TextEncoding, // 导入后数据文件的编码，auto 表示不变
+    #[serde(skip)]
+    pub path: Option<PathBuf>, // 读取自哪个文件，系统菜单的修改写回这里。文件不存在时为 None
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from("."),
+            window_scale: 2,
+            skip_intro: false,
+            music_enabled: true,
+            sound_enabled: true,
+            music_type: MusicType::default(),
+            audio_output: AudioOutput::default(),
+            volume: Volume::default(),
+            text_encoding: TextEncoding::default(),
+            fallback_font: None,
+            keys: default_key_names(),
+            gamepad: default_gamepad_names(),
+            headless: false,
+            record: None,
+            replay: None,
+            check_text: false,
+            export_text: None,
+            import_text: None,
+            import_encoding: TextEncoding::default(),
+            path: None,
+        }
+    }
+}
+
+// 配置文件中只需列出要修改的按键，其余按键沿用默认设置
+fn merge_key_names<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<BTreeMap<String, Vec<String>>, D::Error> {
+    let mut names = default_key_names();
+    names.extend(BTreeMap::<String, Vec<String>>::deserialize(deserializer)?);
+
+    Ok(names)
+}
+
+fn merge_gamepad_names<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<BTreeMap<String, Vec<String>>, D::Error> {
+    let mut names = default_gamepad_names();
+    names.extend(BTreeMap::<String, Vec<String>>::deserialize(deserializer)?);
+
+    Ok(names)
+}
+
+// 用户配置目录下的配置文件
+pub fn default_config_path() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(dir.join("pal").join(CONFIG_FILE_NAME))
+}
+
+fn parse_value<T: std::str::FromStr>(option: &str, value: Option<String>) -> Result<T> {
+    let value = value.ok_or(format!("missing value for {}", option))?;
+    value.parse().map_err(|_| format!("invalid value for {}: {}", option, value).into())
+}
+
+impl Config {
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let config: Config = toml::from_str(text)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    // 读取配置文件，文件不存在时使用默认设置
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        match std::fs::read_to_string(path) {
+            Ok(text) => {
+                let mut config = Self::from_toml(&text)?;
+                config.path = Some(path.to_path_buf());
+                Ok(config)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // 把一项设置写回读取时的文件，文件中的其他内容不变。
+    // 命令行参数覆盖的设置只在本次运行中有效，不会写入文件；没有文件时什么也不做
+    pub fn save_setting<V: Into<toml::Value>>(&self, name: &str, value: V) -> Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => {
+                return Ok(());
+            }
+        };
+        let mut table: toml::Table = toml::from_str(&std::fs::read_to_string(path)?)?;
+        table.insert(name.to_string(), value.into());
+        std::fs::write(path, toml::to_string_pretty(&table)?)?;
+
+        Ok(())
+    }
+
+    // 由命令行参数得到设置：先读取 --config 指定的文件或默认的配置文件，再用其余参数覆盖
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self> {
+        let args: Vec<String> = args.into_iter().collect();
+        let path = match args.iter().position(|arg| arg == "--config") {
+            Some(i) => Some(PathBuf::from(args.get(i + 1).ok_or("missing value for --config")?)),
+            None => default_config_path(),
+        };
+
+        let mut config = match path {
+            Some(path) => Self::load(path)?,
+            None => Self::default(),
+        };
+        config.apply_args(args)?;
+
+        Ok(config)
+    }
+
+    pub fn apply_args<I: IntoIterator<Item = String>>(&mut self, args: I) -> Result<()> {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                // 已在 from_args 中处理
+                "--config" => {
+                    args.next();
+                }
+                "--data-dir" => {
+                    self.data_dir = parse_value(&arg, args.next())?;
+                }
+                "--scale" => {
+                    self.window_scale = parse_value(&arg, args.next())?;
+                }
+                "--skip-intro" => {
+                    self.skip_intro = true;
+                }
+                "--no-music" => {
+                    self.music_enabled = false;
+                }
+                "--no-sound" => {
+                    self.sound_enabled = false;
+                }
+                "--music" => {
+                    self.music_type = match args.next().as_deref() {
+                        Some("rix") => MusicType::Rix,
+                        Some("midi") => MusicType::Midi,
+                        _ => {
+                            return Err("--music expects rix or midi".into());
+                        }
+                    };
+                }
+                "--audio" => {
+                    self.audio_output = match args.next().as_deref() {
+                        Some("device") => AudioOutput::Device,
+                        Some("null") => AudioOutput::Null,
+                        _ => {
+                            return Err("--audio expects device or null".into());
+                        }
+                    };
+                }
+                "--capture-wav" => {
+                    self.audio_output = AudioOutput::Wav(parse_value(&arg, args.next())?);
+                }
+                "--headless" => {
+                    self.headless = true;
+                }
+                "--record" => {
+                    self.record = Some(parse_value(&arg, args.next())?);
+                }
+                "--replay" => {
+                    self.replay = Some(parse_value(&arg, args.next())?);
+                    self.set_batch();
+                }
+                "--encoding" => {
+                    let name = args.next().unwrap_or_default();
+                    self.text_encoding = TextEncoding::from_name(&name).ok_or("--encoding expects auto, big5, gbk or utf-8")?;
+                }
+                "--check-text" => {
+                    self.check_text = true;
+                    self.set_batch();
+                }
+                "--export-text" => {
+                    self.export_text = Some(parse_value(&arg, args.next())?);
+                    self.set_batch();
+                }
+                "--import-text" => {
+                    self.import_text = Some(parse_value(&arg, args.next())?);
+                    self.set_batch();
+                }
+                "--import-encoding" => {
+                    let name = args.next().unwrap_or_default();
+                    self.import_encoding = TextEncoding::from_name(&name).ok_or("--import-encoding expects auto, big5, gbk or utf-8")?;
+                }
+                "--fallback-font" => {
+                    self.fallback_font = Some(parse_value(&arg, args.next())?);
+                }
+                "--master-volume" => {
+                    self.volume.master = parse_value(&arg, args.next())?;
+                }
+                "--music-volume" => {
+                    self.volume.music = parse_value(&arg, args.next())?;
+                }
+                "--sound-volume" => {
+                    self.volume.sound = parse_value(&arg, args.next())?;
+                }
+                _ => {
+                    return Err(format!("unknown option: {}", arg).into());
+                }
+            }
+        }
+
+        self.validate()
+    }
+
+    // 回放、导出导入文字等不需要交互的运行：不打开窗口、不输出声音、跳过开场
+    fn set_batch(&mut self) {
+        self.headless = true;
+        self.skip_intro = true;
+        self.audio_output = AudioOutput::Null;
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !matches!(self.window_scale, 0 | 1 | 2 | 4 | 8 | 16 | 32) {
+            return Err(format!("invalid window scale: {}", self.window_scale).into());
+        }
+        for volume in [self.volume.master, self.volume.music, self.volume.sound] {
+            if !(0.0..=1.0).contains(&volume) {
+                return Err(format!("volume out of range: {}", volume).into());
+            }
+        }
+        key_bindings(&self.keys)?;
+        gamepad_bindings(&self.gamepad)?;
+
+        Ok(())
+    }
+
+    pub fn scale(&self) -> minifb::Scale {
+        match self.window_scale {
+            0 => minifb::Scale::FitScreen,
+            1 => minifb::Scale::X1,
+            4 => minifb::Scale::X4,
+            8 => minifb::Scale::X8,
+            16 => minifb::Scale::X16,
+            32 => minifb::Scale::X32,
+            _ => minifb::Scale::X2,
+        }
+    }
+}
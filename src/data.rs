@@ -1,7 +1,10 @@
 use std::fmt::{Debug, Display};
-use crate::utils::{ decode_c_structs, Pos, Result };
-use crate::{ mkf::MKF, utils::open_mkf };
-use bincode::Decode;
+use crate::utils::{ decode_c_struct, decode_c_structs, encode_c_structs, Dir, Pos, Result };
+use crate::{ mkf::MKF, utils::{ file_path, open_mkf } };
+use bincode::{ Decode, Encode };
+use crate::party::Party;
+use crate::encounter::*;
+use crate::random::GameRng;
 
 pub struct MKFs {
     pub rng: MKF, // RNG动画
@@ -9,10 +12,14 @@ pub struct MKFs {
     pub fbp: MKF, // 战斗背景sprites
     pub mgo: MKF, // 场景sprites
     pub midi: MKF, // MIDI音乐
+    pub mus: Option<MKF>, // RIX音乐，没有此文件时不播放 RIX 音乐
     pub data: MKF, // 杂项数据文件
     pub map: MKF, // 地图
     pub gop: MKF, // tile bitmap
     pub sss: MKF, // 脚本数据
+    pub abc: MKF, // 敌人战斗sprites
+    pub f: MKF, // 角色战斗sprites
+    pub voc: MKF, // 音效
 }
 
 impl MKFs {
@@ -22,16 +29,21 @@ impl MKFs {
         let fbp = open_mkf("FBP.MKF")?;
         let mgo = open_mkf("MGO.MKF")?;
         let midi = open_mkf("MIDI.MKF")?;
+        // 只用 MIDI 音乐或不需要音乐时可以没有 MUS.MKF
+        let mus = if file_path("MUS.MKF").exists() { Some(open_mkf("MUS.MKF")?) } else { None };
         let data = open_mkf("DATA.MKF")?;
         let map = open_mkf("MAP.MKF")?;
         let gop = open_mkf("GOP.MKF")?;
         let sss = open_mkf("SSS.MKF")?;
+        let abc = open_mkf("ABC.MKF")?;
+        let f = open_mkf("F.MKF")?;
+        let voc = open_mkf("VOC.MKF")?;
 
-        Ok(Self { rng, pat, fbp, mgo, midi, data, map, gop, sss })
+        Ok(Self { rng, pat, fbp, mgo, midi, mus, data, map, gop, sss, abc, f, voc })
     }
 }
 
-#[derive(Debug, Decode, Clone)]
+#[derive(Debug, Decode, Encode, Clone, Default, PartialEq)]
 pub struct EventObject {
     pub vanish_time: u16, // vanish time (?)
     pub x: u16, // X coordinate on the map
@@ -51,7 +63,7 @@ pub struct EventObject {
     pub script_idle_frame_count_auto: u16, // count of idle frames, used by auto script
 }
 
-#[derive(Debug, Decode)]
+#[derive(Debug, Decode, Encode, Clone, Default, PartialEq)]
 pub struct Scene {
     pub map_num: u16, // number of the map
     pub script_on_enter: u16, // when entering this scene, execute script from here
@@ -59,16 +71,114 @@ pub struct Scene {
     pub event_object_index: u16, // event objects in this scene begins from number wEventObjectIndex + 1
 }
 
-#[derive(Debug)]
-#[repr(C)]
+pub const MAX_PLAYER_ROLES: usize = 6;
+pub const MAX_PLAYABLE_PLAYER_ROLES: usize = 5;
+pub const MAX_PLAYER_EQUIPMENTS: usize = 6;
+pub const MAX_PLAYER_MAGICS: usize = 32;
+pub const NUM_MAGIC_ELEMENTAL: usize = 5;
+pub const MAX_POISONS: usize = 16;
+pub const MAX_INVENTORY: usize = 256;
+pub const MAX_SCENES: usize = 300;
+pub const MAX_OBJECTS: usize = 600;
+
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
+pub struct PartyMember {
+    pub player_role: u16, // player role
+    pub x: i16, // position
+    pub y: i16,
+    pub frame: u16, // current frame number
+    pub image_offset: u16, // FIXME: ???
+}
+
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
+pub struct Trail {
+    pub x: u16, // position
+    pub y: u16,
+    pub direction: u16, // direction
+}
+
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
+pub struct Experience {
+    pub exp: u16, // current experience points
+    pub reserved: u16,
+    pub level: u16, // current level
+    pub count: u16,
+}
+
+#[derive(Debug, Decode, Encode, Clone, Default, PartialEq)]
+pub struct AllExperience {
+    pub primary_exp: [Experience; MAX_PLAYER_ROLES],
+    pub health_exp: [Experience; MAX_PLAYER_ROLES],
+    pub magic_exp: [Experience; MAX_PLAYER_ROLES],
+    pub attack_exp: [Experience; MAX_PLAYER_ROLES],
+    pub magic_power_exp: [Experience; MAX_PLAYER_ROLES],
+    pub defense_exp: [Experience; MAX_PLAYER_ROLES],
+    pub dexterity_exp: [Experience; MAX_PLAYER_ROLES],
+    pub flee_exp: [Experience; MAX_PLAYER_ROLES],
+}
+
+type Players = [u16; MAX_PLAYER_ROLES];
+
+#[derive(Debug, Decode, Encode, Clone, Default, PartialEq)]
+pub struct PlayerRoles {
+    pub avatar: Players, // avatar (shown in status view)
+    pub sprite_num_in_battle: Players, // sprite displayed in battle (in F.MKF)
+    pub sprite_num: Players, // sprite displayed in normal scene (in MGO.MKF)
+    pub name: Players, // name of player class (in WORD.DAT)
+    pub attack_all: Players, // whether player can attack everyone in a bulk
+    pub unknown1: Players, // FIXME: ???
+    pub level: Players, // level
+    pub max_hp: Players, // maximum HP
+    pub max_mp: Players, // maximum MP
+    pub hp: Players, // current HP
+    pub mp: Players, // current MP
+    pub equipment: [Players; MAX_PLAYER_EQUIPMENTS], // equipments
+    pub attack_strength: Players, // normal attack strength
+    pub magic_strength: Players, // magical attack strength
+    pub defense: Players, // normal defense value
+    pub dexterity: Players, // dexterity
+    pub flee_rate: Players, // chance of successful fleeing
+    pub poison_resistance: Players, // resistance to poison
+    pub elemental_resistance: [Players; NUM_MAGIC_ELEMENTAL], // resistance to elemental magics
+    pub unknown2: Players, // FIXME: ???
+    pub unknown3: Players, // unused
+    pub unknown4: Players, // FIXME: ???
+    pub covered_by: Players, // who will cover me when I am low of HP or not sane
+    pub magic: [Players; MAX_PLAYER_MAGICS], // magics
+    pub walk_frames: Players, // walk frame (???)
+    pub cooperative_magic: Players, // cooperative magic
+    pub unknown5: Players, // FIXME: ???
+    pub unknown6: Players, // FIXME: ???
+    pub death_sound: Players, // sound played when player dies
+    pub attack_sound: Players, // sound played when player attacks
+    pub weapon_sound: Players, // weapon sound (???)
+    pub critical_sound: Players, // sound played when player make critical hits
+    pub magic_sound: Players, // sound played when player is casting a magic
+    pub cover_sound: Players, // sound played when player cover others
+    pub dying_sound: Players, // sound played when player is dying
+}
+
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
+pub struct PoisonStatus {
+    pub poison_id: u16, // kind of the poison
+    pub poison_script: u16, // script entry
+}
+
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
+pub struct Inventory {
+    pub item: u16, // item object code
+    pub amount: u16, // amount of this item
+    pub amount_in_use: u16, // in-use amount of this item
+}
+
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
 pub struct ObjectPlayer {
     pub reserved: [u16; 2], // always zero
     pub script_on_friend_death: u16, // when friends in party dies, execute script from here
     pub script_on_dying: u16, // when dying, execute script from here
 }
 
-#[derive(Debug)]
-#[repr(C)]
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
 pub struct ObjectItem {
     pub bitmap: u16, // bitmap number in BALL.MKF
     pub price: u16, // price
@@ -78,8 +188,7 @@ pub struct ObjectItem {
     pub flags: u16, // flags
 }
 
-#[derive(Debug)]
-#[repr(C)]
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
 pub struct ObjectMagic {
     pub magic_number: u16, // magic number, according to DATA.MKF #3
     pub reserved1: u16, // always zero
@@ -89,8 +198,21 @@ pub struct ObjectMagic {
     pub flags: u16, // flags
 }
 
-#[derive(Debug)]
-#[repr(C)]
+/*
+typedef enum tagMAGICFLAG
+{
+   kMagicFlagUsableOutsideBattle        = (1 << 0),
+   kMagicFlagUsableInBattle             = (1 << 1),
+   kMagicFlagUsableToEnemy              = (1 << 3),
+   kMagicFlagApplyToAll                 = (1 << 4),
+} MAGICFLAG;
+*/
+pub const MAGIC_FLAG_USABLE_OUTSIDE_BATTLE: u16 = 1 << 0;
+pub const MAGIC_FLAG_USABLE_IN_BATTLE: u16 = 1 << 1;
+pub const MAGIC_FLAG_USABLE_TO_ENEMY: u16 = 1 << 3;
+pub const MAGIC_FLAG_APPLY_TO_ALL: u16 = 1 << 4;
+
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
 pub struct ObjectEnemy {
     pub enemy_id: u16, // ID of the enemy, according to DATA.MKF #1.
     pub resistance_to_sorcery: u16, // resistance to sorcery and poison (0 min, 10 max)
@@ -99,8 +221,7 @@ pub struct ObjectEnemy {
     pub script_on_ready: u16, // script executed when the enemy is ready
 }
 
-#[derive(Debug)]
-#[repr(C)]
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
 pub struct ObjectPoison {
     pub poison_level: u16, // level of the poison
     pub color: u16, // color of avatars
@@ -120,13 +241,13 @@ typedef union tagOBJECT_DOS
     OBJECT_POISON     poison;
 } OBJECT_DOS, *LPOBJECT_DOS;
 */
-#[derive(Decode, Debug)]
+#[derive(Decode, Encode, Debug, Clone, Copy, Default, PartialEq)]
 #[repr(C)]
 pub struct Object {
     data: [u16; 6],
 }
 
-#[derive(Decode)]
+#[derive(Decode, Clone, Copy)]
 pub struct ScriptEntry {
     pub operation: u16, // operation code
     pub operands: [u16; 3], // operands
@@ -138,9 +259,110 @@ impl Display for ScriptEntry {
     }
 }
 
+/*
+对象的种类由对象编号决定
+   players  0x0024 - 0x0029
+   items    0x003D - 0x0126
+   magics   0x0127 - 0x018D
+   enemies  0x018E - 0x0226
+   poisons  0x0227 - 0x0234
+*/
+pub const OBJECT_PLAYER_FIRST: u16 = 0x0024;
+pub const OBJECT_PLAYER_LAST: u16 = 0x0029;
+pub const OBJECT_ITEM_FIRST: u16 = 0x003d;
+pub const OBJECT_ITEM_LAST: u16 = 0x0126;
+pub const OBJECT_MAGIC_FIRST: u16 = 0x0127;
+pub const OBJECT_MAGIC_LAST: u16 = 0x018d;
+pub const OBJECT_ENEMY_FIRST: u16 = 0x018e;
+pub const OBJECT_ENEMY_LAST: u16 = 0x0226;
+pub const OBJECT_POISON_FIRST: u16 = 0x0227;
+pub const OBJECT_POISON_LAST: u16 = 0x0234;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObjectKind {
+    Player,
+    Item,
+    Magic,
+    Enemy,
+    Poison,
+    Other, // 系统对象等
+}
+
+impl ObjectKind {
+    pub fn from_id(object_id: u16) -> Self {
+        match object_id {
+            OBJECT_PLAYER_FIRST..=OBJECT_PLAYER_LAST => ObjectKind::Player,
+            OBJECT_ITEM_FIRST..=OBJECT_ITEM_LAST => ObjectKind::Item,
+            OBJECT_MAGIC_FIRST..=OBJECT_MAGIC_LAST => ObjectKind::Magic,
+            OBJECT_ENEMY_FIRST..=OBJECT_ENEMY_LAST => ObjectKind::Enemy,
+            OBJECT_POISON_FIRST..=OBJECT_POISON_LAST => ObjectKind::Poison,
+            _ => ObjectKind::Other,
+        }
+    }
+}
+
 impl Object {
-    pub fn cast<T>(&self) -> &T {
-        unsafe { &*(self.data.as_ptr() as *const T) }
+    pub fn from_words(data: [u16; 6]) -> Self {
+        Self { data }
+    }
+
+    pub fn words(&self) -> &[u16; 6] {
+        &self.data
+    }
+
+    // 以 T 的格式解读对象的数据
+    fn read<T: Decode>(&self) -> T {
+        let buf: Vec<u8> = self.data.iter().flat_map(|w| w.to_le_bytes()).collect();
+        let (value, _) = decode_c_struct::<T>(&buf).expect("object view larger than 12 bytes");
+        value
+    }
+
+    // 把 T 写回对象的数据，T 没有覆盖的字保持不变
+    fn write<T: Encode>(&mut self, value: &T) {
+        let buf = encode_c_structs(std::slice::from_ref(value)).expect("object view larger than 12 bytes");
+        for (word, bytes) in self.data.iter_mut().zip(buf.chunks_exact(2)) {
+            *word = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+    }
+
+    pub fn player(&self) -> ObjectPlayer {
+        self.read()
+    }
+
+    pub fn set_player(&mut self, player: &ObjectPlayer) {
+        self.write(player);
+    }
+
+    pub fn item(&self) -> ObjectItem {
+        self.read()
+    }
+
+    pub fn set_item(&mut self, item: &ObjectItem) {
+        self.write(item);
+    }
+
+    pub fn magic(&self) -> ObjectMagic {
+        self.read()
+    }
+
+    pub fn set_magic(&mut self, magic: &ObjectMagic) {
+        self.write(magic);
+    }
+
+    pub fn enemy(&self) -> ObjectEnemy {
+        self.read()
+    }
+
+    pub fn set_enemy(&mut self, enemy: &ObjectEnemy) {
+        self.write(enemy);
+    }
+
+    pub fn poison(&self) -> ObjectPoison {
+        self.read()
+    }
+
+    pub fn set_poison(&mut self, poison: &ObjectPoison) {
+        self.write(poison);
     }
 }
 
@@ -158,8 +380,189 @@ pub enum ObjectState {
     Blocker,
 }
 
+/*
+typedef enum tagTRIGGERMODE
+{
+   kTriggerNone                  = 0,
+   kTriggerSearchNear            = 1,
+   kTriggerSearchNormal          = 2,
+   kTriggerSearchFar             = 3,
+   kTriggerTouchNear             = 4,
+   kTriggerTouchNormal           = 5,
+   kTriggerTouchFar              = 6,
+   kTriggerTouchFarther          = 7,
+   kTriggerTouchFarthest         = 8
+} TRIGGERMODE;
+*/
+pub enum TriggerMode {
+    None = 0,
+    SearchNear,
+    SearchNormal,
+    SearchFar,
+    TouchNear,
+    TouchNormal,
+    TouchFar,
+    TouchFarther,
+    TouchFarthest,
+}
+
+pub const MAX_STORE_ITEM: usize = 9;
+pub const MAX_ENEMIES_IN_TEAM: usize = 5;
+pub const MAX_LEVELS: usize = 99;
+
+// DATA.MKF 中的数据块
+const CHUNKNUM_STORE: u32 = 0;
+const CHUNKNUM_ENEMY: u32 = 1;
+const CHUNKNUM_ENEMY_TEAM: u32 = 2;
+const CHUNKNUM_PLAYER_ROLES: u32 = 3;
+const CHUNKNUM_MAGIC: u32 = 4;
+const CHUNKNUM_BATTLE_FIELD: u32 = 5;
+const CHUNKNUM_LEVELUP_MAGIC: u32 = 6;
+const CHUNKNUM_BATTLE_EFFECT_INDEX: u32 = 11;
+const CHUNKNUM_ENEMY_POS: u32 = 13;
+const CHUNKNUM_LEVELUP_EXP: u32 = 14;
+
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
+pub struct Store {
+    pub items: [u16; MAX_STORE_ITEM],
+}
+
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
+pub struct Enemy {
+    pub idle_frames: u16, // total number of frames when idle
+    pub magic_frames: u16, // total number of frames when using magics
+    pub attack_frames: u16, // total number of frames when doing normal attack
+    pub idle_anim_speed: u16, // speed of the animation when idle
+    pub act_wait_frames: u16, // FIXME: ???
+    pub y_pos_offset: u16,
+    pub attack_sound: i16, // sound played when this enemy uses normal attack
+    pub action_sound: i16, // FIXME: ???
+    pub magic_sound: i16, // sound played when this enemy uses magic
+    pub death_sound: i16, // sound played when this enemy dies
+    pub call_sound: i16, // sound played when entering the battle
+    pub health: u16, // total HP of the enemy
+    pub exp: u16, // How many EXPs we'll get for beating this enemy
+    pub cash: u16, // how many cashes we'll get for beating this enemy
+    pub level: u16, // this enemy's level
+    pub magic: u16, // this enemy's magic number
+    pub magic_rate: u16, // chance for this enemy to use magic
+    pub attack_equiv_item: u16, // equivalence item of this enemy's normal attack
+    pub attack_equiv_item_rate: u16, // chance for equivalence item
+    pub steal_item: u16, // which item we'll get when stealing from this enemy
+    pub num_steal_item: u16, // total amount of the items which can be stolen
+    pub attack_strength: u16, // normal attack strength
+    pub magic_strength: u16, // magical attack strength
+    pub defense: u16, // resistance to all kinds of attacking
+    pub dexterity: u16, // dexterity
+    pub flee_rate: u16, // chance for successful fleeing
+    pub poison_resistance: u16, // resistance to poison
+    pub elem_resistance: [u16; NUM_MAGIC_ELEMENTAL], // resistance to elemental magics
+    pub physical_resistance: u16, // resistance to physical attack
+    pub dual_move: u16, // whether this enemy can do dual move or not
+    pub collect_value: u16, // value for collecting this enemy for items
+}
+
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
+pub struct EnemyTeam {
+    pub enemies: [u16; MAX_ENEMIES_IN_TEAM], // 0xffff 表示空位
+}
+
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
+pub struct Magic {
+    pub effect: u16, // effect sprite
+    pub magic_type: u16, // type of this magic
+    pub x_offset: u16,
+    pub y_offset: u16,
+    pub summon_effect: u16, // summon effect sprite (in F.MKF)
+    pub speed: i16, // speed of the effect
+    pub keep_effect: u16, // FIXME: ???
+    pub fire_delay: u16, // start frame of the magic fire stage
+    pub effect_times: u16, // total times of effect
+    pub shake: u16, // shake screen
+    pub wave: u16, // wave screen
+    pub unknown: u16, // FIXME: ???
+    pub cost_mp: u16, // MP cost
+    pub base_damage: u16, // base damage
+    pub elemental: u16, // elemental (0 = No Elemental, last = poison)
+    pub sound: i16, // sound played when using this magic
+}
+
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
+pub struct BattleField {
+    pub screen_wave: u16, // level of screen waving
+    pub magic_effect: [i16; NUM_MAGIC_ELEMENTAL], // effect of attributed magics
+}
+
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
+pub struct LevelUpMagic {
+    pub level: u16, // level reached
+    pub magic: u16, // magic learned
+}
+
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
+pub struct LevelUpMagicAll {
+    pub m: [LevelUpMagic; MAX_PLAYABLE_PLAYER_ROLES],
+}
+
+pub type BattleEffectIndex = [u16; 2];
+
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
+pub struct EnemyPosition {
+    pub x: u16,
+    pub y: u16,
+}
+
+// pos[敌人数量 - 1][敌人序号]
+#[derive(Debug, Decode, Encode, Clone, Copy, Default, PartialEq)]
+pub struct EnemyPos {
+    pub pos: [[EnemyPosition; MAX_ENEMIES_IN_TEAM]; MAX_ENEMIES_IN_TEAM],
+}
+
+// 只读的数据表，越界访问返回 None
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table<T> {
+    items: Vec<T>,
+}
+
+impl<T: Decode> Table<T> {
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        let items = if buf.is_empty() { Vec::new() } else { decode_c_structs::<T>(buf)? };
+
+        Ok(Self { items })
+    }
+}
+
+impl<T> Table<T> {
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.items.get(index)
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
 pub struct GameData {
     pub script_entries: Vec<ScriptEntry>,
+    pub stores: Table<Store>,
+    pub enemies: Table<Enemy>,
+    pub enemy_teams: Table<EnemyTeam>,
+    pub player_roles: PlayerRoles, // 新游戏时的角色属性
+    pub magics: Table<Magic>,
+    pub battle_fields: Table<BattleField>,
+    pub level_up_magic: Table<LevelUpMagicAll>,
+    pub battle_effect_index: Table<BattleEffectIndex>,
+    pub enemy_pos: EnemyPos,
+    pub level_up_exp: Table<u16>, // 升到每一级所需的经验值
+    pub encounters: EncounterTable, // 新游戏时的随机遇敌表
 }
 
 impl GameData {
@@ -167,25 +570,179 @@ impl GameData {
         let buf = sss.read_chunk(4)?;
         let script_entries = decode_c_structs::<ScriptEntry>(&buf)?;
 
+        let (player_roles, _) = decode_c_struct::<PlayerRoles>(&data.read_chunk(CHUNKNUM_PLAYER_ROLES)?)?;
+        let (enemy_pos, _) = decode_c_struct::<EnemyPos>(&data.read_chunk(CHUNKNUM_ENEMY_POS)?)?;
+
         Ok(GameData {
             script_entries,
+            stores: Table::decode(&data.read_chunk(CHUNKNUM_STORE)?)?,
+            enemies: Table::decode(&data.read_chunk(CHUNKNUM_ENEMY)?)?,
+            enemy_teams: Table::decode(&data.read_chunk(CHUNKNUM_ENEMY_TEAM)?)?,
+            player_roles,
+            magics: Table::decode(&data.read_chunk(CHUNKNUM_MAGIC)?)?,
+            battle_fields: Table::decode(&data.read_chunk(CHUNKNUM_BATTLE_FIELD)?)?,
+            level_up_magic: Table::decode(&data.read_chunk(CHUNKNUM_LEVELUP_MAGIC)?)?,
+            battle_effect_index: Table::decode(&data.read_chunk(CHUNKNUM_BATTLE_EFFECT_INDEX)?)?,
+            enemy_pos,
+            level_up_exp: Table::decode(&data.read_chunk(CHUNKNUM_LEVELUP_EXP)?)?,
+            encounters: load_encounter_table()?,
         })
     }
+
+    pub fn store(&self, index: usize) -> Option<&Store> {
+        self.stores.get(index)
+    }
+
+    pub fn enemy(&self, index: usize) -> Option<&Enemy> {
+        self.enemies.get(index)
+    }
+
+    pub fn enemy_team(&self, index: usize) -> Option<&EnemyTeam> {
+        self.enemy_teams.get(index)
+    }
+
+    pub fn magic(&self, index: usize) -> Option<&Magic> {
+        self.magics.get(index)
+    }
+
+    pub fn battle_field(&self, index: usize) -> Option<&BattleField> {
+        self.battle_fields.get(index)
+    }
+
+    // 升到 level 级所需的经验值
+    pub fn exp_for_level(&self, level: usize) -> Option<u16> {
+        self.level_up_exp.get(level).copied()
+    }
+
+    // 敌人数量为 count 时第 index 个敌人的位置
+    pub fn enemy_position(&self, count: usize, index: usize) -> Option<&EnemyPosition> {
+        self.enemy_pos.pos.get(count.checked_sub(1)?)?.get(index)
+    }
 }
 
+#[derive(Clone)]
 pub struct GameState {
     pub objects: Vec<Object>,
     pub scenes: Vec<Scene>,
     pub event_objects: Vec<EventObject>,
 
+    pub saved_times: u16, // 存档次数
+    pub max_party_member_index: u16,
+    pub party: Vec<PartyMember>,
+    pub trail: Vec<Trail>,
+    pub follower: u16,
+    pub exp: AllExperience,
+    pub player_roles: PlayerRoles,
+    pub poison_status: Vec<Vec<PoisonStatus>>, // [MAX_POISONS][MAX_PLAYABLE_PLAYER_ROLES]
+    pub inventory: Vec<Inventory>,
+    pub cash: u32,
+    pub party_state: Party,
+
+    pub num_music: u16,
+    pub num_battle_music: u16,
+    pub num_battle_field: u16,
+    pub battle_speed: u16,
+    pub collect_value: u16,
+    pub chase_range: u16,
+    pub chase_speed_change_cycles: u16,
+    pub encounters: EncounterTable, // 各场景的随机遇敌设置，来自数据目录中的遇敌表或存档
+    pub encounter_steps: u16, // 距离下次遇敌的步数
+    pub rng: GameRng, // 所有游戏逻辑共用的随机数发生器
+
     pub entering_scene: bool,
+    pub need_load_scene: bool,
     pub scene_num: u16,
     pub viewport: Pos,
+    pub party_offset: Pos, // 队伍相对于视口的位置
+    pub party_direction: Dir,
+    pub layer: u16,
+    pub num_palette: u16,
+    pub night_palette: bool,
+    pub need_to_fade_in: bool,
+    pub screen_wave: u16,
+    pub wave_progression: i16,
     pub last_object_id: u16,
 }
 
 impl GameState {
-    pub fn load_new_game(sss: &mut MKF) -> Result<Self> {
+    pub fn new(objects: Vec<Object>, scenes: Vec<Scene>, event_objects: Vec<EventObject>) -> Self {
+        Self {
+            objects,
+            scenes,
+            event_objects,
+            saved_times: 0,
+            max_party_member_index: 0,
+            party: vec![PartyMember::default(); MAX_PLAYABLE_PLAYER_ROLES],
+            trail: vec![Trail::default(); MAX_PLAYABLE_PLAYER_ROLES],
+            follower: 0,
+            exp: AllExperience::default(),
+            player_roles: PlayerRoles::default(),
+            poison_status: vec![vec![PoisonStatus::default(); MAX_PLAYABLE_PLAYER_ROLES]; MAX_POISONS],
+            inventory: vec![Inventory::default(); MAX_INVENTORY],
+            cash: 0,
+            party_state: Party::default(),
+            num_music: 0,
+            num_battle_music: 0,
+            num_battle_field: 0,
+            battle_speed: 0,
+            collect_value: 0,
+            chase_range: 1,
+            chase_speed_change_cycles: 0,
+            encounters: EncounterTable::new(),
+            encounter_steps: 0,
+            rng: GameRng::default(),
+            entering_scene: true,
+            need_load_scene: true,
+            scene_num: 1,
+            viewport: Pos { x: 0, y: 0 },
+            party_offset: Pos { x: 160, y: 112 },
+            party_direction: Dir::South,
+            layer: 0,
+            num_palette: 0,
+            night_palette: false,
+            need_to_fade_in: false,
+            screen_wave: 0,
+            wave_progression: 0,
+            last_object_id: 0,
+        }
+    }
+
+    // 对象编号从 0 开始，编号对应的种类不符时返回 None
+    pub fn object_of_kind(&self, object_id: u16, kind: ObjectKind) -> Option<&Object> {
+        if ObjectKind::from_id(object_id) != kind {
+            return None;
+        }
+        self.objects.get(object_id as usize)
+    }
+
+    pub fn object_of_kind_mut(&mut self, object_id: u16, kind: ObjectKind) -> Option<&mut Object> {
+        if ObjectKind::from_id(object_id) != kind {
+            return None;
+        }
+        self.objects.get_mut(object_id as usize)
+    }
+
+    pub fn object_player(&self, object_id: u16) -> Option<ObjectPlayer> {
+        self.object_of_kind(object_id, ObjectKind::Player).map(Object::player)
+    }
+
+    pub fn object_item(&self, object_id: u16) -> Option<ObjectItem> {
+        self.object_of_kind(object_id, ObjectKind::Item).map(Object::item)
+    }
+
+    pub fn object_magic(&self, object_id: u16) -> Option<ObjectMagic> {
+        self.object_of_kind(object_id, ObjectKind::Magic).map(Object::magic)
+    }
+
+    pub fn object_enemy(&self, object_id: u16) -> Option<ObjectEnemy> {
+        self.object_of_kind(object_id, ObjectKind::Enemy).map(Object::enemy)
+    }
+
+    pub fn object_poison(&self, object_id: u16) -> Option<ObjectPoison> {
+        self.object_of_kind(object_id, ObjectKind::Poison).map(Object::poison)
+    }
+
+    pub fn load_new_game(sss: &mut MKF, data: &GameData) -> Result<Self> {
         let buf = sss.read_chunk(0)?;
         let events = decode_c_structs::<EventObject>(&buf)?;
 
@@ -195,14 +752,26 @@ impl GameState {
         let buf = sss.read_chunk(2)?;
         let objects = decode_c_structs::<Object>(&buf)?;
 
-        Ok(Self {
-            objects,
-            scenes,
-            event_objects: events,
-            entering_scene: true,
-            scene_num: 1,
-            viewport: Pos { x: 0, y: 0 },
-            last_object_id: 0,
-        })
+        let mut state = Self::new(objects, scenes, events);
+        state.player_roles = data.player_roles.clone();
+        state.encounters = data.encounters.clone();
+        for i in 0..MAX_PLAYER_ROLES {
+            let level = state.player_roles.level[i];
+            let exp = &mut state.exp;
+            for exps in [
+                &mut exp.primary_exp,
+                &mut exp.health_exp,
+                &mut exp.magic_exp,
+                &mut exp.attack_exp,
+                &mut exp.magic_power_exp,
+                &mut exp.defense_exp,
+                &mut exp.dexterity_exp,
+                &mut exp.flee_exp,
+            ] {
+                exps[i].level = level;
+            }
+        }
+
+        Ok(state)
     }
 }
@@ -0,0 +1,131 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{ DecoderResult, Encoding, BIG5, GBK, UTF_8 };
+use serde::{ Deserialize, Serialize };
+
+// 文字数据（WOR16.ASC、WORD.DAT、M.MSG）的编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextEncoding {
+    #[default]
+    Auto, // 根据文件内容猜测
+    Big5, // 繁体中文 DOS 版
+    Gbk, // 简体中文版
+    Utf8, // 导入了其他语言的翻译后
+}
+
+impl TextEncoding {
+    pub fn from_name(name: &str) -> Option<TextEncoding> {
+        match name.to_ascii_lowercase().as_str() {
+            "auto" => Some(TextEncoding::Auto),
+            "big5" => Some(TextEncoding::Big5),
+            "gbk" | "gb2312" => Some(TextEncoding::Gbk),
+            "utf8" | "utf-8" => Some(TextEncoding::Utf8),
+            _ => None,
+        }
+    }
+
+    // 选定的编码，Auto 时根据 samples 猜测
+    pub fn resolve(self, samples: &[&[u8]]) -> &'static Encoding {
+        match self {
+            TextEncoding::Auto => detect_encoding(samples),
+            TextEncoding::Big5 => BIG5,
+            TextEncoding::Gbk => GBK,
+            TextEncoding::Utf8 => UTF_8,
+        }
+    }
+}
+
+// 用全部文字数据猜测编码。含有非 ASCII 字符且都是合法的 UTF-8 时为 UTF-8（导入的翻译）；
+// 否则猜测结果不是 Big5 或 GBK 时（数据太少容易猜错），选无法解码的字节较少的一个，相同时用 Big5
+pub fn detect_encoding(samples: &[&[u8]]) -> &'static Encoding {
+    if samples.iter().any(|sample| !sample.is_ascii()) &&
+        samples.iter().all(|sample| std::str::from_utf8(sample).is_ok())
+    {
+        return UTF_8;
+    }
+
+    let mut detector = EncodingDetector::new();
+    for (i, sample) in samples.iter().enumerate() {
+        detector.feed(sample, i + 1 == samples.len());
+    }
+    let guess = detector.guess(None, false);
+    if guess == BIG5 || guess == GBK {
+        return guess;
+    }
+
+    let errors = |encoding| samples.iter().map(|sample| find_undecodable(encoding, sample).len()).sum::<usize>();
+    if errors(GBK) < errors(BIG5) { GBK } else { BIG5 }
+}
+
+// 无法解码的字节序列，返回每个序列的起始位置和内容
+pub fn find_undecodable(encoding: &'static Encoding, bytes: &[u8]) -> Vec<(usize, Vec<u8>)> {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let capacity = decoder.max_utf8_buffer_length_without_replacement(bytes.len()).unwrap_or(bytes.len() * 4);
+    let mut decoded = String::with_capacity(capacity);
+    let mut result = Vec::new();
+    let mut pos = 0;
+    loop {
+        let (status, read) = decoder.decode_to_string_without_replacement(&bytes[pos..], &mut decoded, true);
+        pos += read;
+        match status {
+            DecoderResult::InputEmpty => {
+                break;
+            }
+            DecoderResult::OutputFull => {
+                decoded.reserve(capacity);
+            }
+            DecoderResult::Malformed(len, consumed) => {
+                let end = pos - (consumed as usize);
+                let start = end - (len as usize);
+                result.push((start, bytes[start..end].to_vec()));
+            }
+        }
+    }
+
+    result
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextSource {
+    Word, // WORD.DAT
+    Message, // M.MSG
+}
+
+// 解码时出错的一段字节
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeIssue {
+    pub source: TextSource,
+    pub index: usize, // 词条或对话的序号
+    pub offset: usize, // 在该条目中的字节位置
+    pub bytes: Vec<u8>,
+}
+
+// 检查各条目，返回所有无法解码的字节
+pub fn check_entries<'a, I>(encoding: &'static Encoding, source: TextSource, entries: I) -> Vec<DecodeIssue>
+    where I: IntoIterator<Item = &'a [u8]>
+{
+    let mut issues = Vec::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        for (offset, bytes) in find_undecodable(encoding, entry) {
+            issues.push(DecodeIssue { source, index, offset, bytes });
+        }
+    }
+
+    issues
+}
+
+// 诊断报告，每行一个无法解码的字节序列
+pub fn format_report(encoding: &'static Encoding, issues: &[DecodeIssue]) -> String {
+    let mut report = format!("encoding: {}\n", encoding.name());
+    for issue in issues {
+        let source = match issue.source {
+            TextSource::Word => "word",
+            TextSource::Message => "message",
+        };
+        let bytes: Vec<String> = issue.bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        report += &format!("{} {} offset {}: {}\n", source, issue.index, issue.offset, bytes.join(" "));
+    }
+    report += &format!("{} undecodable sequences\n", issues.len());
+
+    report
+}
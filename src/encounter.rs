@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::battle::BattleResult;
+use crate::data::GameState;
+use crate::game::Game;
+use crate::utils::{ file_path, Result };
+
+// 随机遇敌表，放在游戏数据目录中。原版数据中没有此项，文件不存在时所有场景都不会遇敌
+pub const ENCOUNTER_FILE_NAME: &str = "ENCOUNTER.TOML";
+
+// 存档中随机遇敌表的标记，随机遇敌表接在 DOS 版存档的内容之后
+const ENCOUNTER_SAVE_TAG: &[u8; 4] = b"ENCT";
+
+// 场景的随机遇敌设置
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Encounter {
+    pub rate: u16, // 平均每走多少步遇敌一次，0 表示不遇敌
+    pub enemy_teams: Vec<u16>, // 可能遇到的敌人队伍
+}
+
+// 场景号 -> 随机遇敌设置
+pub type EncounterTable = HashMap<u16, Encounter>;
+
+/*
+[[scene]]
+scene = 3
+rate = 30
+enemy_teams = [5, 6]
+*/
+#[derive(Deserialize)]
+struct EncounterFile {
+    #[serde(default)]
+    scene: Vec<SceneEncounter>,
+}
+
+#[derive(Deserialize)]
+struct SceneEncounter {
+    scene: u16,
+    rate: u16,
+    #[serde(default)]
+    enemy_teams: Vec<u16>,
+}
+
+pub fn parse_encounter_table(text: &str) -> Result<EncounterTable> {
+    let file: EncounterFile = toml::from_str(text)?;
+    let mut table = EncounterTable::new();
+    for entry in file.scene {
+        if table.contains_key(&entry.scene) {
+            return Err(format!("duplicate encounter for scene {}", entry.scene).into());
+        }
+        table.insert(entry.scene, Encounter { rate: entry.rate, enemy_teams: entry.enemy_teams });
+    }
+
+    Ok(table)
+}
+
+// 读取数据目录中的随机遇敌表，文件不存在时为空
+pub fn load_encounter_table() -> Result<EncounterTable> {
+    match std::fs::read_to_string(file_path(ENCOUNTER_FILE_NAME)) {
+        Ok(text) => parse_encounter_table(&text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(EncounterTable::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// 存档中的随机遇敌表：按场景号排列的 场景号、遇敌步数、队伍数、各队伍（均为 WORD），
+// 之后是这些数据的字节数（DWORD）和标记
+pub fn encode_encounter_table(table: &EncounterTable) -> Vec<u8> {
+    let mut scenes: Vec<&u16> = table.keys().collect();
+    scenes.sort();
+
+    let mut words = Vec::new();
+    for scene in scenes {
+        let encounter = &table[scene];
+        words.extend([*scene, encounter.rate, encounter.enemy_teams.len() as u16]);
+        words.extend(&encounter.enemy_teams);
+    }
+
+    let mut buf: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+    buf.extend((buf.len() as u32).to_le_bytes());
+    buf.extend(ENCOUNTER_SAVE_TAG);
+    buf
+}
+
+// 从存档末尾分离出随机遇敌表，返回其余的内容。没有标记时为原版存档，不含随机遇敌表
+pub fn split_encounter_table(buf: &[u8]) -> Result<(&[u8], Option<EncounterTable>)> {
+    let rest = match buf.strip_suffix(ENCOUNTER_SAVE_TAG) {
+        Some(rest) if rest.len() >= 4 => rest,
+        _ => {
+            return Ok((buf, None));
+        }
+    };
+    let (rest, len) = rest.split_at(rest.len() - 4);
+    let len = u32::from_le_bytes(len.try_into()?) as usize;
+    if len > rest.len() || len & 1 != 0 {
+        return Err("invalid encounter table in saved game".into());
+    }
+    let (rest, data) = rest.split_at(rest.len() - len);
+
+    let words: Vec<u16> = data.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]])).collect();
+    let mut table = EncounterTable::new();
+    let mut words = words.as_slice();
+    while let [scene, rate, count, tail @ ..] = words {
+        let count = *count as usize;
+        if tail.len() < count {
+            return Err("invalid encounter table in saved game".into());
+        }
+        table.insert(*scene, Encounter { rate: *rate, enemy_teams: tail[..count].to_vec() });
+        words = &tail[count..];
+    }
+    if !words.is_empty() {
+        return Err("invalid encounter table in saved game".into());
+    }
+
+    Ok((rest, Some(table)))
+}
+
+impl GameState {
+    pub fn set_encounter(&mut self, scene_num: u16, encounter: Encounter) {
+        self.encounters.insert(scene_num, encounter);
+        self.encounter_steps = 0;
+    }
+
+    // 队伍走了一步。遇敌时返回敌人队伍，并重新计算到下次遇敌的步数
+    pub fn encounter_step(&mut self) -> Option<u16> {
+        let encounter = self.encounters.get(&self.scene_num)?;
+        if encounter.rate == 0 || encounter.enemy_teams.is_empty() {
+            return None;
+        }
+        let rate = encounter.rate as i32;
+        let num_teams = encounter.enemy_teams.len() as i32;
+
+        // 步数在平均值的一半到一倍半之间浮动
+        let rng = &mut self.rng;
+        let mut next_steps = || std::cmp::max(rng.random_long(rate / 2, rate + rate / 2), 1) as u16;
+
+        if self.encounter_steps == 0 {
+            self.encounter_steps = next_steps();
+        }
+        self.encounter_steps -= 1;
+        if self.encounter_steps > 0 {
+            return None;
+        }
+        self.encounter_steps = next_steps();
+
+        let team = self.rng.random_long(0, num_teams - 1) as usize;
+        self.encounters.get(&self.scene_num).map(|encounter| encounter.enemy_teams[team])
+    }
+}
+
+impl Game {
+    // 随机遇敌，使用当前设置的战场和战斗音乐
+    pub fn check_encounter(&mut self) -> Result<()> {
+        let enemy_team = match self.state.encounter_step() {
+            Some(enemy_team) => enemy_team,
+            None => {
+                return Ok(());
+            }
+        };
+
+        if self.start_battle(enemy_team, false)? == BattleResult::Lost {
+            // 全灭，回到最近的存档
+            self.pending_load = Some(self.current_save_slot);
+        }
+
+        Ok(())
+    }
+}
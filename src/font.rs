@@ -0,0 +1,272 @@
+// 内置的半角字形。WOR16.FON 只有全角字形，ASCII 字符用这里的 8x16 字形绘制。
+// WOR16 中没有的字符（翻译成其他语言时）可以用 BDF 或 TTF 字体补充，渲染成同样的 16x16 格式
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::utils::Result;
+
+pub const HALF_WIDTH: u32 = 8;
+pub const FULL_WIDTH: u32 = 16;
+
+// 字形中基线所在的行，基线以上为 0～BASELINE-1 行
+const BASELINE: i32 = 14;
+
+// 0x20～0x7e 的字形，每行一个字节，位 x 对应第 x 列。
+// 取自 X11 misc-fixed 8x13（公有领域），上方空出 3 行，使基线与全角字形一致
+const ASCII_GLYPHS: [[u8; 16]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x00, 0x08, 0x00, 0x00], // '!'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x24, 0x24, 0x24, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x24, 0x24, 0x7e, 0x24, 0x7e, 0x24, 0x24, 0x00, 0x00, 0x00], // '#'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x3c, 0x0a, 0x0a, 0x1c, 0x28, 0x28, 0x1e, 0x08, 0x00, 0x00], // '$'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x44, 0x4a, 0x24, 0x10, 0x10, 0x08, 0x24, 0x54, 0x22, 0x00, 0x00], // '%'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x12, 0x12, 0x0c, 0x52, 0x22, 0x5c, 0x00, 0x00], // '&'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // "'"
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x10, 0x10, 0x08, 0x08, 0x08, 0x10, 0x10, 0x20, 0x00, 0x00], // '('
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x08, 0x08, 0x10, 0x10, 0x10, 0x08, 0x08, 0x04, 0x00, 0x00], // ')'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x24, 0x18, 0x7e, 0x18, 0x24, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '*'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x08, 0x3e, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1c, 0x0c, 0x02, 0x00], // ','
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3e, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x1c, 0x08, 0x00], // '.'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x40, 0x20, 0x10, 0x08, 0x04, 0x02, 0x01, 0x01, 0x00, 0x00], // '/'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x24, 0x42, 0x42, 0x42, 0x42, 0x42, 0x24, 0x18, 0x00, 0x00], // '0'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x0c, 0x0a, 0x08, 0x08, 0x08, 0x08, 0x08, 0x3e, 0x00, 0x00], // '1'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x42, 0x40, 0x20, 0x18, 0x04, 0x02, 0x7e, 0x00, 0x00], // '2'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x40, 0x20, 0x10, 0x38, 0x40, 0x40, 0x42, 0x3c, 0x00, 0x00], // '3'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x30, 0x28, 0x24, 0x22, 0x22, 0x7e, 0x20, 0x20, 0x00, 0x00], // '4'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x02, 0x02, 0x3a, 0x46, 0x40, 0x40, 0x42, 0x3c, 0x00, 0x00], // '5'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x38, 0x04, 0x02, 0x02, 0x3a, 0x46, 0x42, 0x42, 0x3c, 0x00, 0x00], // '6'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x40, 0x20, 0x10, 0x10, 0x08, 0x08, 0x04, 0x04, 0x00, 0x00], // '7'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x42, 0x42, 0x3c, 0x42, 0x42, 0x42, 0x3c, 0x00, 0x00], // '8'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x42, 0x62, 0x5c, 0x40, 0x40, 0x20, 0x1c, 0x00, 0x00], // '9'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x1c, 0x08, 0x00, 0x00, 0x08, 0x1c, 0x08, 0x00], // ':'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x1c, 0x08, 0x00, 0x00, 0x1c, 0x0c, 0x02, 0x00], // ';'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x20, 0x10, 0x08, 0x04, 0x08, 0x10, 0x20, 0x40, 0x00, 0x00], // '<'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x00, 0x00, 0x7e, 0x00, 0x00, 0x00, 0x00], // '='
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x04, 0x08, 0x10, 0x20, 0x10, 0x08, 0x04, 0x02, 0x00, 0x00], // '>'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x42, 0x40, 0x20, 0x10, 0x10, 0x00, 0x10, 0x00, 0x00], // '?'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x42, 0x72, 0x4a, 0x6a, 0x52, 0x02, 0x3c, 0x00, 0x00], // '@'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x24, 0x42, 0x42, 0x42, 0x7e, 0x42, 0x42, 0x42, 0x00, 0x00], // 'A'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x1e, 0x22, 0x42, 0x22, 0x1e, 0x22, 0x42, 0x22, 0x1e, 0x00, 0x00], // 'B'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x02, 0x02, 0x02, 0x02, 0x02, 0x42, 0x3c, 0x00, 0x00], // 'C'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x1e, 0x22, 0x42, 0x42, 0x42, 0x42, 0x42, 0x22, 0x1e, 0x00, 0x00], // 'D'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x02, 0x02, 0x02, 0x1e, 0x02, 0x02, 0x02, 0x7e, 0x00, 0x00], // 'E'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x02, 0x02, 0x02, 0x1e, 0x02, 0x02, 0x02, 0x02, 0x00, 0x00], // 'F'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x02, 0x02, 0x02, 0x72, 0x42, 0x62, 0x5c, 0x00, 0x00], // 'G'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x42, 0x42, 0x42, 0x42, 0x7e, 0x42, 0x42, 0x42, 0x42, 0x00, 0x00], // 'H'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3e, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x3e, 0x00, 0x00], // 'I'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xf8, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x22, 0x1c, 0x00, 0x00], // 'J'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x42, 0x22, 0x12, 0x0a, 0x06, 0x0a, 0x12, 0x22, 0x42, 0x00, 0x00], // 'K'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x02, 0x02, 0x02, 0x02, 0x02, 0x02, 0x7e, 0x00, 0x00], // 'L'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x41, 0x41, 0x63, 0x55, 0x49, 0x49, 0x41, 0x41, 0x41, 0x00, 0x00], // 'M'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x42, 0x42, 0x46, 0x4a, 0x52, 0x62, 0x42, 0x42, 0x42, 0x00, 0x00], // 'N'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x3c, 0x00, 0x00], // 'O'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3e, 0x42, 0x42, 0x42, 0x3e, 0x02, 0x02, 0x02, 0x02, 0x00, 0x00], // 'P'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x42, 0x42, 0x42, 0x42, 0x4a, 0x52, 0x3c, 0x40, 0x00], // 'Q'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3e, 0x42, 0x42, 0x42, 0x3e, 0x0a, 0x12, 0x22, 0x42, 0x00, 0x00], // 'R'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x02, 0x02, 0x3c, 0x40, 0x40, 0x42, 0x3c, 0x00, 0x00], // 'S'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7f, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x00, 0x00], // 'T'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x3c, 0x00, 0x00], // 'U'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x41, 0x41, 0x22, 0x22, 0x22, 0x14, 0x14, 0x14, 0x08, 0x00, 0x00], // 'V'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x41, 0x41, 0x41, 0x41, 0x49, 0x49, 0x49, 0x55, 0x22, 0x00, 0x00], // 'W'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x41, 0x41, 0x22, 0x14, 0x08, 0x14, 0x22, 0x41, 0x41, 0x00, 0x00], // 'X'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x41, 0x41, 0x22, 0x14, 0x08, 0x08, 0x08, 0x08, 0x08, 0x00, 0x00], // 'Y'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x40, 0x20, 0x10, 0x08, 0x04, 0x02, 0x02, 0x7e, 0x00, 0x00], // 'Z'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x3c, 0x00, 0x00], // '['
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x40, 0x00, 0x00], // '\\'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x1e, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1e, 0x00, 0x00], // ']'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x14, 0x22, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7f, 0x00], // '_'
+    [0x00, 0x00, 0x00, 0x00, 0x08, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x40, 0x7c, 0x42, 0x62, 0x5c, 0x00, 0x00], // 'a'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x02, 0x3a, 0x46, 0x42, 0x42, 0x46, 0x3a, 0x00, 0x00], // 'b'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x02, 0x02, 0x42, 0x3c, 0x00, 0x00], // 'c'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x40, 0x40, 0x5c, 0x62, 0x42, 0x42, 0x62, 0x5c, 0x00, 0x00], // 'd'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x7e, 0x02, 0x42, 0x3c, 0x00, 0x00], // 'e'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x38, 0x44, 0x04, 0x04, 0x3e, 0x04, 0x04, 0x04, 0x04, 0x00, 0x00], // 'f'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x5c, 0x22, 0x22, 0x1c, 0x02, 0x3c, 0x42, 0x3c], // 'g'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x02, 0x3a, 0x46, 0x42, 0x42, 0x42, 0x42, 0x00, 0x00], // 'h'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x0c, 0x08, 0x08, 0x08, 0x08, 0x3e, 0x00, 0x00], // 'i'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00, 0x30, 0x20, 0x20, 0x20, 0x20, 0x22, 0x22, 0x1c], // 'j'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02, 0x02, 0x22, 0x12, 0x0e, 0x12, 0x22, 0x42, 0x00, 0x00], // 'k'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x3e, 0x00, 0x00], // 'l'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x37, 0x49, 0x49, 0x49, 0x49, 0x41, 0x00, 0x00], // 'm'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3a, 0x46, 0x42, 0x42, 0x42, 0x42, 0x00, 0x00], // 'n'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x42, 0x42, 0x42, 0x3c, 0x00, 0x00], // 'o'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3a, 0x46, 0x42, 0x46, 0x3a, 0x02, 0x02, 0x02], // 'p'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x5c, 0x62, 0x42, 0x62, 0x5c, 0x40, 0x40, 0x40], // 'q'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3a, 0x44, 0x04, 0x04, 0x04, 0x04, 0x00, 0x00], // 'r'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x0c, 0x30, 0x42, 0x3c, 0x00, 0x00], // 's'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x04, 0x3e, 0x04, 0x04, 0x04, 0x44, 0x38, 0x00, 0x00], // 't'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x22, 0x22, 0x22, 0x22, 0x22, 0x5c, 0x00, 0x00], // 'u'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x22, 0x22, 0x22, 0x14, 0x14, 0x08, 0x00, 0x00], // 'v'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x41, 0x41, 0x49, 0x49, 0x55, 0x22, 0x00, 0x00], // 'w'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x42, 0x24, 0x18, 0x18, 0x24, 0x42, 0x00, 0x00], // 'x'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x42, 0x42, 0x42, 0x62, 0x5c, 0x40, 0x42, 0x3c], // 'y'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x20, 0x10, 0x08, 0x04, 0x7e, 0x00, 0x00], // 'z'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x70, 0x08, 0x08, 0x10, 0x0c, 0x10, 0x08, 0x08, 0x70, 0x00, 0x00], // '{'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x00, 0x00], // '|'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0e, 0x10, 0x10, 0x08, 0x30, 0x08, 0x10, 0x10, 0x0e, 0x00, 0x00], // '}'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x24, 0x2a, 0x12, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '~'
+];
+
+pub fn ascii_glyph(c: char) -> Option<&'static [u8; 16]> {
+    match c {
+        ' '..='~' => Some(&ASCII_GLYPHS[(c as usize) - 0x20]),
+        _ => None,
+    }
+}
+
+// 半角显示的字符：ASCII 以及拉丁、希腊、西里尔字母。原版文字中不会出现后几种
+pub fn is_half_width(c: char) -> bool {
+    c.is_ascii() || ('\u{a0}'..='\u{4ff}').contains(&c)
+}
+
+struct BdfGlyph {
+    width: i32,
+    height: i32,
+    x_offset: i32,
+    y_offset: i32, // 底部相对基线的位置，向上为正
+    rows: Vec<u32>, // 每行最多 32 位，最高位为最左边
+}
+
+// BDF 点阵字体，按 Unicode 编码读取
+pub struct BdfFont {
+    glyphs: HashMap<char, BdfGlyph>,
+}
+
+impl BdfFont {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut glyphs = HashMap::new();
+        let mut encoding = None;
+        let mut bbx = [0; 4];
+        let mut rows: Option<Vec<u32>> = None;
+
+        for line in text.lines() {
+            let mut fields = line.split_whitespace();
+            let keyword = fields.next().unwrap_or("");
+            if let Some(rows) = rows.as_mut() {
+                if !keyword.is_empty() && keyword != "ENDCHAR" {
+                    // 只保留每行的前 32 位
+                    let hex = &keyword[..keyword.len().min(8)];
+                    let bits = u32::from_str_radix(hex, 16).map_err(|_| format!("invalid BDF bitmap: {}", line))?;
+                    rows.push(bits << (32 - 4 * hex.len()));
+                    continue;
+                }
+            }
+            let numbers: Vec<i32> = fields.filter_map(|field| field.parse().ok()).collect();
+            match keyword {
+                "STARTCHAR" => {
+                    encoding = None;
+                }
+                "ENCODING" => {
+                    encoding = numbers.first().and_then(|&code| char::from_u32(code as u32));
+                }
+                "BBX" if numbers.len() == 4 => {
+                    bbx.copy_from_slice(&numbers);
+                }
+                "BITMAP" => {
+                    rows = Some(Vec::new());
+                }
+                "ENDCHAR" => {
+                    if let (Some(c), Some(rows)) = (encoding, rows.take()) {
+                        let [width, height, x_offset, y_offset] = bbx;
+                        glyphs.insert(c, BdfGlyph { width, height, x_offset, y_offset, rows });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if glyphs.is_empty() {
+            return Err("no glyphs in BDF font".into());
+        }
+
+        Ok(Self { glyphs })
+    }
+
+    pub fn render(&self, c: char) -> Option<[u8; 32]> {
+        let glyph = self.glyphs.get(&c)?;
+        let cell_width = (if is_half_width(c) { HALF_WIDTH } else { FULL_WIDTH }) as i32;
+        let width = glyph.width.min(32);
+        // 放不下时先减小左边的偏移，仍然太宽就横向缩小到字形宽度
+        let x_offset = glyph.x_offset.max(0).min((cell_width - width).max(0));
+        let top = BASELINE - glyph.y_offset - glyph.height;
+        let mut cell = [0; 32];
+        for (i, &bits) in glyph.rows.iter().enumerate() {
+            for x in 0..width {
+                if bits & (0x8000_0000 >> x) != 0 {
+                    let x = if width > cell_width { (x * cell_width) / width } else { x };
+                    set_pixel(&mut cell, x_offset + x, top + (i as i32));
+                }
+            }
+        }
+
+        Some(cell)
+    }
+}
+
+// 16x16 字形：每行两个字节（小端序），位 x 对应第 x 列，超出范围的点忽略
+fn set_pixel(cell: &mut [u8; 32], x: i32, y: i32) {
+    if (0..16).contains(&x) && (0..16).contains(&y) {
+        let bits = u16::from_le_bytes([cell[(y * 2) as usize], cell[(y * 2 + 1) as usize]]) | (1 << x);
+        cell[(y * 2) as usize..(y * 2 + 2) as usize].copy_from_slice(&bits.to_le_bytes());
+    }
+}
+
+// 补充字形用的字体。TTF 需要 ttf 特性
+pub enum FallbackFont {
+    Bdf(BdfFont),
+    #[cfg(feature = "ttf")]
+    Ttf(Box<fontdue::Font>),
+}
+
+impl FallbackFont {
+    // 按扩展名识别 .bdf 和 .ttf/.otf
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("").to_ascii_lowercase();
+        match extension.as_str() {
+            "bdf" => Ok(FallbackFont::Bdf(BdfFont::parse(&std::fs::read_to_string(path)?)?)),
+            #[cfg(feature = "ttf")]
+            "ttf" | "otf" => {
+                let font = fontdue::Font::from_bytes(std::fs::read(path)?, fontdue::FontSettings::default())?;
+                Ok(FallbackFont::Ttf(Box::new(font)))
+            }
+            #[cfg(not(feature = "ttf"))]
+            "ttf" | "otf" => Err("TTF fonts need the ttf feature".into()),
+            _ => Err(format!("unsupported font: {}", path.display()).into()),
+        }
+    }
+
+    // 渲染成 16x16 字形，字体中没有这个字符时返回 None
+    pub fn render(&self, c: char) -> Option<[u8; 32]> {
+        match self {
+            FallbackFont::Bdf(font) => font.render(c),
+            #[cfg(feature = "ttf")]
+            FallbackFont::Ttf(font) => {
+                if font.lookup_glyph_index(c) == 0 {
+                    return None;
+                }
+                let (width, size) = if is_half_width(c) { (HALF_WIDTH, 13.0) } else { (FULL_WIDTH, 15.0) };
+                let (mut metrics, mut coverage) = font.rasterize(c, size);
+                // 太宽的字形缩小到放得下
+                if metrics.width > (width as usize) {
+                    (metrics, coverage) = font.rasterize(c, size * (width as f32) / (metrics.width as f32));
+                }
+                let x_offset = metrics.xmin.max(0).min(((width as i32) - (metrics.width as i32)).max(0));
+                let top = BASELINE - metrics.ymin - (metrics.height as i32);
+                let mut cell = [0; 32];
+                for (i, &value) in coverage.iter().enumerate() {
+                    if value >= 128 {
+                        let x = x_offset + ((i % metrics.width) as i32);
+                        set_pixel(&mut cell, x, top + ((i / metrics.width) as i32));
+                    }
+                }
+                Some(cell)
+            }
+        }
+    }
+}
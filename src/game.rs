@@ -1,14 +1,24 @@
-use std::time::Duration;
-use std::time::Instant;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 use minifb::{ Window, WindowOptions };
 
+use crate::audio::{ Audio, SAMPLE_RATE };
+use crate::audio_sink::open_audio_sink;
+use crate::battle::Battle;
 use crate::canvas::*;
+use crate::config::Config;
 use crate::data::GameData;
 use crate::data::GameState;
 use crate::data::MKFs;
-use crate::input::InputState;
+use crate::font::FallbackFont;
+use crate::gamepad::Gamepad;
+use crate::input::{ gamepad_bindings, key_bindings, InputState };
+use crate::mixer::Mixer;
+use crate::palette_effects::*;
+use crate::scheduler::Scheduler;
 use crate::play::Resource;
+use crate::replay::InputMode;
 use crate::sprite::*;
 use crate::ui::*;
 use crate::utils::*;
@@ -22,75 +32,106 @@ const SPRITENUM_SPLASH_TITLE: u32 = 0x47;
 const SPRITENUM_SPLASH_CRANE: u32 = 0x49;
 
 // MIDI
-const NUM_RIX_TITLE: u32 = 0x05;
+const NUM_RIX_TITLE: u16 = 0x05;
 
 const WIDTH: usize = 320;
 const HEIGHT: usize = 200;
 
 pub struct Game {
-    pub window: Window,
+    pub window: Option<Window>, // 无界面运行时为 None
     pub canvas: Canvas,
     pub ui: UI,
     pub input: InputState,
+    pub input_mode: InputMode,
+    pub gamepad: Gamepad,
+    pub config: Config,
+    pub audio: Mixer,
+    pub sound_cache: HashMap<u16, Arc<[i16]>>, // 已重采样的音效，按 VOC.MKF 中的序号
 
-    pub start_time: Instant, // for tick
+    pub scheduler: Scheduler,
     pub mkf: MKFs,
     pub data: GameData,
     pub state: GameState,
     pub resource: Option<Resource>,
+    pub battle: Option<Battle>,
+    pub auto_battle: bool, // 下一场战斗自动进行
     pub ui_sprite: Vec<SpriteFrame>,
+    pub script_success: bool,
+    pub current_save_slot: u16,
+    pub pending_load: Option<u16>, // 脚本要求重新读档时的存档号
+    pub quit: bool, // 在系统菜单中选择了结束游戏
 }
 
 impl Game {
-    pub fn new() -> Result<Self> {
-        let window = Window::new("PAL(DOS Version) - Rust Edition", WIDTH, HEIGHT, WindowOptions {
-            resize: true,
-            scale: minifb::Scale::X2,
-            ..WindowOptions::default()
-        })?;
-        
+    pub fn new(config: Config) -> Result<Self> {
+        let window = if config.headless {
+            None
+        } else {
+            Some(Window::new("PAL(DOS Version) - Rust Edition", WIDTH, HEIGHT, WindowOptions {
+                resize: true,
+                scale: config.scale(),
+                ..WindowOptions::default()
+            })?)
+        };
+
+        set_data_dir(&config.data_dir);
         let mut mkf = MKFs::open()?;
-        let ui = UI::load(&mut mkf.data)?;
+        let mut ui = UI::load(&mut mkf.data, config.text_encoding)?;
+        if let Some(path) = &config.fallback_font {
+            ui.add_fallback_glyphs(&FallbackFont::load(path)?);
+        }
         let data = GameData::load(&mut mkf.sss, &mut mkf.data)?;
-        let state = GameState::load_new_game(&mut mkf.sss)?;
+        let state = GameState::load_new_game(&mut mkf.sss, &data)?;
+
+        let mut audio = Audio::new(SAMPLE_RATE);
+        audio.volume = config.volume;
+        let sink = open_audio_sink(&config.audio_output, SAMPLE_RATE)?;
 
-        Ok(Self {
+        let mut game = Self {
             window,
             canvas: Canvas::new(WIDTH, HEIGHT),
             ui,
-            input: InputState::new(),
-            start_time: Instant::now(),
+            input: InputState::with_bindings(key_bindings(&config.keys)?, gamepad_bindings(&config.gamepad)?),
+            input_mode: InputMode::Live,
+            gamepad: Gamepad::new(),
+            config,
+            audio: Mixer::start(audio, sink),
+            sound_cache: HashMap::new(),
+            scheduler: Scheduler::new(),
             mkf,
             data,
             state,
             resource: None,
+            battle: None,
+            auto_battle: false,
             ui_sprite: Vec::new(),
-        })
+            script_success: true,
+            current_save_slot: 0,
+            pending_load: None,
+            quit: false,
+        };
+        game.update_equipments()?;
+
+        Ok(game)
     }
 
-    pub fn get_palette(&mut self, palette_id: u32) -> Result<Palette> {
+    pub fn get_palette(&mut self, palette_id: u32, night: bool) -> Result<Palette> {
         let buf = self.mkf.pat.read_chunk(palette_id)?;
-        let mut colors = Vec::<Color>::with_capacity(256);
-        for i in 0..256 {
-            let r = buf[i * 3] << 2;
-            let g = buf[i * 3 + 1] << 2;
-            let b = buf[i * 3 + 2] << 2;
-
-            colors.push(Color::from_rgb(r, g, b));
-        }
 
-        Ok(Palette::with_colors(colors))
+        Ok(decode_palette(&buf, night))
     }
 
-    pub fn set_palette(&mut self, palette_id: u32) -> Result<()> {
-        let pal = self.get_palette(palette_id)?;
+    pub fn set_palette(&mut self, palette_id: u32, night: bool) -> Result<()> {
+        let pal = self.get_palette(palette_id, night)?;
         self.canvas.set_palette(&pal);
 
         Ok(())
     }
 
     pub fn blit_to_screen(&mut self) -> Result<()> {
-        self.window.update_with_buffer(self.canvas.get_buffer(), WIDTH, HEIGHT)?;
+        if let Some(window) = self.window.as_mut() {
+            window.update_with_buffer(self.canvas.get_buffer(), WIDTH, HEIGHT)?;
+        }
 
         Ok(())
     }
@@ -108,8 +149,8 @@ impl Game {
             sprite_id: u32,
         }
 
-        let pal = self.get_palette(1)?;
-        let mut fadein_pal = Palette::new();
+        let pal = self.get_palette(1, false)?;
+        let fade = PaletteFade::fade_in(&pal, 15000 / 64);
 
         // 开场的那个从下往上的山是由两个图片拼接的，一个在上面，一个在下面。尺寸是320x200
         let splash_down_bitmap = self.mkf.fbp.read_chunk_decompressed(BITMAPNUM_SPLASH_DOWN)?;
@@ -130,40 +171,24 @@ impl Game {
         let mut cranes = Vec::<Crane>::with_capacity(8);
         for _ in 0..cranes.capacity() {
             cranes.push(Crane {
-                x: ((rand::random::<usize>() % 320) + 320) as isize,
-                y: ((rand::random::<usize>() % 80) + 80) as isize,
-                sprite_id: rand::random::<u32>() % 8,
+                x: (self.state.rng.random_long(0, 319) + 320) as isize,
+                y: (self.state.rng.random_long(0, 79) + 80) as isize,
+                sprite_id: self.state.rng.random_long(0, 7) as u32,
             });
         }
 
-        let begin_time = Instant::now();
+        let begin_time = self.ticks();
         let mut h_offset = 0;
 
-        /*
-        let chunk = self.midi_mkf.read_chunk()?;
-        let rw = sdl2::rwops::RWops::from_bytes(&chunk)?;
-        let music = rw.load_music()?;
-        music.play(-1)?;
-        */
-        //self.play_midi(NUM_RIX_TITLE, -1)?;
+        self.play_music(NUM_RIX_TITLE, true, 2.0)?;
 
         let mut i = 0;
         'running: loop {
             i += 1;
-            let elapsed_time = Instant::now() - begin_time;
-
-            if elapsed_time < Duration::from_millis(15000) {
-                let ratio = elapsed_time.as_secs_f32() / 15_f32;
-                for i in 0..256 {
-                    let (r, g, b) = pal.colors[i].to_rgb();
-                    fadein_pal.colors[i] = Color::from_rgb(
-                        ((r as f32) * ratio) as u8,
-                        ((g as f32) * ratio) as u8,
-                        ((b as f32) * ratio) as u8
-                    );
-                }
+            let elapsed_time = self.ticks() - begin_time;
 
-                self.canvas.set_palette(&fadein_pal);
+            if elapsed_time < fade.duration() {
+                self.canvas.set_palette(fade.palette_at(elapsed_time));
             }
 
             if h_offset < 200 {
@@ -200,20 +225,21 @@ impl Game {
             });
 
             self.blit_to_screen()?;
-            self.process_event();
+            self.process_event()?;
 
             if self.input.is_any_pressed() {
                 break 'running;
             }
 
-            std::thread::sleep(Duration::from_millis(30));
+            self.delay(30);
         }
 
         Ok(())
     }
 
     fn opening_menu_screen(&mut self) -> Result<()> {
-        self.set_palette(0)?;
+        self.set_palette(0, false)?;
+        self.play_music(RIX_NUM_OPENINGMENU, true, 1.0)?;
 
         let menu_items = [
             MenuItem { value: 0, num_word: MAINMENU_LABEL_NEWGAME, enabled: true, x: 125, y: 95 },
@@ -228,24 +254,14 @@ impl Game {
             });
 
             let menu_selected = self.read_menu(&menu_items)?;
-            if menu_selected == 1 {
+            if menu_selected == MENUITEM_VALUE_CANCELLED {
+                continue;
+            } else if menu_selected == 1 {
                 // 存档选择
-                let mut menu_items = Vec::<MenuItem>::new();
-                for i in 0..5 {
-                    menu_items.push(MenuItem {
-                        value: i + 1,
-                        num_word: LOADMENU_LABEL_SLOT_FIRST + (i as u32),
-                        enabled: true,
-                        x: 210,
-                        y: 17 + 38 * i,
-                    });
-                    self.draw_signle_linebox_with_shadow(
-                        Pos { x: 195, y: 7 + 38 * (i as isize) },
-                        6
-                    );
+                let slot = self.save_slot_menu(1)?;
+                if slot != MENUITEM_VALUE_CANCELLED && self.load_game(slot).is_ok() {
+                    break 'running;
                 }
-
-                self.read_menu(&menu_items)?;
             } else {
                 break 'running;
             }
@@ -254,11 +270,28 @@ impl Game {
         Ok(())
     }
 
+    // 保存系统菜单修改的一项设置，失败时不影响游戏
+    pub fn save_config<V: Into<toml::Value>>(&self, name: &str, value: V) {
+        if let Err(e) = self.config.save_setting(name, value) {
+            eprintln!("cannot save {}: {}", name, e);
+        }
+    }
+
     pub fn run(&mut self) -> Result<()> {
-        //self.trademark_screen()?;
-        //self.splash_screen()?;
-        //self.opening_menu_screen()?;
+        if !self.config.skip_intro {
+            self.trademark_screen()?;
+            self.splash_screen()?;
+        }
+        self.opening_menu_screen()?;
+
+        let record = self.config.record.clone();
+        if record.is_some() {
+            self.start_recording()?;
+        }
         self.mainloop()?;
+        if let Some(path) = record {
+            self.finish_recording(path)?;
+        }
 
         Ok(())
     }
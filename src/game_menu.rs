@@ -0,0 +1,649 @@
+use crate::data::*;
+use crate::game::Game;
+use crate::input::PalKey;
+use crate::inventory::*;
+use crate::item_menu::GridItem;
+use crate::save::MAX_SAVE_SLOTS;
+use crate::sprite::draw_sprite_frame;
+use crate::ui::*;
+use crate::utils::*;
+
+const GAMEMENU_LABEL_STATUS: u32 = 3;
+const GAMEMENU_LABEL_MAGIC: u32 = 4;
+const GAMEMENU_LABEL_INVENTORY: u32 = 5;
+const GAMEMENU_LABEL_SYSTEM: u32 = 6;
+
+const SYSMENU_LABEL_SAVE: u32 = 11;
+const SYSMENU_LABEL_LOAD: u32 = 12;
+const SYSMENU_LABEL_MUSIC: u32 = 13;
+const SYSMENU_LABEL_SOUND: u32 = 14;
+const SYSMENU_LABEL_QUIT: u32 = 15;
+
+const SWITCHMENU_LABEL_DISABLE: u32 = 17;
+const SWITCHMENU_LABEL_ENABLE: u32 = 18;
+
+const CONFIRMMENU_LABEL_NO: u32 = 19;
+const CONFIRMMENU_LABEL_YES: u32 = 20;
+
+const INVENTORYMENU_LABEL_EQUIP: u32 = 22;
+const INVENTORYMENU_LABEL_USE: u32 = 23;
+
+const STATUS_LABEL_EXP: u32 = 2;
+const STATUS_LABEL_LEVEL: u32 = 48;
+const STATUS_LABEL_HP: u32 = 49;
+const STATUS_LABEL_MP: u32 = 50;
+const STATUS_LABEL_ATTACKPOWER: u32 = 51;
+const STATUS_LABEL_MAGICPOWER: u32 = 52;
+const STATUS_LABEL_RESISTANCE: u32 = 53;
+const STATUS_LABEL_DEXTERITY: u32 = 54;
+const STATUS_LABEL_FLEERATE: u32 = 55;
+
+const STATUS_BACKGROUND_FBPNUM: u32 = 0;
+const EQUIPMENU_BACKGROUND_FBPNUM: u32 = 1;
+
+const SPRITENUM_SLASH: usize = 39;
+const STATUS_COLOR_EQUIPMENT: u8 = 0xbe;
+
+// 状态画面中各件装备名称的位置
+const EQUIP_POS: [(isize, isize); MAX_PLAYER_EQUIPMENTS] = [
+    (190, 0),
+    (248, 40),
+    (252, 102),
+    (202, 134),
+    (142, 142),
+    (180, 100),
+];
+
+// 状态和装备画面显示的属性：攻击、灵力、防御、身法、吉运
+const STAT_LABELS: [u32; 5] = [
+    STATUS_LABEL_ATTACKPOWER,
+    STATUS_LABEL_MAGICPOWER,
+    STATUS_LABEL_RESISTANCE,
+    STATUS_LABEL_DEXTERITY,
+    STATUS_LABEL_FLEERATE,
+];
+
+fn player_stats(player: &crate::party::Player) -> [u16; 5] {
+    [player.attack_strength, player.magic_strength, player.defense, player.dexterity, player.flee_rate]
+}
+
+impl Game {
+    fn restore_pixels(&mut self, background: &[u8]) {
+        self.canvas.set_pixels(|pixels: &mut [u8]| {
+            pixels.copy_from_slice(background);
+        });
+    }
+
+    fn draw_fbp(&mut self, fbp_num: u32) -> Result<()> {
+        let bitmap = self.mkf.fbp.read_chunk_decompressed(fbp_num)?;
+        self.restore_pixels(&bitmap);
+
+        Ok(())
+    }
+
+    // 游戏中按菜单键打开的主菜单
+    pub fn in_game_menu(&mut self) -> Result<()> {
+        let menu_items = [
+            MenuItem { value: 1, num_word: GAMEMENU_LABEL_STATUS, enabled: true, x: 16, y: 50 },
+            MenuItem { value: 2, num_word: GAMEMENU_LABEL_MAGIC, enabled: true, x: 16, y: 50 + 18 },
+            MenuItem { value: 3, num_word: GAMEMENU_LABEL_INVENTORY, enabled: true, x: 16, y: 50 + 36 },
+            MenuItem { value: 4, num_word: GAMEMENU_LABEL_SYSTEM, enabled: true, x: 16, y: 50 + 54 },
+        ];
+
+        self.make_scence();
+        let background = self.canvas.get_pixels().to_vec();
+        let mut selected = 0;
+        loop {
+            self.restore_pixels(&background);
+            self.draw_cash();
+            self.draw_box(Pos { x: 3, y: 37 }, 3, 2);
+
+            let value = self.read_menu_with(&menu_items, selected, |_, _| Ok(()))?;
+            if value == MENUITEM_VALUE_CANCELLED {
+                break;
+            }
+            selected = (value as usize) - 1;
+
+            let done = match value {
+                1 => {
+                    self.player_status()?;
+                    true
+                }
+                2 => {
+                    self.in_game_magic_menu()?;
+                    true
+                }
+                3 => self.inventory_menu()?,
+                _ => self.system_menu()?,
+            };
+            if done {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    // 存档选择菜单，返回存档号或 MENUITEM_VALUE_CANCELLED
+    pub fn save_slot_menu(&mut self, default_slot: u16) -> Result<u16> {
+        let mut menu_items = Vec::<MenuItem>::new();
+        for i in 0..MAX_SAVE_SLOTS {
+            menu_items.push(MenuItem {
+                value: i + 1,
+                num_word: LOADMENU_LABEL_SLOT_FIRST + (i as u32),
+                enabled: true,
+                x: 210,
+                y: 17 + 38 * i,
+            });
+            self.draw_signle_linebox_with_shadow(Pos { x: 195, y: 7 + 38 * (i as isize) }, 6);
+
+            // 存档的保存次数
+            let saved_times = self.get_saved_times(i + 1);
+            self.draw_number(
+                saved_times as u32,
+                4,
+                Pos { x: 270, y: (38 * i + 17) as isize },
+                NumColor::Yellow,
+                NumAlign::Right
+            );
+        }
+
+        let default_index = (default_slot as usize).saturating_sub(1);
+        self.read_menu_with(&menu_items, default_index, |_, _| Ok(()))
+    }
+
+    // 开/关选择，返回新的开关状态，取消时返回 None
+    fn switch_menu(&mut self, enabled: bool) -> Result<Option<bool>> {
+        let menu_items = [
+            MenuItem { value: 0, num_word: SWITCHMENU_LABEL_DISABLE, enabled: true, x: 145, y: 98 },
+            MenuItem { value: 1, num_word: SWITCHMENU_LABEL_ENABLE, enabled: true, x: 145, y: 98 + 18 },
+        ];
+
+        self.draw_box(Pos { x: 130, y: 88 }, 1, 2);
+        let value = self.read_menu_with(&menu_items, enabled as usize, |_, _| Ok(()))?;
+        if value == MENUITEM_VALUE_CANCELLED {
+            return Ok(None);
+        }
+
+        Ok(Some(value == 1))
+    }
+
+    // 是/否确认，默认选“否”
+    pub fn confirm_menu(&mut self) -> Result<bool> {
+        let menu_items = [
+            MenuItem { value: 0, num_word: CONFIRMMENU_LABEL_NO, enabled: true, x: 146, y: 112 },
+            MenuItem { value: 1, num_word: CONFIRMMENU_LABEL_YES, enabled: true, x: 221, y: 112 },
+        ];
+
+        for i in 0..2 {
+            self.draw_signle_linebox_with_shadow(Pos { x: 130 + 75 * i, y: 100 }, 2);
+        }
+        let value = self.read_menu_with(&menu_items, 0, |_, _| Ok(()))?;
+
+        Ok(value == 1)
+    }
+
+    // 返回 true 表示完成了操作，应关闭整个菜单
+    fn system_menu(&mut self) -> Result<bool> {
+        let menu_items = [
+            MenuItem { value: 1, num_word: SYSMENU_LABEL_SAVE, enabled: true, x: 53, y: 72 },
+            MenuItem { value: 2, num_word: SYSMENU_LABEL_LOAD, enabled: true, x: 53, y: 72 + 18 },
+            MenuItem { value: 3, num_word: SYSMENU_LABEL_MUSIC, enabled: true, x: 53, y: 72 + 36 },
+            MenuItem { value: 4, num_word: SYSMENU_LABEL_SOUND, enabled: true, x: 53, y: 72 + 54 },
+            MenuItem { value: 5, num_word: SYSMENU_LABEL_QUIT, enabled: true, x: 53, y: 72 + 72 },
+        ];
+
+        self.draw_box(Pos { x: 40, y: 60 }, 4, 3);
+        let value = self.read_menu_with(&menu_items, 0, |_, _| Ok(()))?;
+
+        match value {
+            1 => {
+                let slot = self.save_slot_menu(self.current_save_slot)?;
+                if slot == MENUITEM_VALUE_CANCELLED {
+                    return Ok(false);
+                }
+                self.save_game(slot)?;
+            }
+            2 => {
+                let slot = self.save_slot_menu(self.current_save_slot)?;
+                if slot == MENUITEM_VALUE_CANCELLED || self.get_saved_times(slot) == 0 {
+                    return Ok(false);
+                }
+                // 下一个逻辑帧读档
+                self.fade_out(1)?;
+                self.pending_load = Some(slot);
+            }
+            3 => match self.switch_menu(self.config.music_enabled)? {
+                Some(enabled) => {
+                    self.config.music_enabled = enabled;
+                    self.save_config("music_enabled", enabled);
+                    self.play_music(self.state.num_music, true, 0.0)?;
+                }
+                None => {
+                    return Ok(false);
+                }
+            }
+            4 => match self.switch_menu(self.config.sound_enabled)? {
+                Some(enabled) => {
+                    self.config.sound_enabled = enabled;
+                    self.save_config("sound_enabled", enabled);
+                }
+                None => {
+                    return Ok(false);
+                }
+            }
+            5 => {
+                if !self.confirm_menu()? {
+                    return Ok(false);
+                }
+                self.quit = true;
+            }
+            _ => {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    // 物品菜单：装备或使用
+    fn inventory_menu(&mut self) -> Result<bool> {
+        let menu_items = [
+            MenuItem { value: 1, num_word: INVENTORYMENU_LABEL_EQUIP, enabled: true, x: 43, y: 73 },
+            MenuItem { value: 2, num_word: INVENTORYMENU_LABEL_USE, enabled: true, x: 43, y: 73 + 18 },
+        ];
+
+        self.draw_box(Pos { x: 30, y: 60 }, 1, 1);
+        match self.read_menu_with(&menu_items, 0, |_, _| Ok(()))? {
+            1 => self.game_equip_item()?,
+            2 => self.game_use_item()?,
+            _ => {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    // 在 HP/MP 框中显示角色的状态
+    pub fn draw_player_hp_mp(&mut self, player_role: usize, pos: Pos) {
+        let player = self.state.player(player_role);
+
+        self.draw_signle_linebox_with_shadow(pos, 5);
+        self.draw_word_at(player.name as u32, Pos { x: pos.x + 10, y: pos.y + 10 }, MENUITEM_COLOR_CONFIRMED);
+        for (i, (value, max)) in [(player.hp, player.max_hp), (player.mp, player.max_mp)].into_iter().enumerate() {
+            let x = pos.x + 50 + 44 * (i as isize);
+            let y = pos.y + 10;
+            self.draw_number(value as u32, 4, Pos { x, y }, NumColor::Yellow, NumAlign::Right);
+            self.draw_number(max as u32, 4, Pos { x, y: y + 7 }, NumColor::Blue, NumAlign::Right);
+        }
+    }
+
+    // 选择队伍成员，返回其在队伍中的位置。only_alive 为 true 时阵亡的角色不能选择
+    pub fn select_party_member(&mut self, only_alive: bool) -> Result<Option<usize>> {
+        let roles = self.state.party_roles();
+        let menu_items: Vec<MenuItem> = roles
+            .iter()
+            .enumerate()
+            .map(|(i, &role)| MenuItem {
+                value: i as u16,
+                num_word: self.state.player_roles.name[role] as u32,
+                enabled: !only_alive || self.state.player_roles.hp[role] > 0,
+                x: 48,
+                y: 75 + 18 * (i as u16),
+            })
+            .collect();
+
+        self.draw_box(Pos { x: 35, y: 62 }, (roles.len() as u32).saturating_sub(1), 2);
+        let value = self.read_menu_with(&menu_items, 0, |game, value| {
+            game.draw_player_hp_mp(roles[value as usize], Pos { x: 120, y: 150 });
+            Ok(())
+        })?;
+
+        Ok(if value == MENUITEM_VALUE_CANCELLED { None } else { Some(value as usize) })
+    }
+
+    // 角色状态画面，上下左右或 PgUp/PgDn 切换角色
+    pub fn player_status(&mut self) -> Result<()> {
+        let roles = self.state.party_roles();
+        let mut current = 0;
+
+        loop {
+            let role = roles[current];
+            let player = self.state.player(role);
+
+            self.draw_fbp(STATUS_BACKGROUND_FBPNUM)?;
+
+            let labels = [STATUS_LABEL_EXP, STATUS_LABEL_LEVEL, STATUS_LABEL_HP, STATUS_LABEL_MP];
+            for (label, y) in labels.into_iter().zip([6, 32, 54, 76]) {
+                self.draw_word_at(label, Pos { x: 6, y }, MENUITEM_COLOR);
+            }
+            for (i, label) in STAT_LABELS.into_iter().enumerate() {
+                self.draw_word_at(label, Pos { x: 6, y: 98 + 20 * (i as isize) }, MENUITEM_COLOR);
+            }
+            self.draw_word_at(player.name as u32, Pos { x: 110, y: 8 }, MENUITEM_COLOR_CONFIRMED);
+
+            let exp = self.state.exp.primary_exp[role].exp;
+            let next_exp = self.data.exp_for_level(player.level as usize).unwrap_or(0);
+            self.draw_number(exp as u32, 5, Pos { x: 58, y: 6 }, NumColor::Yellow, NumAlign::Right);
+            self.draw_number(next_exp as u32, 5, Pos { x: 58, y: 15 }, NumColor::Cyan, NumAlign::Right);
+            self.draw_number(player.level as u32, 2, Pos { x: 54, y: 35 }, NumColor::Yellow, NumAlign::Right);
+
+            let slash = &self.ui.sprite[SPRITENUM_SLASH];
+            self.canvas.set_pixels(|pixels: &mut [u8]| {
+                draw_sprite_frame(slash, pixels, 320, 200, 65, 58);
+                draw_sprite_frame(slash, pixels, 320, 200, 65, 80);
+            });
+            self.draw_number(player.hp as u32, 4, Pos { x: 42, y: 56 }, NumColor::Yellow, NumAlign::Right);
+            self.draw_number(player.max_hp as u32, 4, Pos { x: 63, y: 61 }, NumColor::Blue, NumAlign::Right);
+            self.draw_number(player.mp as u32, 4, Pos { x: 42, y: 78 }, NumColor::Yellow, NumAlign::Right);
+            self.draw_number(player.max_mp as u32, 4, Pos { x: 63, y: 83 }, NumColor::Blue, NumAlign::Right);
+
+            for (i, value) in player_stats(&player).into_iter().enumerate() {
+                let y = 102 + 20 * (i as isize);
+                self.draw_number(value as u32, 4, Pos { x: 42, y }, NumColor::Yellow, NumAlign::Right);
+            }
+
+            for (i, &item) in player.equipment.iter().enumerate() {
+                if item != 0 {
+                    let (x, y) = EQUIP_POS[i];
+                    self.draw_word_at(item as u32, Pos { x: x + 5, y: y + 38 }, STATUS_COLOR_EQUIPMENT);
+                }
+            }
+
+            // 中毒状态，只显示等级不高于 3 的毒
+            let mut y = 58;
+            for poison in player.poisons.iter() {
+                if let Some(object) = self.state.object_poison(poison.poison_id) {
+                    if object.poison_level <= 3 {
+                        self.draw_word_at(poison.poison_id as u32, Pos { x: 185, y }, (object.color as u8).wrapping_add(10));
+                        y += 18;
+                    }
+                }
+            }
+
+            self.blit_to_screen()?;
+
+            // 等待按键
+            loop {
+                self.process_event()?;
+                if self.input.is_any_pressed() {
+                    break;
+                }
+                self.delay(30);
+            }
+
+            let len = roles.len();
+            if self.input.is_pressed(PalKey::Menu) || self.input.is_pressed(PalKey::Search) {
+                break;
+            } else if
+                self.input.is_pressed(PalKey::Up) ||
+                self.input.is_pressed(PalKey::Left) ||
+                self.input.is_pressed(PalKey::PgUp)
+            {
+                current = (current + len - 1) % len;
+            } else if
+                self.input.is_pressed(PalKey::Down) ||
+                self.input.is_pressed(PalKey::Right) ||
+                self.input.is_pressed(PalKey::PgDn)
+            {
+                current = (current + 1) % len;
+            }
+        }
+
+        Ok(())
+    }
+
+    // 使用物品
+    pub fn game_use_item(&mut self) -> Result<()> {
+        loop {
+            let item = self.item_select_menu(ITEM_FLAG_USABLE, |_, _| Ok(()))?;
+            if item == 0 {
+                return Ok(());
+            }
+
+            let flags = self.state.object_item(item).map_or(0, |item| item.flags);
+            if flags & ITEM_FLAG_APPLY_TO_ALL != 0 {
+                self.use_item(item, 0)?;
+                return Ok(());
+            }
+
+            // 选择使用对象，可以连续使用
+            while self.state.item_amount(item) > 0 {
+                let index = match self.select_party_member(false)? {
+                    Some(index) => index,
+                    None => {
+                        break;
+                    }
+                };
+                let role = self.state.party[index].player_role;
+                self.use_item(item, role)?;
+            }
+        }
+    }
+
+    // 装备物品
+    pub fn game_equip_item(&mut self) -> Result<()> {
+        loop {
+            let item = self.item_select_menu(ITEM_FLAG_EQUIPABLE, |_, _| Ok(()))?;
+            if item == 0 {
+                return Ok(());
+            }
+            self.equip_item_menu(item)?;
+        }
+    }
+
+    // 试着装备，返回装备后的属性值，不改变游戏状态。
+    // 装备脚本可能改动任何状态（包括随机数），之后整体恢复，调色板也一并恢复
+    fn preview_equip(&mut self, item: u16, player_role: usize) -> Result<Option<[u16; 5]>> {
+        let state = self.state.clone();
+        let palette = self.canvas.get_palette().clone();
+        let script_success = self.script_success;
+
+        let stats = if self.equip_item(item, player_role as u16)? {
+            Some(player_stats(&self.state.player(player_role)))
+        } else {
+            None
+        };
+
+        self.state = state;
+        self.canvas.set_palette(&palette);
+        self.script_success = script_success;
+
+        Ok(stats)
+    }
+
+    // 为队伍成员装备物品，显示装备前后的属性变化
+    pub fn equip_item_menu(&mut self, item: u16) -> Result<()> {
+        let roles = self.state.party_roles();
+        let mut current = 0;
+        // 试装备要运行装备脚本，只在选中的角色变化或装备后重新计算
+        let mut cached_preview = None;
+
+        loop {
+            let role = roles[current];
+            let player = self.state.player(role);
+            let preview = match cached_preview {
+                Some((cached_role, preview)) if cached_role == role => preview,
+                _ => {
+                    let preview = self.preview_equip(item, role)?;
+                    cached_preview = Some((role, preview));
+                    preview
+                }
+            };
+
+            self.draw_fbp(EQUIPMENU_BACKGROUND_FBPNUM)?;
+
+            for (i, &equipment) in player.equipment.iter().enumerate() {
+                if equipment != 0 {
+                    self.draw_word_at(equipment as u32, Pos { x: 130, y: 11 + 22 * (i as isize) }, MENUITEM_COLOR);
+                }
+            }
+
+            let stats = player_stats(&player);
+            for (i, &value) in stats.iter().enumerate() {
+                let y = 14 + 22 * (i as isize);
+                self.draw_number(value as u32, 4, Pos { x: 260, y }, NumColor::Cyan, NumAlign::Right);
+
+                // 装备后的属性
+                if let Some(new_value) = preview.map(|preview| preview[i]) {
+                    if new_value != value {
+                        let color = if new_value > value { NumColor::Yellow } else { NumColor::Blue };
+                        self.draw_number(new_value as u32, 4, Pos { x: 292, y }, color, NumAlign::Right);
+                    }
+                }
+            }
+
+            self.draw_box(Pos { x: 2, y: 95 }, (roles.len() as u32).saturating_sub(1), 2);
+            let equipable = self.state.object_item(item).map_or(0, |o| o.flags) & ITEM_FLAG_EQUIPABLE != 0;
+            let selected_color = self.menu_color_selected();
+            for (i, &r) in roles.iter().enumerate() {
+                let can_equip = equipable && self.state.object_item(item).is_some_and(|o| o.is_equipable_by(r));
+                let color = match (can_equip, i == current) {
+                    (true, true) => selected_color,
+                    (true, false) => MENUITEM_COLOR,
+                    (false, true) => MENUITEM_COLOR_SELECTED_INACTIVE,
+                    (false, false) => MENUITEM_COLOR_INACTIVE,
+                };
+                let name = self.state.player_roles.name[r] as u32;
+                self.draw_word_at(name, Pos { x: 15, y: 108 + 18 * (i as isize) }, color);
+            }
+
+            self.draw_word_at(item as u32, Pos { x: 5, y: 70 }, MENUITEM_COLOR_CONFIRMED);
+            self.draw_number(self.state.item_amount(item) as u32, 2, Pos { x: 65, y: 73 }, NumColor::Cyan, NumAlign::Right);
+
+            self.blit_to_screen()?;
+            self.process_event()?;
+
+            let len = roles.len();
+            if self.input.is_pressed(PalKey::Menu) {
+                break;
+            } else if self.input.is_pressed(PalKey::Up) || self.input.is_pressed(PalKey::Left) {
+                current = (current + len - 1) % len;
+            } else if self.input.is_pressed(PalKey::Down) || self.input.is_pressed(PalKey::Right) {
+                current = (current + 1) % len;
+            } else if self.input.is_pressed(PalKey::Search) && preview.is_some() {
+                self.equip_item(item, role as u16)?;
+                cached_preview = None;
+                if self.state.item_amount(item) == 0 {
+                    break;
+                }
+            }
+
+            self.delay(30);
+        }
+
+        Ok(())
+    }
+
+    // 法术的 MP 消耗
+    pub fn magic_cost(&self, magic: u16) -> u16 {
+        self.state
+            .object_magic(magic)
+            .and_then(|object| self.data.magic(object.magic_number as usize))
+            .map_or(0, |magic| magic.cost_mp)
+    }
+
+    // 选择角色的法术，只有战斗外可用且 MP 足够的法术可以选择。取消时返回 0
+    pub fn magic_selection_menu(&mut self, player_role: usize, in_battle: bool, default_magic: u16) -> Result<u16> {
+        let flag = if in_battle { MAGIC_FLAG_USABLE_IN_BATTLE } else { MAGIC_FLAG_USABLE_OUTSIDE_BATTLE };
+        let mp = self.state.player_roles.mp[player_role];
+
+        let magics = self.state.player(player_role).magics;
+        let items: Vec<GridItem> = magics
+            .iter()
+            .map(|&magic| {
+                let cost = self.magic_cost(magic);
+                let flags = self.state.object_magic(magic).map_or(0, |object| object.flags);
+                GridItem {
+                    value: magic,
+                    num_word: magic as u32,
+                    number: cost as u32,
+                    enabled: flags & flag != 0 && cost <= mp,
+                }
+            })
+            .collect();
+
+        let default_index = magics.iter().position(|&magic| magic == default_magic).unwrap_or(0);
+        let magic = self.grid_select_menu(&items, default_index, |game, _| {
+            game.draw_player_hp_mp(player_role, Pos { x: 120, y: 150 });
+            Ok(())
+        })?;
+
+        Ok(if magic == MENUITEM_VALUE_CANCELLED { 0 } else { magic })
+    }
+
+    // 在战斗外施法，target 为目标角色，全体法术忽略 target。成功时扣除 MP
+    pub fn use_magic(&mut self, player_role: usize, magic: u16, target: u16) -> Result<bool> {
+        let object = match self.state.object_magic(magic) {
+            Some(object) => object,
+            None => {
+                return Ok(false);
+            }
+        };
+        let target = if object.flags & MAGIC_FLAG_APPLY_TO_ALL != 0 { 0 } else { target };
+
+        let script_on_success = self.run_trigger_script(object.script_on_success, target)?;
+        self.update_magic(magic, |object| object.script_on_success = script_on_success);
+        if self.script_success {
+            let object = self.state.object_magic(magic).unwrap_or(object);
+            let script_on_use = self.run_trigger_script(object.script_on_use, target)?;
+            self.update_magic(magic, |object| object.script_on_use = script_on_use);
+        }
+
+        if self.script_success {
+            let cost = self.magic_cost(magic);
+            let mp = &mut self.state.player_roles.mp[player_role];
+            *mp = mp.saturating_sub(cost);
+        }
+
+        Ok(self.script_success)
+    }
+
+    pub fn update_magic<F: FnOnce(&mut ObjectMagic)>(&mut self, magic: u16, f: F) {
+        if let Some(object) = self.state.object_of_kind_mut(magic, ObjectKind::Magic) {
+            let mut value = object.magic();
+            f(&mut value);
+            object.set_magic(&value);
+        }
+    }
+
+    // 战斗外使用法术
+    pub fn in_game_magic_menu(&mut self) -> Result<()> {
+        let index = match self.select_party_member(true)? {
+            Some(index) => index,
+            None => {
+                return Ok(());
+            }
+        };
+        let role = self.state.party[index].player_role as usize;
+
+        let mut magic = 0;
+        loop {
+            magic = self.magic_selection_menu(role, false, magic)?;
+            if magic == 0 {
+                break;
+            }
+
+            let flags = self.state.object_magic(magic).map_or(0, |object| object.flags);
+            if flags & MAGIC_FLAG_APPLY_TO_ALL != 0 {
+                self.use_magic(role, magic, 0)?;
+                continue;
+            }
+
+            // 选择施法对象，可以连续施法
+            while self.state.player_roles.mp[role] >= self.magic_cost(magic) {
+                let target = match self.select_party_member(false)? {
+                    Some(target) => target,
+                    None => {
+                        break;
+                    }
+                };
+                let target = self.state.party[target].player_role;
+                self.use_magic(role, magic, target)?;
+            }
+        }
+
+        Ok(())
+    }
+}
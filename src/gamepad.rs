@@ -0,0 +1,162 @@
+// 手柄输入。实际读取手柄需要 gamepad 特性（gilrs），否则手柄始终没有输入
+
+// 摇杆偏离中心超过这个值才算按下方向
+const STICK_THRESHOLD: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamepadInput {
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    StickUp,
+    StickDown,
+    StickLeft,
+    StickRight,
+    South,
+    East,
+    North,
+    West,
+    LeftTrigger,
+    RightTrigger,
+    LeftTrigger2,
+    RightTrigger2,
+    Select,
+    Start,
+}
+
+// 配置文件中使用的名字
+const GAMEPAD_INPUT_NAMES: [(&str, GamepadInput); 18] = [
+    ("dpad_up", GamepadInput::DPadUp),
+    ("dpad_down", GamepadInput::DPadDown),
+    ("dpad_left", GamepadInput::DPadLeft),
+    ("dpad_right", GamepadInput::DPadRight),
+    ("stick_up", GamepadInput::StickUp),
+    ("stick_down", GamepadInput::StickDown),
+    ("stick_left", GamepadInput::StickLeft),
+    ("stick_right", GamepadInput::StickRight),
+    ("south", GamepadInput::South),
+    ("east", GamepadInput::East),
+    ("north", GamepadInput::North),
+    ("west", GamepadInput::West),
+    ("left_trigger", GamepadInput::LeftTrigger),
+    ("right_trigger", GamepadInput::RightTrigger),
+    ("left_trigger2", GamepadInput::LeftTrigger2),
+    ("right_trigger2", GamepadInput::RightTrigger2),
+    ("select", GamepadInput::Select),
+    ("start", GamepadInput::Start),
+];
+
+impl GamepadInput {
+    pub fn name(self) -> &'static str {
+        GAMEPAD_INPUT_NAMES.iter().find(|(_, input)| *input == self).map_or("", |(name, _)| name)
+    }
+
+    pub fn from_name(name: &str) -> Option<GamepadInput> {
+        GAMEPAD_INPUT_NAMES.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, input)| *input)
+    }
+}
+
+// 所有手柄合在一起的当前状态
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GamepadState {
+    pub buttons: u32, // 以 GamepadInput 为位序，摇杆方向除外
+    pub stick_x: f32, // 左摇杆，-1.0（左）~ 1.0（右）
+    pub stick_y: f32, // 左摇杆，-1.0（下）~ 1.0（上）
+}
+
+impl GamepadState {
+    pub fn set_button(&mut self, input: GamepadInput, down: bool) {
+        if down {
+            self.buttons |= 1 << (input as u32);
+        } else {
+            self.buttons &= !(1 << (input as u32));
+        }
+    }
+
+    pub fn is_down(&self, input: GamepadInput) -> bool {
+        match input {
+            GamepadInput::StickUp => self.stick_y > STICK_THRESHOLD,
+            GamepadInput::StickDown => self.stick_y < -STICK_THRESHOLD,
+            GamepadInput::StickLeft => self.stick_x < -STICK_THRESHOLD,
+            GamepadInput::StickRight => self.stick_x > STICK_THRESHOLD,
+            _ => self.buttons & (1 << (input as u32)) != 0,
+        }
+    }
+}
+
+pub struct Gamepad {
+    pub state: GamepadState,
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<gilrs::Gilrs>,
+}
+
+impl Default for Gamepad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gamepad {
+    pub fn new() -> Self {
+        Self {
+            state: GamepadState::default(),
+            // 初始化失败时当作没有手柄
+            #[cfg(feature = "gamepad")]
+            gilrs: gilrs::Gilrs::new().ok(),
+        }
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    pub fn poll(&mut self) {}
+
+    // 处理手柄事件，更新 state
+    #[cfg(feature = "gamepad")]
+    pub fn poll(&mut self) {
+        use gilrs::{ Axis, Button };
+
+        const BUTTONS: [(Button, GamepadInput); 14] = [
+            (Button::DPadUp, GamepadInput::DPadUp),
+            (Button::DPadDown, GamepadInput::DPadDown),
+            (Button::DPadLeft, GamepadInput::DPadLeft),
+            (Button::DPadRight, GamepadInput::DPadRight),
+            (Button::South, GamepadInput::South),
+            (Button::East, GamepadInput::East),
+            (Button::North, GamepadInput::North),
+            (Button::West, GamepadInput::West),
+            (Button::LeftTrigger, GamepadInput::LeftTrigger),
+            (Button::RightTrigger, GamepadInput::RightTrigger),
+            (Button::LeftTrigger2, GamepadInput::LeftTrigger2),
+            (Button::RightTrigger2, GamepadInput::RightTrigger2),
+            (Button::Select, GamepadInput::Select),
+            (Button::Start, GamepadInput::Start),
+        ];
+
+        let gilrs = match self.gilrs.as_mut() {
+            Some(gilrs) => gilrs,
+            None => {
+                return;
+            }
+        };
+        while gilrs.next_event().is_some() {}
+
+        let mut state = GamepadState::default();
+        for (_, gamepad) in gilrs.gamepads() {
+            for (button, input) in BUTTONS {
+                if gamepad.is_pressed(button) {
+                    state.set_button(input, true);
+                }
+            }
+            // 多个手柄时取偏离中心最多的
+            let x = gamepad.value(Axis::LeftStickX);
+            let y = gamepad.value(Axis::LeftStickY);
+            if x.abs() > state.stick_x.abs() {
+                state.stick_x = x;
+            }
+            if y.abs() > state.stick_y.abs() {
+                state.stick_y = y;
+            }
+        }
+        self.state = state;
+    }
+}
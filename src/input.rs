@@ -1,6 +1,9 @@
-use minifb::{Key, Window};
+use std::collections::BTreeMap;
+
+use minifb::{Key, KeyRepeat, Window};
 
 use crate::game::Game;
+use crate::gamepad::{ GamepadInput, GamepadState };
 use crate::utils::*;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -26,67 +29,219 @@ pub enum PalKey {
     End = 1 << 17,
 }
 
-struct KeyCode {
+// 配置文件中使用的按键名
+const PAL_KEY_NAMES: [(&str, PalKey); 18] = [
+    ("menu", PalKey::Menu),
+    ("search", PalKey::Search),
+    ("down", PalKey::Down),
+    ("left", PalKey::Left),
+    ("up", PalKey::Up),
+    ("right", PalKey::Right),
+    ("pgup", PalKey::PgUp),
+    ("pgdn", PalKey::PgDn),
+    ("repeat", PalKey::Repeat),
+    ("auto", PalKey::Auto),
+    ("defend", PalKey::Defend),
+    ("use_item", PalKey::UseItem),
+    ("throw_item", PalKey::ThrowItem),
+    ("flee", PalKey::Flee),
+    ("status", PalKey::Status),
+    ("force", PalKey::Force),
+    ("home", PalKey::Home),
+    ("end", PalKey::End),
+];
+
+impl PalKey {
+    pub fn name(self) -> &'static str {
+        PAL_KEY_NAMES.iter().find(|(_, key)| *key == self).map_or("none", |(name, _)| name)
+    }
+
+    pub fn from_name(name: &str) -> Option<PalKey> {
+        PAL_KEY_NAMES.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, key)| *key)
+    }
+}
+
+// 可以绑定的键盘按键，名字与 minifb::Key 相同
+const KEYBOARD_KEYS: [Key; 106] = [
+    Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+    Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J, Key::K, Key::L, Key::M,
+    Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10, Key::F11,
+    Key::F12, Key::F13, Key::F14, Key::F15,
+    Key::Down, Key::Left, Key::Right, Key::Up, Key::Apostrophe, Key::Backquote, Key::Backslash, Key::Comma,
+    Key::Equal, Key::LeftBracket, Key::Minus, Key::Period, Key::RightBracket, Key::Semicolon, Key::Slash,
+    Key::Backspace, Key::Delete, Key::End, Key::Enter, Key::Escape, Key::Home, Key::Insert, Key::Menu,
+    Key::PageDown, Key::PageUp, Key::Pause, Key::Space, Key::Tab, Key::NumLock, Key::CapsLock, Key::ScrollLock,
+    Key::LeftShift, Key::RightShift, Key::LeftCtrl, Key::RightCtrl,
+    Key::NumPad0, Key::NumPad1, Key::NumPad2, Key::NumPad3, Key::NumPad4, Key::NumPad5, Key::NumPad6,
+    Key::NumPad7, Key::NumPad8, Key::NumPad9, Key::NumPadDot, Key::NumPadSlash, Key::NumPadAsterisk,
+    Key::NumPadMinus, Key::NumPadPlus, Key::NumPadEnter, Key::LeftAlt, Key::RightAlt, Key::LeftSuper,
+    Key::RightSuper,
+];
+
+pub fn key_name(code: Key) -> String {
+    format!("{:?}", code)
+}
+
+pub fn key_from_name(name: &str) -> Option<Key> {
+    KEYBOARD_KEYS.iter().find(|&&code| key_name(code).eq_ignore_ascii_case(name)).copied()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyBinding {
     pub code: Key,
     pub key: PalKey,
 }
-const KEY_COUNT: usize = 33;
-
-const KEY_MAP: [KeyCode; KEY_COUNT] = [
-    KeyCode { code: Key::Up, key: PalKey::Up },
-    KeyCode { code: Key::NumPad8, key: PalKey::Up },
-    KeyCode { code: Key::Down, key: PalKey::Down },
-    KeyCode { code: Key::NumPad2, key: PalKey::Down },
-    KeyCode { code: Key::Left, key: PalKey::Left },
-    KeyCode { code: Key::NumPad4, key: PalKey::Left },
-    KeyCode { code: Key::Right, key: PalKey::Right },
-    KeyCode { code: Key::NumPad6, key: PalKey::Right },
-    KeyCode { code: Key::Escape, key: PalKey::Menu },
-    KeyCode { code: Key::Insert, key: PalKey::Menu },
-    KeyCode { code: Key::LeftAlt, key: PalKey::Menu },
-    KeyCode { code: Key::RightAlt, key: PalKey::Menu },
-    KeyCode { code: Key::NumPad0, key: PalKey::Menu },
-    KeyCode { code: Key::Enter, key: PalKey::Search },
-    KeyCode { code: Key::Space, key: PalKey::Search },
-    KeyCode { code: Key::NumPadEnter, key: PalKey::Search },
-    KeyCode { code: Key::LeftCtrl, key: PalKey::Search },
-    KeyCode { code: Key::PageUp, key: PalKey::PgUp },
-    KeyCode { code: Key::NumPad9, key: PalKey::PgUp },
-    KeyCode { code: Key::PageDown, key: PalKey::PgDn },
-    KeyCode { code: Key::NumPad3, key: PalKey::PgDn },
-    KeyCode { code: Key::Home, key: PalKey::Home },
-    KeyCode { code: Key::NumPad7, key: PalKey::Home },
-    KeyCode { code: Key::End, key: PalKey::End },
-    KeyCode { code: Key::NumPad1, key: PalKey::End },
-    KeyCode { code: Key::R, key: PalKey::Repeat },
-    KeyCode { code: Key::A, key: PalKey::Auto },
-    KeyCode { code: Key::D, key: PalKey::Defend },
-    KeyCode { code: Key::E, key: PalKey::UseItem },
-    KeyCode { code: Key::W, key: PalKey::ThrowItem },
-    KeyCode { code: Key::Q, key: PalKey::Flee },
-    KeyCode { code: Key::F, key: PalKey::Force },
-    KeyCode { code: Key::S, key: PalKey::Status },
+
+const DEFAULT_KEY_MAP: [KeyBinding; 33] = [
+    KeyBinding { code: Key::Up, key: PalKey::Up },
+    KeyBinding { code: Key::NumPad8, key: PalKey::Up },
+    KeyBinding { code: Key::Down, key: PalKey::Down },
+    KeyBinding { code: Key::NumPad2, key: PalKey::Down },
+    KeyBinding { code: Key::Left, key: PalKey::Left },
+    KeyBinding { code: Key::NumPad4, key: PalKey::Left },
+    KeyBinding { code: Key::Right, key: PalKey::Right },
+    KeyBinding { code: Key::NumPad6, key: PalKey::Right },
+    KeyBinding { code: Key::Escape, key: PalKey::Menu },
+    KeyBinding { code: Key::Insert, key: PalKey::Menu },
+    KeyBinding { code: Key::LeftAlt, key: PalKey::Menu },
+    KeyBinding { code: Key::RightAlt, key: PalKey::Menu },
+    KeyBinding { code: Key::NumPad0, key: PalKey::Menu },
+    KeyBinding { code: Key::Enter, key: PalKey::Search },
+    KeyBinding { code: Key::Space, key: PalKey::Search },
+    KeyBinding { code: Key::NumPadEnter, key: PalKey::Search },
+    KeyBinding { code: Key::LeftCtrl, key: PalKey::Search },
+    KeyBinding { code: Key::PageUp, key: PalKey::PgUp },
+    KeyBinding { code: Key::NumPad9, key: PalKey::PgUp },
+    KeyBinding { code: Key::PageDown, key: PalKey::PgDn },
+    KeyBinding { code: Key::NumPad3, key: PalKey::PgDn },
+    KeyBinding { code: Key::Home, key: PalKey::Home },
+    KeyBinding { code: Key::NumPad7, key: PalKey::Home },
+    KeyBinding { code: Key::End, key: PalKey::End },
+    KeyBinding { code: Key::NumPad1, key: PalKey::End },
+    KeyBinding { code: Key::R, key: PalKey::Repeat },
+    KeyBinding { code: Key::A, key: PalKey::Auto },
+    KeyBinding { code: Key::D, key: PalKey::Defend },
+    KeyBinding { code: Key::E, key: PalKey::UseItem },
+    KeyBinding { code: Key::W, key: PalKey::ThrowItem },
+    KeyBinding { code: Key::Q, key: PalKey::Flee },
+    KeyBinding { code: Key::F, key: PalKey::Force },
+    KeyBinding { code: Key::S, key: PalKey::Status },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GamepadBinding {
+    pub input: GamepadInput,
+    pub key: PalKey,
+}
+
+const DEFAULT_GAMEPAD_MAP: [GamepadBinding; 16] = [
+    GamepadBinding { input: GamepadInput::DPadUp, key: PalKey::Up },
+    GamepadBinding { input: GamepadInput::StickUp, key: PalKey::Up },
+    GamepadBinding { input: GamepadInput::DPadDown, key: PalKey::Down },
+    GamepadBinding { input: GamepadInput::StickDown, key: PalKey::Down },
+    GamepadBinding { input: GamepadInput::DPadLeft, key: PalKey::Left },
+    GamepadBinding { input: GamepadInput::StickLeft, key: PalKey::Left },
+    GamepadBinding { input: GamepadInput::DPadRight, key: PalKey::Right },
+    GamepadBinding { input: GamepadInput::StickRight, key: PalKey::Right },
+    GamepadBinding { input: GamepadInput::South, key: PalKey::Search },
+    GamepadBinding { input: GamepadInput::East, key: PalKey::Menu },
+    GamepadBinding { input: GamepadInput::Start, key: PalKey::Menu },
+    GamepadBinding { input: GamepadInput::West, key: PalKey::Repeat },
+    GamepadBinding { input: GamepadInput::North, key: PalKey::Auto },
+    GamepadBinding { input: GamepadInput::Select, key: PalKey::Status },
+    GamepadBinding { input: GamepadInput::LeftTrigger, key: PalKey::PgUp },
+    GamepadBinding { input: GamepadInput::RightTrigger, key: PalKey::PgDn },
 ];
 
+// 默认按键设置，按键名 -> 键盘按键名
+pub fn default_key_names() -> BTreeMap<String, Vec<String>> {
+    let mut names = BTreeMap::<String, Vec<String>>::new();
+    for binding in DEFAULT_KEY_MAP.iter() {
+        names.entry(binding.key.name().to_string()).or_default().push(key_name(binding.code));
+    }
+
+    names
+}
+
+// 默认手柄设置，按键名 -> 手柄按钮名
+pub fn default_gamepad_names() -> BTreeMap<String, Vec<String>> {
+    let mut names = BTreeMap::<String, Vec<String>>::new();
+    for binding in DEFAULT_GAMEPAD_MAP.iter() {
+        names.entry(binding.key.name().to_string()).or_default().push(binding.input.name().to_string());
+    }
+
+    names
+}
+
+pub fn gamepad_bindings(names: &BTreeMap<String, Vec<String>>) -> Result<Vec<GamepadBinding>> {
+    let mut bindings = Vec::new();
+    for (action, inputs) in names {
+        let key = PalKey::from_name(action).ok_or(format!("unknown action in gamepad bindings: {}", action))?;
+        for input in inputs {
+            let input = GamepadInput::from_name(input).ok_or(format!("unknown gamepad input: {}", input))?;
+            bindings.push(GamepadBinding { input, key });
+        }
+    }
+
+    Ok(bindings)
+}
+
+// 由配置中的按键名得到绑定列表
+pub fn key_bindings(names: &BTreeMap<String, Vec<String>>) -> Result<Vec<KeyBinding>> {
+    let mut bindings = Vec::new();
+    for (action, codes) in names {
+        let key = PalKey::from_name(action).ok_or(format!("unknown action in key bindings: {}", action))?;
+        for code in codes {
+            let code = key_from_name(code).ok_or(format!("unknown key in key bindings: {}", code))?;
+            bindings.push(KeyBinding { code, key });
+        }
+    }
+
+    Ok(bindings)
+}
+
 pub struct InputState {
     pub dir: Dir,
     pub key_press: u32,
     pub key_order: [u32; 4],
     pub key_max_count: u32,
-    pub key_last_time: [u32; KEY_COUNT],
+    pub key_last_time: Vec<u32>, // 每个绑定一项，先键盘后手柄
+    bindings: Vec<KeyBinding>,
+    gamepad_bindings: Vec<GamepadBinding>,
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl InputState {
     pub fn new() -> Self {
+        Self::with_bindings(DEFAULT_KEY_MAP.to_vec(), DEFAULT_GAMEPAD_MAP.to_vec())
+    }
+
+    pub fn with_bindings(bindings: Vec<KeyBinding>, gamepad_bindings: Vec<GamepadBinding>) -> Self {
         InputState {
             dir: Dir::Unknown,
             key_press: 0,
             key_order: [0; 4],
             key_max_count: 0,
-            key_last_time: [0; KEY_COUNT],
+            key_last_time: vec![0; bindings.len() + gamepad_bindings.len()],
+            bindings,
+            gamepad_bindings,
         }
     }
 
+    pub fn bindings(&self) -> &[KeyBinding] {
+        &self.bindings
+    }
+
+    pub fn gamepad_bindings(&self) -> &[GamepadBinding] {
+        &self.gamepad_bindings
+    }
+
     #[inline]
     pub fn is_pressed(&self, key: PalKey) -> bool {
         (self.key_press & (key as u32)) != 0
@@ -126,26 +281,32 @@ impl InputState {
             return Dir::Unknown;
         }
 
-        return Dir::from_u8(idx as u8);
+        Dir::from_u8(idx as u8)
+    }
+
+    // 没有窗口时只读取手柄
+    fn update_state(&mut self, window: Option<&Window>, gamepad: &GamepadState, ticks: u32) {
+        let keys: Vec<(PalKey, bool)> = self.bindings
+            .iter()
+            .map(|binding| (binding.key, window.is_some_and(|window| window.is_key_down(binding.code))))
+            .chain(self.gamepad_bindings.iter().map(|binding| (binding.key, gamepad.is_down(binding.input))))
+            .collect();
+        self.update_keys(&keys, ticks);
     }
 
-    fn update_state(&mut self, window: &Window, ticks: u32) {
+    // keys 与绑定一一对应，给出每个绑定的键是否按下。键盘和手柄共用按键重复和方向优先的逻辑
+    pub fn update_keys(&mut self, keys: &[(PalKey, bool)], ticks: u32) {
         let cur_time = ticks;
 
-        for i in 0..KEY_COUNT {
-            let key_code = &KEY_MAP[i];
-            if window.is_key_down(key_code.code) {
+        for (i, &(key, down)) in keys.iter().enumerate().take(self.key_last_time.len()) {
+            if down {
                 if cur_time > self.key_last_time[i] {
                     let is_repeat = self.key_last_time[i] != 0;
-                    let delay = if self.key_last_time[i] == 0 {
-                        200 as u32
-                    } else {
-                        75 as u32
-                    };
+                    let delay = if self.key_last_time[i] == 0 { 200 } else { 75 };
 
                     self.key_last_time[i] = cur_time + delay;
                     if !is_repeat {
-                        let dir = InputState::key_to_dir(self, key_code.key);
+                        let dir = InputState::key_to_dir(self, key);
 
                         if dir != Dir::Unknown {
                             self.key_max_count += 1;
@@ -154,24 +315,22 @@ impl InputState {
                         }
                     }
 
-                    self.key_press |= key_code.key as u32;
+                    self.key_press |= key as u32;
                 }
-            } else {
-                if self.key_last_time[i] != 0 {
-                    let dir = InputState::key_to_dir(self, key_code.key);
-                    if dir != Dir::Unknown {
-                        self.key_order[dir as usize] = 0;
-                        let cur_dir = self.get_cur_dir();
-                        self.key_max_count = if cur_dir == Dir::Unknown {
-                            0
-                        } else {
-                            self.key_order[cur_dir.clone() as usize]
-                        };
-                        self.dir = cur_dir;
-                    }
-
-                    self.key_last_time[i] = 0;
+            } else if self.key_last_time[i] != 0 {
+                let dir = InputState::key_to_dir(self, key);
+                if dir != Dir::Unknown {
+                    self.key_order[dir as usize] = 0;
+                    let cur_dir = self.get_cur_dir();
+                    self.key_max_count = if cur_dir == Dir::Unknown {
+                        0
+                    } else {
+                        self.key_order[cur_dir.clone() as usize]
+                    };
+                    self.dir = cur_dir;
                 }
+
+                self.key_last_time[i] = 0;
             }
         }
     }
@@ -180,13 +339,41 @@ impl InputState {
 impl Game {
     pub fn update_keyboard_state(&mut self) {}
 
-    pub fn process_event(&mut self) {
-        if !self.window.is_open() {
-            std::process::exit(0);
+    // 关闭窗口时结束程序，正在录像时先保存录像。
+    // process::exit 不会运行 Drop，所以先停止混音线程，让 WAV 文件写完文件头
+    fn exit(&mut self) -> ! {
+        if let Some(path) = self.config.record.clone() {
+            let _ = self.finish_recording(path);
         }
+        if let Err(e) = self.audio.stop() {
+            eprintln!("cannot finish audio output: {}", e);
+        }
+        std::process::exit(0);
+    }
 
+    // 回放时返回 ReplayFinished 表示录像已经结束
+    pub fn process_event(&mut self) -> Result<()> {
+        if self.replay_input()? {
+            return Ok(());
+        }
+
+        if let Some(window) = self.window.as_ref() {
+            if !window.is_open() {
+                self.exit();
+            }
+
+            // 快进开关
+            if window.is_key_pressed(Key::Tab, KeyRepeat::No) {
+                self.scheduler.toggle_fast_forward();
+            }
+        }
+
+        self.gamepad.poll();
+        let ticks = self.ticks();
         self.input.key_press = 0;
-        self.input.update_state(&self.window, self.ticks())
-        //println!("key_press: {}", self.input_state.key_press);
+        self.input.update_state(self.window.as_ref(), &self.gamepad.state, ticks);
+        self.record_input();
+
+        Ok(())
     }
 }
@@ -0,0 +1,203 @@
+use crate::data::*;
+use crate::game::Game;
+use crate::utils::*;
+
+// 每种物品最多持有的数量
+pub const MAX_ITEM_AMOUNT: u16 = 99;
+
+/*
+typedef enum tagITEMFLAG
+{
+   kItemFlagUsable          = (1 << 0),
+   kItemFlagEquipable       = (1 << 1),
+   kItemFlagThrowable       = (1 << 2),
+   kItemFlagConsuming       = (1 << 3),
+   kItemFlagApplyToAll      = (1 << 4),
+   kItemFlagSellable        = (1 << 5),
+   kItemFlagEquipableByPlayerRole_First  = (1 << 6)
+} ITEMFLAG, *LPITEMFLAG;
+*/
+pub const ITEM_FLAG_USABLE: u16 = 1 << 0;
+pub const ITEM_FLAG_EQUIPABLE: u16 = 1 << 1;
+pub const ITEM_FLAG_THROWABLE: u16 = 1 << 2;
+pub const ITEM_FLAG_CONSUMING: u16 = 1 << 3;
+pub const ITEM_FLAG_APPLY_TO_ALL: u16 = 1 << 4;
+pub const ITEM_FLAG_SELLABLE: u16 = 1 << 5;
+pub const ITEM_FLAG_EQUIPABLE_BY_PLAYER_ROLE_FIRST: u16 = 1 << 6;
+
+impl GameState {
+    // num 为正时添加物品，为负时移除物品。库存已满或物品不足时返回 false
+    pub fn add_item_to_inventory(&mut self, object_id: u16, num: i16) -> bool {
+        if object_id == 0 {
+            return false;
+        }
+        let num = if num == 0 { 1 } else { num as i32 };
+
+        let index = self.inventory
+            .iter()
+            .position(|item| item.item == object_id || item.item == 0)
+            .unwrap_or(self.inventory.len());
+        let found = self.inventory.get(index).is_some_and(|item| item.item == object_id);
+
+        if num > 0 {
+            let item = match self.inventory.get_mut(index) {
+                Some(item) => item,
+                None => {
+                    return false;
+                }
+            };
+            item.item = object_id;
+            item.amount = std::cmp::min((item.amount as i32) + num, MAX_ITEM_AMOUNT as i32) as u16;
+            true
+        } else {
+            if !found {
+                return false;
+            }
+            let item = &mut self.inventory[index];
+            if (item.amount as i32) < -num {
+                // 物品已用完
+                item.amount = 0;
+                return false;
+            }
+            item.amount -= (-num) as u16;
+            true
+        }
+    }
+
+    // 库存中物品的数量，不含已装备的
+    pub fn item_amount(&self, object_id: u16) -> u16 {
+        self.inventory
+            .iter()
+            .find(|item| item.item == object_id)
+            .map_or(0, |item| item.amount)
+    }
+
+    // 队伍中是否有人装备了此物品
+    pub fn is_item_equipped(&self, object_id: u16) -> bool {
+        self.party_roles()
+            .into_iter()
+            .any(|role| self.player_roles.equipment.iter().any(|part| part[role] == object_id))
+    }
+
+    // 从队伍成员身上卸下最多 num 件此物品，装备不回到库存。返回未能卸下的数量
+    pub fn remove_equipped_item(&mut self, object_id: u16, num: u16) -> u16 {
+        let mut num = num;
+        for role in self.party_roles() {
+            for part in 0..MAX_PLAYER_EQUIPMENTS {
+                if num > 0 && self.player_roles.equipment[part][role] == object_id {
+                    self.remove_equipment_effect(role, part);
+                    self.player_roles.equipment[part][role] = 0;
+                    num -= 1;
+                }
+            }
+        }
+        num
+    }
+
+    // 库存中带有 flag 标志的物品
+    pub fn items_with_flag(&self, flag: u16) -> Vec<Inventory> {
+        self.inventory
+            .iter()
+            .filter(|item| item.item != 0 && item.amount > 0)
+            .filter(|item| self.object_item(item.item).is_some_and(|object| object.flags & flag != 0))
+            .copied()
+            .collect()
+    }
+
+    // 移除数量为 0 的物品，并把剩余物品移到库存前部
+    pub fn compress_inventory(&mut self) {
+        let len = self.inventory.len();
+        self.inventory.retain(|item| item.item != 0 && item.amount > 0);
+        self.inventory.resize(len, Inventory::default());
+    }
+}
+
+impl ObjectItem {
+    pub fn is_equipable_by(&self, player_role: usize) -> bool {
+        let flag = (ITEM_FLAG_EQUIPABLE_BY_PLAYER_ROLE_FIRST as u32) << player_role;
+        (self.flags as u32) & flag != 0
+    }
+}
+
+impl Game {
+    // 对角色 player_role 使用物品，带有“全体”标志的物品忽略 player_role。物品不能使用时返回 false
+    pub fn use_item(&mut self, object_id: u16, player_role: u16) -> Result<bool> {
+        let item = match self.state.object_item(object_id) {
+            Some(item) if item.flags & ITEM_FLAG_USABLE != 0 && self.state.item_amount(object_id) > 0 => item,
+            _ => {
+                return Ok(false);
+            }
+        };
+
+        let target = if item.flags & ITEM_FLAG_APPLY_TO_ALL != 0 { 0 } else { player_role };
+        let script_on_use = self.run_trigger_script(item.script_on_use, target)?;
+        self.update_item(object_id, |item| item.script_on_use = script_on_use);
+
+        // 消耗品在脚本成功时减少
+        if item.flags & ITEM_FLAG_CONSUMING != 0 && self.script_success {
+            self.state.add_item_to_inventory(object_id, -1);
+        }
+
+        Ok(self.script_success)
+    }
+
+    // 让角色装备物品，物品不能被该角色装备时返回 false
+    pub fn equip_item(&mut self, object_id: u16, player_role: u16) -> Result<bool> {
+        let item = match self.state.object_item(object_id) {
+            Some(item) if item.flags & ITEM_FLAG_EQUIPABLE != 0 && item.is_equipable_by(player_role as usize) =>
+                item,
+            _ => {
+                return Ok(false);
+            }
+        };
+
+        let script_on_equip = self.run_trigger_script(item.script_on_equip, player_role)?;
+        self.update_item(object_id, |item| item.script_on_equip = script_on_equip);
+        self.state.party_state.cur_equip_part = None;
+        self.state.compress_inventory();
+
+        Ok(true)
+    }
+
+    // 向第 enemy_index 个敌人投掷物品，物品不能投掷时返回 false
+    pub fn throw_item(&mut self, object_id: u16, enemy_index: u16) -> Result<bool> {
+        let item = match self.state.object_item(object_id) {
+            Some(item) if item.flags & ITEM_FLAG_THROWABLE != 0 && self.state.item_amount(object_id) > 0 => item,
+            _ => {
+                return Ok(false);
+            }
+        };
+
+        let script_on_throw = self.run_trigger_script(item.script_on_throw, enemy_index)?;
+        self.update_item(object_id, |item| item.script_on_throw = script_on_throw);
+        self.state.add_item_to_inventory(object_id, -1);
+
+        Ok(true)
+    }
+
+    // 重新执行所有装备的脚本，计算装备效果。读档后调用
+    pub fn update_equipments(&mut self) -> Result<()> {
+        self.state.party_state.equipment_effect = vec![PlayerRoles::default(); MAX_PLAYER_EQUIPMENTS + 1];
+
+        for role in 0..MAX_PLAYER_ROLES {
+            for part in 0..MAX_PLAYER_EQUIPMENTS {
+                let object_id = self.state.player_roles.equipment[part][role];
+                if let Some(item) = self.state.object_item(object_id) {
+                    let script_on_equip = self.run_trigger_script(item.script_on_equip, role as u16)?;
+                    self.update_item(object_id, |item| item.script_on_equip = script_on_equip);
+                }
+            }
+        }
+        self.state.party_state.cur_equip_part = None;
+
+        Ok(())
+    }
+
+    pub fn update_item<F: FnOnce(&mut ObjectItem)>(&mut self, object_id: u16, f: F) {
+        if let Some(object) = self.state.object_of_kind_mut(object_id, ObjectKind::Item) {
+            let mut item = object.item();
+            f(&mut item);
+            object.set_item(&item);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use crate::game::Game;
+use crate::input::PalKey;
+use crate::ui::*;
+use crate::utils::*;
+
+// 物品列表每页的行列数
+const GRID_COLUMNS: usize = 3;
+const GRID_ROWS: usize = 7;
+const GRID_PER_PAGE: usize = GRID_COLUMNS * GRID_ROWS;
+
+// 三列的物品/法术列表中的一项
+pub struct GridItem {
+    pub value: u16,
+    pub num_word: u32,
+    pub number: u32, // 名称右侧显示的数字（数量或 MP）
+    pub enabled: bool,
+}
+
+impl Game {
+    // 在三列的列表中选择一项，PgUp/PgDn 翻页。on_draw 在每帧绘制与当前选项相关的内容。
+    // 取消时返回 MENUITEM_VALUE_CANCELLED
+    pub fn grid_select_menu<F>(&mut self, items: &[GridItem], default_index: usize, mut on_draw: F) -> Result<u16>
+        where F: FnMut(&mut Game, u16) -> Result<()>
+    {
+        if items.is_empty() {
+            return Ok(MENUITEM_VALUE_CANCELLED);
+        }
+
+        let background = self.canvas.get_pixels().to_vec();
+        let len = items.len();
+        let mut selected_index = std::cmp::min(default_index, len - 1);
+        loop {
+            self.canvas.set_pixels(|pixels: &mut [u8]| {
+                pixels.copy_from_slice(&background);
+            });
+            self.draw_box(Pos { x: 2, y: 0 }, 6, 17);
+
+            let page = selected_index / GRID_PER_PAGE;
+            let selected_color = self.menu_color_selected();
+            for (i, item) in items.iter().enumerate().skip(page * GRID_PER_PAGE).take(GRID_PER_PAGE) {
+                let k = (i % GRID_PER_PAGE) % GRID_COLUMNS;
+                let j = (i % GRID_PER_PAGE) / GRID_COLUMNS;
+
+                let color = match (item.enabled, i == selected_index) {
+                    (true, true) => selected_color,
+                    (true, false) => MENUITEM_COLOR,
+                    (false, true) => MENUITEM_COLOR_SELECTED_INACTIVE,
+                    (false, false) => MENUITEM_COLOR_INACTIVE,
+                };
+
+                let x = 15 + (k as isize) * 100;
+                let y = 12 + (j as isize) * 18;
+                self.draw_word_at(item.num_word, Pos { x, y }, color);
+                self.draw_number(item.number, 3, Pos { x: x + 66, y: y + 2 }, NumColor::Cyan, NumAlign::Right);
+            }
+
+            on_draw(self, items[selected_index].value)?;
+
+            self.blit_to_screen()?;
+            self.process_event()?;
+
+            if self.input.is_pressed(PalKey::Right) {
+                selected_index = (selected_index + 1) % len;
+            } else if self.input.is_pressed(PalKey::Left) {
+                selected_index = (selected_index + len - 1) % len;
+            } else if self.input.is_pressed(PalKey::Down) {
+                selected_index = std::cmp::min(selected_index + GRID_COLUMNS, len - 1);
+            } else if self.input.is_pressed(PalKey::Up) {
+                selected_index = selected_index.saturating_sub(GRID_COLUMNS);
+            } else if self.input.is_pressed(PalKey::PgDn) {
+                selected_index = std::cmp::min(selected_index + GRID_PER_PAGE, len - 1);
+            } else if self.input.is_pressed(PalKey::PgUp) {
+                selected_index = selected_index.saturating_sub(GRID_PER_PAGE);
+            }
+
+            if self.input.is_pressed(PalKey::Search) && items[selected_index].enabled {
+                return Ok(items[selected_index].value);
+            }
+            if self.input.is_pressed(PalKey::Menu) {
+                self.canvas.set_pixels(|pixels: &mut [u8]| {
+                    pixels.copy_from_slice(&background);
+                });
+                return Ok(MENUITEM_VALUE_CANCELLED);
+            }
+
+            self.delay(30);
+        }
+    }
+
+    // 从库存中选择带有 flag 标志的物品，其余物品显示为灰色。取消时返回 0
+    pub fn item_select_menu<F>(&mut self, flag: u16, on_draw: F) -> Result<u16>
+        where F: FnMut(&mut Game, u16) -> Result<()>
+    {
+        self.state.compress_inventory();
+        let items: Vec<GridItem> = self.state.inventory
+            .iter()
+            .filter(|item| item.item != 0)
+            .map(|item| GridItem {
+                value: item.item,
+                num_word: item.item as u32,
+                number: item.amount.saturating_sub(item.amount_in_use) as u32,
+                enabled: self.state.object_item(item.item).is_some_and(|o| o.flags & flag != 0),
+            })
+            .collect();
+
+        let item = self.grid_select_menu(&items, 0, on_draw)?;
+        Ok(if item == MENUITEM_VALUE_CANCELLED { 0 } else { item })
+    }
+}
@@ -1,12 +1,37 @@
+pub mod audio;
+pub mod audio_sink;
+pub mod battle;
 pub mod canvas;
+pub mod config;
 pub mod data;
+pub mod encoding;
+pub mod encounter;
+pub mod font;
 pub mod game;
+pub mod gamepad;
+pub mod game_menu;
 pub mod input;
+pub mod inventory;
+pub mod item_menu;
 pub mod scene;
+pub mod scheduler;
+pub mod shop;
+pub mod midi;
+pub mod mixer;
 pub mod mkf;
+pub mod opl;
+pub mod palette_effects;
+pub mod party;
 pub mod play;
+pub mod random;
+pub mod replay;
+pub mod rix;
 pub mod rng;
+pub mod save;
 pub mod sprite;
+pub mod translation;
 pub mod ui;
 pub mod utils;
+pub mod video;
+pub mod voc;
 pub mod script;
@@ -1,6 +1,33 @@
+use pal::config::Config;
 use pal::game::Game;
+use pal::replay::Recording;
 
 fn main() {
-    let mut pal = Game::new().unwrap();
-    pal.run().unwrap();
+    let config = Config::from_args(std::env::args().skip(1)).unwrap();
+    let replay = config.replay.clone();
+    let check_text = config.check_text;
+    let export_text = config.export_text.clone();
+    let import_text = config.import_text.clone();
+    let mut pal = Game::new(config).unwrap();
+
+    if check_text {
+        print!("{}", pal.ui.text_report());
+        return;
+    }
+    if let Some(path) = export_text {
+        pal.export_text(path).unwrap();
+        return;
+    }
+    if let Some(path) = import_text {
+        pal.import_text(path).unwrap();
+        return;
+    }
+
+    match replay {
+        Some(path) => {
+            let hash = pal.run_replay(Recording::load(path).unwrap()).unwrap();
+            println!("replay ok, state hash {:016x}", hash);
+        }
+        None => pal.run().unwrap(),
+    }
 }
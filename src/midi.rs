@@ -0,0 +1,651 @@
+use std::f32::consts::PI;
+
+use crate::utils::Result;
+
+// 标准 MIDI 文件（SMF）的解析和一个简单的 General MIDI 波表合成器。
+// MIDI.MKF 中每块是一个完整的 .mid 文件
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MidiMessage {
+    NoteOff { channel: u8, key: u8 },
+    NoteOn { channel: u8, key: u8, velocity: u8 },
+    Controller { channel: u8, controller: u8, value: u8 },
+    ProgramChange { channel: u8, program: u8 },
+    PitchBend { channel: u8, value: u16 }, // 0x2000 为中央
+    Tempo(u32), // 每个四分音符的微秒数
+    EndOfTrack,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MidiEvent {
+    pub tick: u32, // 从音轨开头算起的绝对时间
+    pub message: MidiMessage,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MidiFile {
+    pub format: u16,
+    pub division: u16, // 最高位为 0 时是每四分音符的 tick 数，否则为 SMPTE 时间
+    pub tracks: Vec<Vec<MidiEvent>>,
+}
+
+// 未指定速度时为 120 BPM
+const DEFAULT_TEMPO: u32 = 500000;
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn u8(&mut self) -> Result<u8> {
+        let value = *self.buf.get(self.pos).ok_or("unexpected end of MIDI data")?;
+        self.pos += 1;
+        Ok(value)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.buf.len()).ok_or("unexpected end of MIDI data")?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    // 变长整数，每字节 7 位，最多 4 字节
+    fn var_len(&mut self) -> Result<u32> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let b = self.u8()?;
+            value = (value << 7) | ((b & 0x7f) as u32);
+            if b & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("invalid variable-length quantity".into())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+}
+
+impl MidiFile {
+    pub fn parse(buf: &[u8]) -> Result<Self> {
+        let mut reader = Reader { buf, pos: 0 };
+        if reader.bytes(4)? != b"MThd" {
+            return Err("not a MIDI file".into());
+        }
+        let header_len = reader.u32()? as usize;
+        let header = reader.bytes(header_len)?;
+        if header.len() < 6 {
+            return Err("invalid MIDI header".into());
+        }
+        let format = u16::from_be_bytes([header[0], header[1]]);
+        let num_tracks = u16::from_be_bytes([header[2], header[3]]);
+        let division = u16::from_be_bytes([header[4], header[5]]);
+        if format > 1 {
+            return Err(format!("unsupported MIDI format {}", format).into());
+        }
+
+        let mut tracks = Vec::new();
+        while tracks.len() < (num_tracks as usize) && !reader.is_empty() {
+            let id = reader.bytes(4)?;
+            let len = reader.u32()? as usize;
+            let data = reader.bytes(len)?;
+            // 跳过未知的块
+            if id == b"MTrk" {
+                tracks.push(parse_track(data)?);
+            }
+        }
+
+        Ok(Self { format, division, tracks })
+    }
+
+    // 所有音轨按时间合并，时间换算为秒
+    pub fn timeline(&self) -> Vec<(f64, MidiMessage)> {
+        let mut events: Vec<(u32, usize, MidiMessage)> = self.tracks
+            .iter()
+            .enumerate()
+            .flat_map(|(track, events)| events.iter().map(move |event| (event.tick, track, event.message)))
+            .collect();
+        // 同一时刻按音轨顺序，保证速度变化先于音符
+        events.sort_by_key(|&(tick, track, _)| (tick, track));
+
+        let smpte = self.division & 0x8000 != 0;
+        let ticks_per_second = if smpte {
+            // 高字节为负的帧率，-128 取反时超出 i8 的范围
+            let fps = (-((self.division >> 8) as u8 as i8 as i16) as f64).max(1.0);
+            fps * (((self.division & 0xff) as f64).max(1.0))
+        } else {
+            0.0
+        };
+        let ticks_per_quarter = (std::cmp::max(self.division, 1)) as f64;
+
+        let mut tempo = DEFAULT_TEMPO;
+        let mut last_tick = 0;
+        let mut seconds = 0.0;
+        let mut timeline = Vec::with_capacity(events.len());
+        for (tick, _, message) in events {
+            let delta = (tick - last_tick) as f64;
+            seconds += if smpte {
+                delta / ticks_per_second
+            } else {
+                delta * (tempo as f64) / 1_000_000.0 / ticks_per_quarter
+            };
+            last_tick = tick;
+
+            if let MidiMessage::Tempo(value) = message {
+                tempo = std::cmp::max(value, 1);
+            }
+            timeline.push((seconds, message));
+        }
+
+        timeline
+    }
+}
+
+fn parse_track(data: &[u8]) -> Result<Vec<MidiEvent>> {
+    let mut reader = Reader { buf: data, pos: 0 };
+    let mut events = Vec::new();
+    let mut tick = 0u32;
+    let mut running_status = 0u8;
+
+    while !reader.is_empty() {
+        tick = tick.saturating_add(reader.var_len()?);
+
+        let mut status = reader.u8()?;
+        let first_data = if status < 0x80 {
+            // 沿用上一个状态字节
+            if running_status == 0 {
+                return Err("MIDI running status without a previous status".into());
+            }
+            let data = status;
+            status = running_status;
+            Some(data)
+        } else {
+            None
+        };
+
+        let message = match status {
+            0x80..=0xef => {
+                running_status = status;
+                let channel = status & 0x0f;
+                let a = match first_data {
+                    Some(data) => data,
+                    None => reader.u8()?,
+                };
+                match status & 0xf0 {
+                    // 只有一个数据字节
+                    0xc0 => Some(MidiMessage::ProgramChange { channel, program: a }),
+                    0xd0 => None,
+                    kind => {
+                        let b = reader.u8()?;
+                        match kind {
+                            0x80 => Some(MidiMessage::NoteOff { channel, key: a }),
+                            // 力度为 0 的 NoteOn 相当于 NoteOff
+                            0x90 if b == 0 => Some(MidiMessage::NoteOff { channel, key: a }),
+                            0x90 => Some(MidiMessage::NoteOn { channel, key: a, velocity: b }),
+                            0xb0 => Some(MidiMessage::Controller { channel, controller: a, value: b }),
+                            0xe0 => Some(MidiMessage::PitchBend { channel, value: ((b as u16) << 7) | (a as u16) }),
+                            _ => None,
+                        }
+                    }
+                }
+            }
+            0xf0 | 0xf7 => {
+                // SysEx，跳过
+                let len = reader.var_len()? as usize;
+                reader.bytes(len)?;
+                None
+            }
+            0xff => {
+                let kind = reader.u8()?;
+                let len = reader.var_len()? as usize;
+                let data = reader.bytes(len)?;
+                match kind {
+                    0x2f => Some(MidiMessage::EndOfTrack),
+                    0x51 if data.len() >= 3 => Some(MidiMessage::Tempo(u32::from_be_bytes([0, data[0], data[1], data[2]]))),
+                    _ => None,
+                }
+            }
+            _ => {
+                return Err(format!("invalid MIDI status byte {:#04x}", status).into());
+            }
+        };
+
+        if let Some(message) = message {
+            events.push(MidiEvent { tick, message });
+            if message == MidiMessage::EndOfTrack {
+                break;
+            }
+        }
+    }
+
+    Ok(events)
+}
+
+// 波表长度
+const TABLE_LEN: usize = 2048;
+
+// 同时发声的最大音符数
+const MAX_VOICES: usize = 32;
+
+// 打击乐固定使用第 10 通道
+const DRUM_CHANNEL: u8 = 9;
+
+// 弯音的范围，单位半音
+const PITCH_BEND_RANGE: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Wave {
+    Sine,
+    Triangle,
+    Saw,
+    Square,
+    Piano,
+}
+
+// 一类乐器的音色：波形和包络（秒）
+#[derive(Debug, Clone, Copy)]
+struct Patch {
+    wave: Wave,
+    attack: f32,
+    decay: f32, // 衰减到持续电平的时间
+    sustain: f32, // 持续电平
+    release: f32,
+}
+
+const fn patch(wave: Wave, attack: f32, decay: f32, sustain: f32, release: f32) -> Patch {
+    Patch { wave, attack, decay, sustain, release }
+}
+
+// General MIDI 的 16 个乐器族，每族 8 个音色
+const PATCHES: [Patch; 16] = [
+    patch(Wave::Piano, 0.002, 1.5, 0.0, 0.3), // 钢琴
+    patch(Wave::Sine, 0.001, 0.8, 0.0, 0.3), // 色彩打击乐
+    patch(Wave::Square, 0.01, 0.05, 0.8, 0.08), // 风琴
+    patch(Wave::Saw, 0.002, 0.9, 0.0, 0.15), // 吉他
+    patch(Wave::Triangle, 0.005, 0.6, 0.5, 0.1), // 贝斯
+    patch(Wave::Saw, 0.08, 0.2, 0.8, 0.3), // 弦乐
+    patch(Wave::Saw, 0.1, 0.3, 0.7, 0.4), // 合奏
+    patch(Wave::Saw, 0.03, 0.2, 0.7, 0.15), // 铜管
+    patch(Wave::Square, 0.03, 0.2, 0.7, 0.1), // 簧管
+    patch(Wave::Sine, 0.04, 0.2, 0.8, 0.15), // 吹管
+    patch(Wave::Square, 0.01, 0.2, 0.7, 0.1), // 合成主音
+    patch(Wave::Triangle, 0.2, 0.5, 0.7, 0.6), // 合成音色
+    patch(Wave::Triangle, 0.1, 0.5, 0.5, 0.5), // 合成效果
+    patch(Wave::Saw, 0.002, 0.7, 0.0, 0.2), // 民族乐器
+    patch(Wave::Sine, 0.001, 0.4, 0.0, 0.1), // 打击乐器
+    patch(Wave::Triangle, 0.01, 0.5, 0.3, 0.3), // 音效
+];
+
+fn build_table(wave: Wave) -> Vec<f32> {
+    // 用有限次谐波叠加，避免高音时严重的混叠
+    let harmonics = |amplitude: &dyn Fn(usize) -> f32| -> Vec<f32> {
+        let table: Vec<f32> = (0..TABLE_LEN)
+            .map(|i| {
+                let x = (i as f32) / (TABLE_LEN as f32) * PI * 2.0;
+                (1..=16).map(|n| amplitude(n) * ((n as f32) * x).sin()).sum()
+            })
+            .collect();
+        let peak = table.iter().fold(0f32, |peak, v| peak.max(v.abs())).max(f32::EPSILON);
+        table.iter().map(|v| v / peak).collect()
+    };
+
+    match wave {
+        Wave::Sine => harmonics(&|n| if n == 1 { 1.0 } else { 0.0 }),
+        Wave::Triangle =>
+            harmonics(&|n| {
+                if n % 2 == 0 {
+                    0.0
+                } else {
+                    let sign = if (n / 2) % 2 == 0 { 1.0 } else { -1.0 };
+                    sign / ((n * n) as f32)
+                }
+            }),
+        Wave::Saw => harmonics(&|n| 1.0 / (n as f32)),
+        Wave::Square => harmonics(&|n| if n % 2 == 1 { 1.0 / (n as f32) } else { 0.0 }),
+        Wave::Piano => harmonics(&|n| 1.0 / ((n * n) as f32).powf(0.8)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    Done,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Voice {
+    channel: u8,
+    key: u8,
+    velocity: f32,
+    patch: Patch,
+    drum: bool,
+    phase: f32,
+    stage: Stage,
+    level: f32,
+    held: bool, // 音符已松开，但延音踏板仍踩着
+    age: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MidiChannel {
+    program: u8,
+    volume: f32,
+    expression: f32,
+    pitch_bend: f32, // 单位半音
+    sustain: bool,
+}
+
+impl Default for MidiChannel {
+    fn default() -> Self {
+        Self { program: 0, volume: 100.0 / 127.0, expression: 1.0, pitch_bend: 0.0, sustain: false }
+    }
+}
+
+pub struct Synth {
+    sample_rate: f32,
+    tables: Vec<Vec<f32>>,
+    channels: [MidiChannel; 16],
+    voices: Vec<Voice>,
+    noise: u32,
+    age: u64,
+}
+
+impl Synth {
+    pub fn new(sample_rate: u32) -> Self {
+        let tables = [Wave::Sine, Wave::Triangle, Wave::Saw, Wave::Square, Wave::Piano]
+            .iter()
+            .map(|&wave| build_table(wave))
+            .collect();
+
+        Self {
+            sample_rate: sample_rate as f32,
+            tables,
+            channels: [MidiChannel::default(); 16],
+            voices: Vec::with_capacity(MAX_VOICES),
+            noise: 0x1234_5678,
+            age: 0,
+        }
+    }
+
+    pub fn active_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    // 复位所有通道并立即停止发声
+    pub fn reset(&mut self) {
+        self.channels = [MidiChannel::default(); 16];
+        self.voices.clear();
+    }
+
+    pub fn handle(&mut self, message: MidiMessage) {
+        match message {
+            MidiMessage::NoteOn { channel, key, velocity } => self.note_on(channel, key, velocity),
+            MidiMessage::NoteOff { channel, key } => self.note_off(channel, key),
+            MidiMessage::ProgramChange { channel, program } => {
+                self.channels[(channel & 0x0f) as usize].program = program;
+            }
+            MidiMessage::PitchBend { channel, value } => {
+                let bend = ((value as f32) - 8192.0) / 8192.0 * PITCH_BEND_RANGE;
+                self.channels[(channel & 0x0f) as usize].pitch_bend = bend;
+            }
+            MidiMessage::Controller { channel, controller, value } => self.controller(channel, controller, value),
+            MidiMessage::Tempo(_) | MidiMessage::EndOfTrack => {}
+        }
+    }
+
+    fn controller(&mut self, channel: u8, controller: u8, value: u8) {
+        let ch = &mut self.channels[(channel & 0x0f) as usize];
+        match controller {
+            7 => {
+                ch.volume = (value as f32) / 127.0;
+            }
+            11 => {
+                ch.expression = (value as f32) / 127.0;
+            }
+            // 延音踏板
+            64 => {
+                ch.sustain = value >= 64;
+                if !ch.sustain {
+                    for voice in self.voices.iter_mut().filter(|voice| voice.channel == channel && voice.held) {
+                        voice.held = false;
+                        voice.stage = Stage::Release;
+                    }
+                }
+            }
+            // 所有声音关闭
+            120 => {
+                self.voices.retain(|voice| voice.channel != channel);
+            }
+            // 复位控制器
+            121 => {
+                *ch = MidiChannel { program: ch.program, ..MidiChannel::default() };
+            }
+            // 所有音符关闭
+            123 => {
+                for voice in self.voices.iter_mut().filter(|voice| voice.channel == channel) {
+                    voice.stage = Stage::Release;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn note_on(&mut self, channel: u8, key: u8, velocity: u8) {
+        let drum = channel == DRUM_CHANNEL;
+        let patch = if drum {
+            PATCHES[14]
+        } else {
+            PATCHES[((self.channels[(channel & 0x0f) as usize].program >> 3) & 0x0f) as usize]
+        };
+
+        // 同一个键重新按下时替换原来的音符
+        self.voices.retain(|voice| !(voice.channel == channel && voice.key == key));
+        if self.voices.len() >= MAX_VOICES {
+            // 挤掉最早的音符
+            if let Some(oldest) = self.voices
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, voice)| (voice.stage != Stage::Release, voice.age))
+                .map(|(i, _)| i)
+            {
+                self.voices.remove(oldest);
+            }
+        }
+
+        self.age += 1;
+        self.voices.push(Voice {
+            channel,
+            key,
+            velocity: (velocity as f32) / 127.0,
+            patch,
+            drum,
+            phase: 0.0,
+            stage: Stage::Attack,
+            level: 0.0,
+            held: false,
+            age: self.age,
+        });
+    }
+
+    fn note_off(&mut self, channel: u8, key: u8) {
+        let sustain = self.channels[(channel & 0x0f) as usize].sustain;
+        for voice in self.voices.iter_mut().filter(|voice| voice.channel == channel && voice.key == key) {
+            if sustain {
+                voice.held = true;
+            } else {
+                voice.stage = Stage::Release;
+            }
+        }
+    }
+
+    fn next_noise(&mut self) -> f32 {
+        // xorshift32
+        let mut x = self.noise;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.noise = x;
+        ((x >> 8) as f32) / ((1 << 23) as f32) - 1.0
+    }
+
+    // 生成单声道 16 位采样，累加到 out 上
+    pub fn render(&mut self, out: &mut [i16]) {
+        let dt = 1.0 / self.sample_rate;
+        for sample in out.iter_mut() {
+            let mut mix = 0.0;
+            for i in 0..self.voices.len() {
+                let noise = if self.voices[i].drum { self.next_noise() } else { 0.0 };
+                let voice = &mut self.voices[i];
+                let channel = &self.channels[(voice.channel & 0x0f) as usize];
+
+                // 包络
+                let patch = voice.patch;
+                match voice.stage {
+                    Stage::Attack => {
+                        voice.level += dt / patch.attack.max(dt);
+                        if voice.level >= 1.0 {
+                            voice.level = 1.0;
+                            voice.stage = Stage::Decay;
+                        }
+                    }
+                    Stage::Decay => {
+                        voice.level -= dt / patch.decay.max(dt) * (1.0 - patch.sustain);
+                        if voice.level <= patch.sustain {
+                            voice.level = patch.sustain;
+                            voice.stage = if patch.sustain > 0.0 { Stage::Sustain } else { Stage::Done };
+                        }
+                    }
+                    Stage::Sustain => {}
+                    Stage::Release => {
+                        voice.level -= dt / patch.release.max(dt);
+                        if voice.level <= 0.0 {
+                            voice.level = 0.0;
+                            voice.stage = Stage::Done;
+                        }
+                    }
+                    Stage::Done => {
+                        continue;
+                    }
+                }
+
+                let gain = voice.level * voice.velocity * channel.volume * channel.expression;
+                let value = if voice.drum {
+                    // 低音鼓和通鼓用低频正弦，其余用噪声
+                    let key = voice.key as f32;
+                    if voice.key < 50 {
+                        let freq = 40.0 + (key - 35.0).max(0.0) * 8.0;
+                        voice.phase = (voice.phase + freq * dt).fract();
+                        (voice.phase * PI * 2.0).sin()
+                    } else {
+                        noise * 0.6
+                    }
+                } else {
+                    let key = (voice.key as f32) + channel.pitch_bend;
+                    let freq = 440.0 * 2f32.powf((key - 69.0) / 12.0);
+                    voice.phase = (voice.phase + freq * dt).fract();
+                    let table = &self.tables[patch.wave as usize];
+                    table[((voice.phase * (TABLE_LEN as f32)) as usize) % TABLE_LEN]
+                };
+                mix += value * gain;
+            }
+            self.voices.retain(|voice| voice.stage != Stage::Done);
+
+            let value = (*sample as f32) + mix * 0.2 * (i16::MAX as f32);
+            *sample = value.clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        }
+    }
+}
+
+// 乐曲结束后每次渲染的采样数
+const TAIL_BLOCK: u64 = 256;
+
+// 按时间顺序把 MIDI 事件送给合成器
+pub struct MidiPlayer {
+    timeline: Vec<(f64, MidiMessage)>,
+    synth: Synth,
+    looping: bool,
+    next_event: usize,
+    sample_rate: u32,
+    samples_played: u64,
+}
+
+impl MidiPlayer {
+    pub fn new(data: &[u8], looping: bool, sample_rate: u32) -> Result<Self> {
+        let midi = MidiFile::parse(data)?;
+
+        Ok(Self {
+            timeline: midi.timeline(),
+            synth: Synth::new(sample_rate),
+            looping,
+            next_event: 0,
+            sample_rate,
+            samples_played: 0,
+        })
+    }
+
+    // 乐曲时长，秒
+    pub fn duration(&self) -> f64 {
+        self.timeline.last().map_or(0.0, |&(seconds, _)| seconds)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.next_event >= self.timeline.len() && self.synth.active_voices() == 0
+    }
+
+    fn rewind(&mut self) {
+        self.synth.reset();
+        self.next_event = 0;
+        self.samples_played = 0;
+    }
+
+    // 填充 out，返回实际生成的采样数。乐曲结束且不循环时少于 out 的长度
+    pub fn render(&mut self, out: &mut [i16]) -> usize {
+        out.fill(0);
+        let mut written = 0;
+
+        while written < out.len() {
+            let now = (self.samples_played as f64) / (self.sample_rate as f64);
+            while let Some(&(seconds, message)) = self.timeline.get(self.next_event) {
+                if seconds > now {
+                    break;
+                }
+                self.synth.handle(message);
+                self.next_event += 1;
+            }
+
+            if self.next_event >= self.timeline.len() {
+                // 所有事件都在开头时，要等生成了采样才重新开始，否则会一直重复
+                let progressed = self.samples_played > 0 || self.duration() > 0.0;
+                if self.looping && !self.timeline.is_empty() && progressed {
+                    self.rewind();
+                    continue;
+                }
+                if self.synth.active_voices() == 0 {
+                    break;
+                }
+            }
+
+            // 渲染到下一个事件为止，乐曲结束后按小块渲染，以便释音完毕时及时停止
+            let until_next = self.timeline
+                .get(self.next_event)
+                .map_or(TAIL_BLOCK, |&(seconds, _)| ((seconds * (self.sample_rate as f64)).ceil() as u64).saturating_sub(self.samples_played));
+            let n = std::cmp::min(std::cmp::max(until_next, 1), (out.len() - written) as u64) as usize;
+            self.synth.render(&mut out[written..written + n]);
+            written += n;
+            self.samples_played += n as u64;
+        }
+
+        written
+    }
+}
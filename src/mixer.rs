@@ -0,0 +1,78 @@
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::sync::{ Arc, Mutex, MutexGuard };
+use std::thread::{ self, JoinHandle };
+use std::time::{ Duration, Instant };
+
+use crate::audio::Audio;
+use crate::audio_sink::AudioSink;
+use crate::utils::Result;
+
+// 每次混音的采样数
+const MIX_BLOCK: usize = 512;
+
+// 混音领先实际时间的最大值
+const MIX_AHEAD: Duration = Duration::from_millis(50);
+
+// 在单独的线程上从 Audio 拉取采样，按实际时间的节奏送给 AudioSink
+pub struct Mixer {
+    audio: Arc<Mutex<Audio>>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<std::result::Result<Box<dyn AudioSink>, String>>>,
+}
+
+impl Mixer {
+    pub fn start(audio: Audio, mut sink: Box<dyn AudioSink>) -> Self {
+        let audio = Arc::new(Mutex::new(audio));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let source = audio.clone();
+        let flag = running.clone();
+        let thread = thread::spawn(move || {
+            let sample_rate = sink.sample_rate() as f64;
+            let mut buf = vec![0i16; MIX_BLOCK];
+            let begin = Instant::now();
+            let mut mixed = 0u64;
+
+            while flag.load(Ordering::Relaxed) {
+                source.lock().unwrap_or_else(|e| e.into_inner()).render(&mut buf);
+                sink.write(&buf).map_err(|e| e.to_string())?;
+                mixed += MIX_BLOCK as u64;
+
+                let ahead = Duration::from_secs_f64((mixed as f64) / sample_rate).saturating_sub(begin.elapsed());
+                if ahead > MIX_AHEAD {
+                    thread::sleep(ahead - MIX_AHEAD);
+                }
+            }
+            sink.finish().map_err(|e| e.to_string())?;
+
+            Ok(sink)
+        });
+
+        Self { audio, running, thread: Some(thread) }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, Audio> {
+        self.audio.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.lock().sample_rate
+    }
+
+    // 停止混音线程，返回 AudioSink
+    pub fn stop(&mut self) -> Result<Box<dyn AudioSink>> {
+        self.running.store(false, Ordering::Relaxed);
+        let thread = self.thread.take().ok_or("mixer already stopped")?;
+        let sink = thread.join().map_err(|_| "mixer thread panicked")??;
+
+        Ok(sink)
+    }
+}
+
+impl Drop for Mixer {
+    fn drop(&mut self) {
+        if self.thread.is_some() {
+            let _ = self.stop();
+        }
+    }
+}
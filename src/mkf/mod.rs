@@ -54,6 +54,11 @@ impl MKF {
         Ok(data)
     }
 
+    // 读出所有块，用于替换其中一部分后重新生成文件
+    pub fn read_all_chunks(&mut self) -> Result<Vec<Vec<u8>>, std::io::Error> {
+        (0..self.chunk_count).map(|index| self.read_chunk(index)).collect()
+    }
+
     pub fn read_chunk_decompressed(&mut self, index: u32) -> Result<Vec<u8>, std::io::Error> {
         let data = self.read_chunk(index)?;
         match decompress::decompress(data) {
@@ -127,6 +132,22 @@ impl MKF {
     }
 }
 
+// 由各块的内容生成 MKF 文件：开头是每块的起始位置，最后一项为文件长度
+pub fn build(chunks: &[Vec<u8>]) -> Vec<u8> {
+    let mut offset = ((chunks.len() + 1) * 4) as u32;
+    let mut buf = Vec::new();
+    for chunk in chunks {
+        buf.extend_from_slice(&offset.to_le_bytes());
+        offset += chunk.len() as u32;
+    }
+    buf.extend_from_slice(&offset.to_le_bytes());
+    for chunk in chunks {
+        buf.extend_from_slice(chunk);
+    }
+
+    buf
+}
+
 pub fn open(mut file: File) -> Result<MKF, std::io::Error> {
     let buf = &mut [0; 4];
 
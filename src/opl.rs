@@ -0,0 +1,424 @@
+use std::f32::consts::PI;
+
+// YM3812 (OPL2) 的软件模拟。
+// 按寄存器的含义用浮点数近似计算包络、调频和节奏乐器，不追求与硬件逐位一致。
+// RIX 只写 OPL2 的寄存器，所以没有实现 OPL3 (YMF262) 的第二组寄存器、四算子连接和立体声输出
+
+// 芯片的原生采样率，频率计算以此为准
+const OPL_RATE: f64 = 49716.0;
+
+pub const NUM_CHANNELS: usize = 9;
+const NUM_OPERATORS: usize = 18;
+
+// 寄存器偏移到算子编号，-1 表示没有对应的算子
+const SLOT_OF_OFFSET: [i8; 0x20] = [
+    0, 1, 2, 3, 4, 5, -1, -1, 6, 7, 8, 9, 10, 11, -1, -1, 12, 13, 14, 15, 16, 17, -1, -1, -1, -1, -1, -1, -1, -1,
+    -1, -1,
+];
+
+const MULTIPLIER: [f32; 16] = [
+    0.5, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 10.0, 12.0, 12.0, 15.0, 15.0,
+];
+
+// 第 7 组音高时各 F-Number 高 4 位对应的音阶衰减，单位 dB
+const KSL_BASE: [f32; 16] = [
+    0.0, 9.0, 12.0, 13.875, 15.0, 16.125, 16.875, 17.625, 18.0, 18.75, 19.125, 19.5, 19.875, 20.25, 20.625, 21.0,
+];
+
+// KSL 寄存器值对应的倍率：0、3、1.5、6 dB/八度
+const KSL_FACTOR: [f32; 4] = [0.0, 0.5, 0.25, 1.0];
+
+// 反馈量对应的相位调制幅度，单位弧度
+const FEEDBACK: [f32; 8] = [0.0, PI / 16.0, PI / 8.0, PI / 4.0, PI / 2.0, PI, PI * 2.0, PI * 4.0];
+
+// 调制器满幅输出对应的相位偏移
+const MODULATION: f32 = PI * 8.0;
+
+// 衰减超过此值视为静音
+const MAX_ATTENUATION: f32 = 96.0;
+
+// 单个算子满幅时对应的 16 位采样值
+const OPERATOR_AMPLITUDE: f32 = 4096.0;
+
+// 节奏模式下各打击乐器在 0xBD 寄存器中的位
+const RHYTHM_BD: u8 = 0x10;
+const RHYTHM_SD: u8 = 0x08;
+const RHYTHM_TOM: u8 = 0x04;
+const RHYTHM_TC: u8 = 0x02;
+const RHYTHM_HH: u8 = 0x01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum Envelope {
+    #[default]
+    Off,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Operator {
+    tremolo: bool,
+    vibrato: bool,
+    sustaining: bool,
+    key_scale_rate: bool,
+    multiplier: u8,
+    key_scale_level: u8,
+    total_level: u8,
+    attack_rate: u8,
+    decay_rate: u8,
+    sustain_level: u8,
+    release_rate: u8,
+    waveform: u8,
+
+    key_on: bool,
+    phase: f64, // 当前相位，以周期为单位
+    envelope: Envelope,
+    attenuation: f32, // 包络的衰减，单位 dB
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Channel {
+    fnum: u16,
+    block: u8,
+    key_on: bool,
+    feedback: u8,
+    additive: bool,
+    feedback_out: [f32; 2], // 第一个算子最近两次的输出
+}
+
+// 包络速率为 rate 时变化 96 dB 所需的秒数，base 为 rate = 4 时的毫秒数
+fn envelope_time(base: f64, rate: u8) -> f64 {
+    base / 1000.0 * 2f64.powf(-((rate as f64) - 4.0) / 4.0)
+}
+
+fn waveform(waveform: u8, phase: f32) -> f32 {
+    let s = phase.sin();
+    match waveform {
+        // 半正弦
+        1 => s.max(0.0),
+        // 全波整流
+        2 => s.abs(),
+        // 每半周期只保留前四分之一
+        3 => {
+            let quarter = (phase / (PI / 2.0)).rem_euclid(2.0);
+            if quarter < 1.0 { s.abs() } else { 0.0 }
+        }
+        _ => s,
+    }
+}
+
+impl Operator {
+    // 音阶速率偏移
+    fn rate_offset(&self, channel: &Channel) -> u8 {
+        let rof = (channel.block << 1) | (((channel.fnum >> 9) & 1) as u8);
+        if self.key_scale_rate { rof } else { rof >> 2 }
+    }
+
+    fn effective_rate(&self, rate: u8, channel: &Channel) -> u8 {
+        if rate == 0 { 0 } else { std::cmp::min(rate * 4 + self.rate_offset(channel), 63) }
+    }
+
+    fn set_key(&mut self, on: bool) {
+        if on && !self.key_on {
+            if self.envelope == Envelope::Off {
+                self.attenuation = MAX_ATTENUATION;
+            }
+            self.envelope = Envelope::Attack;
+            self.phase = 0.0;
+        } else if !on && self.key_on && self.envelope != Envelope::Off {
+            self.envelope = Envelope::Release;
+        }
+        self.key_on = on;
+    }
+
+    fn update_envelope(&mut self, channel: &Channel, sample_rate: f64) {
+        let decay = |op: &Operator, rate: u8| {
+            let rate = op.effective_rate(rate, channel);
+            if rate == 0 { 0.0 } else { (MAX_ATTENUATION as f64) / (envelope_time(39280.64, rate) * sample_rate) }
+        };
+
+        match self.envelope {
+            Envelope::Off => {
+                self.attenuation = MAX_ATTENUATION;
+            }
+            Envelope::Attack => {
+                let rate = self.effective_rate(self.attack_rate, channel);
+                if rate >= 60 {
+                    self.attenuation = 0.0;
+                } else if rate > 0 {
+                    let step = (MAX_ATTENUATION as f64) / (envelope_time(2826.24, rate) * sample_rate);
+                    self.attenuation -= step as f32;
+                }
+                if self.attenuation <= 0.0 {
+                    self.attenuation = 0.0;
+                    self.envelope = Envelope::Decay;
+                }
+            }
+            Envelope::Decay => {
+                let sustain_level = if self.sustain_level == 15 { 93.0 } else { (self.sustain_level as f32) * 3.0 };
+                self.attenuation += decay(self, self.decay_rate) as f32;
+                if self.attenuation >= sustain_level {
+                    self.attenuation = sustain_level;
+                    self.envelope = Envelope::Sustain;
+                }
+            }
+            Envelope::Sustain => {
+                // 非持续音在到达持续电平后按释放速率衰减
+                if !self.sustaining {
+                    self.attenuation += decay(self, self.release_rate) as f32;
+                }
+            }
+            Envelope::Release => {
+                self.attenuation += decay(self, self.release_rate) as f32;
+            }
+        }
+
+        if self.attenuation >= MAX_ATTENUATION {
+            self.attenuation = MAX_ATTENUATION;
+            if self.envelope != Envelope::Attack {
+                self.envelope = Envelope::Off;
+            }
+        }
+    }
+
+    fn key_scale_attenuation(&self, channel: &Channel) -> f32 {
+        let base = KSL_BASE[(channel.fnum >> 6) as usize & 0x0f] - 6.0 * ((7 - channel.block) as f32);
+        base.max(0.0) * KSL_FACTOR[self.key_scale_level as usize & 3]
+    }
+}
+
+pub struct Opl {
+    sample_rate: f64,
+    registers: [u8; 0x100],
+    operators: [Operator; NUM_OPERATORS],
+    channels: [Channel; NUM_CHANNELS],
+    wave_select: bool,
+    tremolo_depth: bool,
+    vibrato_depth: bool,
+    rhythm: u8, // 0xBD 寄存器的低 6 位
+    tremolo_phase: f64,
+    vibrato_phase: f64,
+    noise: u32,
+}
+
+impl Opl {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate as f64,
+            registers: [0; 0x100],
+            operators: [Operator::default(); NUM_OPERATORS],
+            channels: [Channel::default(); NUM_CHANNELS],
+            wave_select: false,
+            tremolo_depth: false,
+            vibrato_depth: false,
+            rhythm: 0,
+            tremolo_phase: 0.0,
+            vibrato_phase: 0.0,
+            noise: 1,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate as u32
+    }
+
+    // 复位所有寄存器，停止发声
+    pub fn reset(&mut self) {
+        *self = Self::new(self.sample_rate as u32);
+    }
+
+    pub fn register(&self, reg: u8) -> u8 {
+        self.registers[reg as usize]
+    }
+
+    pub fn write(&mut self, reg: u8, value: u8) {
+        self.registers[reg as usize] = value;
+
+        let slot = SLOT_OF_OFFSET
+            .get((reg & 0x1f) as usize)
+            .filter(|&&slot| slot >= 0)
+            .map(|&slot| slot as usize);
+        let channel = (reg & 0x0f) as usize;
+
+        match reg {
+            0x01 => {
+                self.wave_select = value & 0x20 != 0;
+            }
+            0x20..=0x35 => {
+                if let Some(op) = slot.map(|slot| &mut self.operators[slot]) {
+                    op.tremolo = value & 0x80 != 0;
+                    op.vibrato = value & 0x40 != 0;
+                    op.sustaining = value & 0x20 != 0;
+                    op.key_scale_rate = value & 0x10 != 0;
+                    op.multiplier = value & 0x0f;
+                }
+            }
+            0x40..=0x55 => {
+                if let Some(op) = slot.map(|slot| &mut self.operators[slot]) {
+                    op.key_scale_level = value >> 6;
+                    op.total_level = value & 0x3f;
+                }
+            }
+            0x60..=0x75 => {
+                if let Some(op) = slot.map(|slot| &mut self.operators[slot]) {
+                    op.attack_rate = value >> 4;
+                    op.decay_rate = value & 0x0f;
+                }
+            }
+            0x80..=0x95 => {
+                if let Some(op) = slot.map(|slot| &mut self.operators[slot]) {
+                    op.sustain_level = value >> 4;
+                    op.release_rate = value & 0x0f;
+                }
+            }
+            0xa0..=0xa8 => {
+                let ch = &mut self.channels[channel];
+                ch.fnum = (ch.fnum & 0x300) | (value as u16);
+            }
+            0xb0..=0xb8 => {
+                let ch = &mut self.channels[channel];
+                ch.fnum = (ch.fnum & 0xff) | (((value & 0x03) as u16) << 8);
+                ch.block = (value >> 2) & 0x07;
+                ch.key_on = value & 0x20 != 0;
+                self.update_keys();
+            }
+            0xbd => {
+                self.tremolo_depth = value & 0x80 != 0;
+                self.vibrato_depth = value & 0x40 != 0;
+                self.rhythm = value & 0x3f;
+                self.update_keys();
+            }
+            0xc0..=0xc8 => {
+                let ch = &mut self.channels[channel];
+                ch.feedback = (value >> 1) & 0x07;
+                ch.additive = value & 0x01 != 0;
+            }
+            0xe0..=0xf5 => {
+                if let Some(op) = slot.map(|slot| &mut self.operators[slot]) {
+                    op.waveform = value & 0x03;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn rhythm_enabled(&self) -> bool {
+        self.rhythm & 0x20 != 0
+    }
+
+    // 根据通道和节奏寄存器的键位更新每个算子的发声状态
+    fn update_keys(&mut self) {
+        let rhythm = if self.rhythm_enabled() { self.rhythm } else { 0 };
+        let drums = [
+            (12, RHYTHM_BD),
+            (15, RHYTHM_BD),
+            (13, RHYTHM_HH),
+            (16, RHYTHM_SD),
+            (14, RHYTHM_TOM),
+            (17, RHYTHM_TC),
+        ];
+
+        for ch in 0..NUM_CHANNELS {
+            let op1 = (ch / 3) * 6 + (ch % 3);
+            for slot in [op1, op1 + 3] {
+                let drum = drums
+                    .iter()
+                    .find(|&&(drum_slot, _)| drum_slot == slot)
+                    .is_some_and(|&(_, bit)| rhythm & bit != 0);
+                self.operators[slot].set_key(self.channels[ch].key_on || drum);
+            }
+        }
+    }
+
+    // 计算一个算子的输出，modulation 为相位调制，单位弧度
+    fn operator_output(&mut self, slot: usize, ch: usize, modulation: f32, tremolo: f32, vibrato: f32) -> f32 {
+        let channel = self.channels[ch];
+        let sample_rate = self.sample_rate;
+        let wave_select = self.wave_select;
+        let op = &mut self.operators[slot];
+
+        let mut freq = (channel.fnum as f64) * 2f64.powi(channel.block as i32) * OPL_RATE / ((1 << 20) as f64);
+        freq *= MULTIPLIER[op.multiplier as usize] as f64;
+        if op.vibrato {
+            freq *= vibrato as f64;
+        }
+        op.phase = (op.phase + freq / sample_rate).fract();
+        op.update_envelope(&channel, sample_rate);
+
+        let mut attenuation =
+            op.attenuation + (op.total_level as f32) * 0.75 + op.key_scale_attenuation(&channel);
+        if op.tremolo {
+            attenuation += tremolo;
+        }
+        if attenuation >= MAX_ATTENUATION {
+            return 0.0;
+        }
+
+        let wave = if wave_select { op.waveform } else { 0 };
+        let phase = (op.phase as f32) * PI * 2.0 + modulation;
+        waveform(wave, phase) * 10f32.powf(-attenuation / 20.0)
+    }
+
+    // 普通的双算子通道
+    fn channel_output(&mut self, ch: usize, tremolo: f32, vibrato: f32) -> f32 {
+        let op1 = (ch / 3) * 6 + (ch % 3);
+        let op2 = op1 + 3;
+        let channel = self.channels[ch];
+
+        let feedback = if channel.feedback > 0 {
+            (channel.feedback_out[0] + channel.feedback_out[1]) * 0.5 * FEEDBACK[channel.feedback as usize]
+        } else {
+            0.0
+        };
+        let out1 = self.operator_output(op1, ch, feedback, tremolo, vibrato);
+        self.channels[ch].feedback_out = [channel.feedback_out[1], out1];
+
+        if channel.additive {
+            out1 + self.operator_output(op2, ch, 0.0, tremolo, vibrato)
+        } else {
+            self.operator_output(op2, ch, out1 * MODULATION, tremolo, vibrato)
+        }
+    }
+
+    // 节奏模式下第 6～8 通道的打击乐器
+    fn rhythm_output(&mut self, tremolo: f32, vibrato: f32) -> f32 {
+        // 23 位线性反馈移位寄存器产生的噪声
+        let bit = ((self.noise >> 22) ^ (self.noise >> 8)) & 1;
+        self.noise = ((self.noise << 1) | bit) & 0x7f_ffff;
+        let noise = if bit != 0 { 1.0 } else { -1.0 };
+
+        let bass_drum = self.channel_output(6, tremolo, vibrato) * 2.0;
+        let hi_hat = self.operator_output(13, 7, 0.0, tremolo, vibrato).abs() * noise;
+        let snare = self.operator_output(16, 7, 0.0, tremolo, vibrato).abs() * noise;
+        let tom = self.operator_output(14, 8, 0.0, tremolo, vibrato);
+        let cymbal = self.operator_output(17, 8, 0.0, tremolo, vibrato).abs() * noise;
+
+        bass_drum + (hi_hat + snare + tom + cymbal) * 2.0
+    }
+
+    // 生成单声道 16 位采样
+    pub fn render(&mut self, out: &mut [i16]) {
+        for sample in out.iter_mut() {
+            // 3.7 Hz 的颤音（音量）和 6.1 Hz 的抖音（音高）
+            self.tremolo_phase = (self.tremolo_phase + 3.7 / self.sample_rate).fract();
+            self.vibrato_phase = (self.vibrato_phase + 6.1 / self.sample_rate).fract();
+            let triangle = 1.0 - ((self.tremolo_phase as f32) * 2.0 - 1.0).abs();
+            let tremolo = triangle * (if self.tremolo_depth { 4.8 } else { 1.0 });
+            let cents = if self.vibrato_depth { 14.0 } else { 7.0 };
+            let vibrato = 2f32.powf(((self.vibrato_phase as f32) * PI * 2.0).sin() * cents / 1200.0);
+
+            let melodic = if self.rhythm_enabled() { 6 } else { NUM_CHANNELS };
+            let mut mix = 0.0;
+            for ch in 0..melodic {
+                mix += self.channel_output(ch, tremolo, vibrato);
+            }
+            if self.rhythm_enabled() {
+                mix += self.rhythm_output(tremolo, vibrato);
+            }
+
+            *sample = (mix * OPERATOR_AMPLITUDE).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use crate::canvas::{ Color, Palette };
+use crate::game::Game;
+use crate::scheduler::FRAME_TIME;
+use crate::utils::*;
+
+// 渐变到红色时不受影响的颜色（文字颜色）
+const FADE_TO_RED_SKIP_COLOR: u8 = 0x4f;
+
+// 一次调色板渐变，由若干帧调色板组成，每帧持续 frame_time 毫秒
+#[derive(Clone)]
+pub struct PaletteFade {
+    frames: Vec<Palette>,
+    frame_time: u32,
+}
+
+// 解析 PAT.MKF 中的调色板，有夜晚调色板的块长度为 1536 字节
+pub fn decode_palette(buf: &[u8], night: bool) -> Palette {
+    let offset = if night && buf.len() >= 256 * 3 * 2 { 256 * 3 } else { 0 };
+
+    let mut colors = Vec::<Color>::with_capacity(256);
+    for i in 0..256 {
+        let r = buf[offset + i * 3] << 2;
+        let g = buf[offset + i * 3 + 1] << 2;
+        let b = buf[offset + i * 3 + 2] << 2;
+
+        colors.push(Color::from_rgb(r, g, b));
+    }
+
+    Palette::with_colors(colors)
+}
+
+impl PaletteFade {
+    // 从 from 线性渐变到 to，共 steps 帧，最后一帧与 to 相同
+    pub fn blend(from: &Palette, to: &Palette, steps: u32, frame_time: u32) -> Self {
+        let frames = (1..=steps).map(|i| from.blend(to, i, steps)).collect();
+
+        Self { frames, frame_time }
+    }
+
+    // 渐暗到黑色
+    pub fn fade_out(from: &Palette, frame_time: u32) -> Self {
+        Self::blend(from, &Palette::new(), 64, frame_time)
+    }
+
+    // 从黑色渐亮
+    pub fn fade_in(to: &Palette, frame_time: u32) -> Self {
+        Self::blend(&Palette::new(), to, 64, frame_time)
+    }
+
+    // 渐变为单一颜色
+    pub fn to_color(from: &Palette, color: Color, frame_time: u32) -> Self {
+        Self::blend(from, &Palette::filled(color), 64, frame_time)
+    }
+
+    // 从单一颜色渐变到调色板
+    pub fn from_color(color: Color, to: &Palette, frame_time: u32) -> Self {
+        Self::blend(&Palette::filled(color), to, 64, frame_time)
+    }
+
+    // 白天、夜晚调色板之间的切换
+    pub fn day_night(from: &Palette, to: &Palette) -> Self {
+        Self::blend(from, to, 32, 100)
+    }
+
+    // 游戏结束时屏幕渐变为红色，每帧各分量最多变化 8
+    pub fn to_red(from: &Palette) -> Self {
+        let mut palette = from.clone();
+        let mut frames = Vec::with_capacity(32);
+
+        for _ in 0..32 {
+            for (i, color) in palette.colors.iter_mut().enumerate() {
+                if i == (FADE_TO_RED_SKIP_COLOR as usize) {
+                    continue;
+                }
+
+                let (r0, g0, b0) = from.colors[i].to_rgb();
+                let target = (((r0 as u32) + (g0 as u32) + (b0 as u32)) / 4 + 64) as u8;
+
+                let (r, g, b) = color.to_rgb();
+                let r = if r > target {
+                    r - std::cmp::min(r - target, 8)
+                } else {
+                    r + std::cmp::min(target - r, 8)
+                };
+
+                *color = Color::from_rgb(r, g.saturating_sub(8), b.saturating_sub(8));
+            }
+
+            frames.push(palette.clone());
+        }
+
+        Self { frames, frame_time: 75 }
+    }
+
+    pub fn frames(&self) -> &[Palette] {
+        &self.frames
+    }
+
+    pub fn frame_time(&self) -> u32 {
+        self.frame_time
+    }
+
+    // 整个渐变的时长，毫秒
+    pub fn duration(&self) -> u32 {
+        (self.frames.len() as u32) * self.frame_time
+    }
+
+    // 渐变开始 elapsed 毫秒后的调色板
+    pub fn palette_at(&self, elapsed: u32) -> &Palette {
+        let i = elapsed.checked_div(self.frame_time).unwrap_or(u32::MAX) as usize;
+        let i = std::cmp::min(i, self.frames.len() - 1);
+
+        &self.frames[i]
+    }
+
+    pub fn last(&self) -> &Palette {
+        &self.frames[self.frames.len() - 1]
+    }
+}
+
+impl Game {
+    // 逐帧播放调色板渐变，update_scene 为 true 时每帧重新绘制场景
+    pub fn play_palette_fade(&mut self, fade: &PaletteFade, update_scene: bool) -> Result<()> {
+        for palette in fade.frames() {
+            if update_scene && self.resource.is_some() {
+                self.make_scence();
+            }
+
+            self.canvas.set_palette(palette);
+            self.blit_to_screen()?;
+            self.process_event()?;
+
+            self.delay(fade.frame_time());
+        }
+
+        Ok(())
+    }
+
+    // 当前场景使用的调色板
+    pub fn get_scene_palette(&mut self) -> Result<Palette> {
+        self.get_palette(self.state.num_palette as u32, self.state.night_palette)
+    }
+
+    // 屏幕渐暗，delay 越大渐变越慢
+    pub fn fade_out(&mut self, delay: u32) -> Result<()> {
+        let fade = PaletteFade::fade_out(self.canvas.get_palette(), delay * 10);
+        self.play_palette_fade(&fade, false)
+    }
+
+    // 从黑屏渐亮到当前场景的调色板
+    pub fn fade_in(&mut self, delay: u32) -> Result<()> {
+        let palette = self.get_scene_palette()?;
+        let fade = PaletteFade::fade_in(&palette, delay * 10);
+        self.play_palette_fade(&fade, false)
+    }
+
+    // 屏幕渐变为红色（游戏结束）
+    pub fn fade_to_red(&mut self) -> Result<()> {
+        let palette = self.get_scene_palette()?;
+
+        // 文字使用的颜色不参与渐变，把画面上同色的像素换成相近的颜色
+        self.canvas.set_pixels(|pixels: &mut [u8]| {
+            for pixel in pixels.iter_mut() {
+                if *pixel == FADE_TO_RED_SKIP_COLOR {
+                    *pixel = FADE_TO_RED_SKIP_COLOR - 1;
+                }
+            }
+        });
+
+        self.play_palette_fade(&PaletteFade::to_red(&palette), false)
+    }
+
+    // 从调色板中的某个颜色渐变出来（from 为 true），或渐变到该颜色
+    pub fn color_fade(&mut self, delay: u32, color_index: u8, from: bool) -> Result<()> {
+        let palette = self.get_scene_palette()?;
+        let color = palette.colors[color_index as usize];
+        let frame_time = if delay == 0 { 10 } else { delay * 10 };
+
+        let fade = if from {
+            PaletteFade::from_color(color, &palette, frame_time)
+        } else {
+            PaletteFade::to_color(&palette, color, frame_time)
+        };
+
+        self.play_palette_fade(&fade, false)
+    }
+
+    // 渐变到指定的白天/夜晚调色板
+    pub fn day_night_fade(&mut self, night: bool, update_scene: bool) -> Result<()> {
+        let palette = self.get_palette(self.state.num_palette as u32, night)?;
+        let fade = PaletteFade::day_night(self.canvas.get_palette(), &palette);
+
+        self.play_palette_fade(&fade, update_scene)
+    }
+
+    // 场景渐变，step 为正时渐亮，为负时渐暗，渐变时持续更新场景
+    pub fn scene_fade(&mut self, step: i16) -> Result<()> {
+        let palette = self.get_scene_palette()?;
+        let step = if step == 0 { 1 } else { step };
+        self.state.need_to_fade_in = false;
+
+        let levels: Vec<u32> = if step > 0 {
+            (0..64).step_by(step as usize).collect()
+        } else {
+            (0..64).rev().step_by(step.unsigned_abs() as usize).collect()
+        };
+
+        for level in levels {
+            if self.resource.is_some() {
+                self.make_scence();
+            }
+            self.canvas.set_palette(&palette.scaled(level, 64));
+            self.blit_to_screen()?;
+            self.process_event()?;
+
+            self.delay(FRAME_TIME);
+        }
+
+        Ok(())
+    }
+}
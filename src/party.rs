@@ -0,0 +1,422 @@
+use crate::data::*;
+
+/*
+typedef enum tagSTATUS
+{
+   kStatusConfused = 0,  // attack friends randomly
+   kStatusParalyzed,     // paralyzed
+   kStatusSleep,         // not allowed to move
+   kStatusSilence,       // cannot use magic
+   kStatusPuppet,        // for dead players only, continue attacking
+   kStatusBravery,       // more power for physical attacks
+   kStatusProtect,       // more defense value
+   kStatusHaste,         // faster
+   kStatusDualAttack,    // dual attack
+   kStatusAll
+} STATUS;
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Confused = 0,
+    Paralyzed,
+    Sleep,
+    Silence,
+    Puppet,
+    Bravery,
+    Protect,
+    Haste,
+    DualAttack,
+}
+
+pub const STATUS_ALL: usize = 9;
+
+impl Status {
+    pub fn from_u16(status: u16) -> Option<Self> {
+        match status {
+            0 => Some(Status::Confused),
+            1 => Some(Status::Paralyzed),
+            2 => Some(Status::Sleep),
+            3 => Some(Status::Silence),
+            4 => Some(Status::Puppet),
+            5 => Some(Status::Bravery),
+            6 => Some(Status::Protect),
+            7 => Some(Status::Haste),
+            8 => Some(Status::DualAttack),
+            _ => None,
+        }
+    }
+}
+
+// 装备效果中额外的一栏，用于临时提升属性
+pub const BODY_PART_EXTRA: usize = MAX_PLAYER_EQUIPMENTS;
+
+// 属性的上限
+const STAT_LIMIT: u16 = 999;
+
+// 持续回合数大于此值的状态来自装备，不会被移除
+const STATUS_FROM_EQUIPMENT: u16 = 999;
+
+// PlayerRoles 中各属性的序号，即在 WORD 数组中的偏移 / MAX_PLAYER_ROLES
+pub const ATTR_SPRITE_NUM: usize = 2;
+pub const ATTR_LEVEL: usize = 6;
+pub const ATTR_MAX_HP: usize = 7;
+pub const ATTR_MAX_MP: usize = 8;
+pub const ATTR_HP: usize = 9;
+pub const ATTR_MP: usize = 10;
+pub const ATTR_EQUIPMENT: usize = 11;
+pub const ATTR_ATTACK_STRENGTH: usize = 17;
+pub const ATTR_MAGIC_STRENGTH: usize = 18;
+pub const ATTR_DEFENSE: usize = 19;
+pub const ATTR_DEXTERITY: usize = 20;
+pub const ATTR_FLEE_RATE: usize = 21;
+pub const ATTR_POISON_RESISTANCE: usize = 22;
+pub const ATTR_ELEMENTAL_RESISTANCE: usize = 23;
+const ATTR_MAGIC: usize = 32;
+const ATTR_ALL: usize = 75;
+
+// 属性序号对应的字段，$borrow 为 & 或 &mut
+macro_rules! attribute_row {
+    ($roles:expr, $attribute:expr, $($borrow:tt)+) => {
+        match $attribute {
+            0 => Some($($borrow)+ $roles.avatar),
+            1 => Some($($borrow)+ $roles.sprite_num_in_battle),
+            2 => Some($($borrow)+ $roles.sprite_num),
+            3 => Some($($borrow)+ $roles.name),
+            4 => Some($($borrow)+ $roles.attack_all),
+            5 => Some($($borrow)+ $roles.unknown1),
+            6 => Some($($borrow)+ $roles.level),
+            7 => Some($($borrow)+ $roles.max_hp),
+            8 => Some($($borrow)+ $roles.max_mp),
+            9 => Some($($borrow)+ $roles.hp),
+            10 => Some($($borrow)+ $roles.mp),
+            11..=16 => Some($($borrow)+ $roles.equipment[$attribute - ATTR_EQUIPMENT]),
+            17 => Some($($borrow)+ $roles.attack_strength),
+            18 => Some($($borrow)+ $roles.magic_strength),
+            19 => Some($($borrow)+ $roles.defense),
+            20 => Some($($borrow)+ $roles.dexterity),
+            21 => Some($($borrow)+ $roles.flee_rate),
+            22 => Some($($borrow)+ $roles.poison_resistance),
+            23..=27 => Some($($borrow)+ $roles.elemental_resistance[$attribute - ATTR_ELEMENTAL_RESISTANCE]),
+            28 => Some($($borrow)+ $roles.unknown2),
+            29 => Some($($borrow)+ $roles.unknown3),
+            30 => Some($($borrow)+ $roles.unknown4),
+            31 => Some($($borrow)+ $roles.covered_by),
+            32..=63 => Some($($borrow)+ $roles.magic[$attribute - ATTR_MAGIC]),
+            64 => Some($($borrow)+ $roles.walk_frames),
+            65 => Some($($borrow)+ $roles.cooperative_magic),
+            66 => Some($($borrow)+ $roles.unknown5),
+            67 => Some($($borrow)+ $roles.unknown6),
+            68 => Some($($borrow)+ $roles.death_sound),
+            69 => Some($($borrow)+ $roles.attack_sound),
+            70 => Some($($borrow)+ $roles.weapon_sound),
+            71 => Some($($borrow)+ $roles.critical_sound),
+            72 => Some($($borrow)+ $roles.magic_sound),
+            73 => Some($($borrow)+ $roles.cover_sound),
+            74 => Some($($borrow)+ $roles.dying_sound),
+            _ => None,
+        }
+    };
+}
+
+impl PlayerRoles {
+    // 按 sdlpal 的方式把 PlayerRoles 当作 WORD 数组访问，越界时返回 0
+    pub fn attribute(&self, attribute: usize, player_role: usize) -> u16 {
+        let row: Option<&[u16; MAX_PLAYER_ROLES]> = attribute_row!(self, attribute, &);
+        row.and_then(|row| row.get(player_role)).copied().unwrap_or(0)
+    }
+
+    pub fn set_attribute(&mut self, attribute: usize, player_role: usize, value: u16) {
+        let row: Option<&mut [u16; MAX_PLAYER_ROLES]> = attribute_row!(self, attribute, &mut);
+        if let Some(word) = row.and_then(|row| row.get_mut(player_role)) {
+            *word = value;
+        }
+    }
+}
+
+// 队伍中角色的状态快照，属性值已计入装备效果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Player {
+    pub player_role: usize,
+    pub name: u16,
+    pub level: u16,
+    pub hp: u16,
+    pub max_hp: u16,
+    pub mp: u16,
+    pub max_mp: u16,
+    pub attack_strength: u16,
+    pub magic_strength: u16,
+    pub defense: u16,
+    pub dexterity: u16,
+    pub flee_rate: u16,
+    pub equipment: [u16; MAX_PLAYER_EQUIPMENTS],
+    pub status: [u16; STATUS_ALL],
+    pub poisons: Vec<PoisonStatus>,
+    pub magics: Vec<u16>,
+}
+
+// 队伍的运行时数据，不写入存档
+#[derive(Debug, Clone, PartialEq)]
+pub struct Party {
+    pub status: [[u16; STATUS_ALL]; MAX_PLAYER_ROLES], // 各角色的状态及剩余回合数
+    pub equipment_effect: Vec<PlayerRoles>, // 各件装备和临时效果对属性的加成
+    pub cur_equip_part: Option<usize>, // 正在执行装备脚本的部位
+    pub last_unequipped_item: u16, // 最近一次卸下的装备
+}
+
+impl Default for Party {
+    fn default() -> Self {
+        Self {
+            status: [[0; STATUS_ALL]; MAX_PLAYER_ROLES],
+            equipment_effect: vec![PlayerRoles::default(); MAX_PLAYER_EQUIPMENTS + 1],
+            cur_equip_part: None,
+            last_unequipped_item: 0,
+        }
+    }
+}
+
+impl GameState {
+    // 队伍成员的角色编号
+    pub fn party_roles(&self) -> Vec<usize> {
+        self.party
+            .iter()
+            .take((self.max_party_member_index as usize) + 1)
+            .map(|member| member.player_role as usize)
+            .collect()
+    }
+
+    // 角色在队伍中的位置
+    pub fn party_index(&self, player_role: usize) -> Option<usize> {
+        self.party_roles()
+            .iter()
+            .position(|&role| role == player_role)
+    }
+
+    // 计入装备效果后的属性值
+    pub fn player_stat(&self, attribute: usize, player_role: usize) -> u16 {
+        let base = self.player_roles.attribute(attribute, player_role);
+        self.party_state.equipment_effect
+            .iter()
+            .fold(base, |value, effect| value.wrapping_add(effect.attribute(attribute, player_role)))
+    }
+
+    pub fn player(&self, player_role: usize) -> Player {
+        let roles = &self.player_roles;
+        let role = player_role;
+
+        let poisons = match self.party_index(role) {
+            Some(index) =>
+                self.poison_status
+                    .iter()
+                    .filter_map(|slots| slots.get(index))
+                    .filter(|poison| poison.poison_id != 0)
+                    .copied()
+                    .collect(),
+            None => Vec::new(),
+        };
+
+        Player {
+            player_role,
+            name: roles.name[role],
+            level: roles.level[role],
+            hp: roles.hp[role],
+            max_hp: roles.max_hp[role],
+            mp: roles.mp[role],
+            max_mp: roles.max_mp[role],
+            attack_strength: self.player_stat(ATTR_ATTACK_STRENGTH, role),
+            magic_strength: self.player_stat(ATTR_MAGIC_STRENGTH, role),
+            defense: self.player_stat(ATTR_DEFENSE, role),
+            dexterity: self.player_stat(ATTR_DEXTERITY, role),
+            flee_rate: self.player_stat(ATTR_FLEE_RATE, role),
+            equipment: std::array::from_fn(|i| roles.equipment[i][role]),
+            status: self.party_state.status[role],
+            poisons,
+            magics: roles.magic
+                .iter()
+                .map(|magics| magics[role])
+                .filter(|&magic| magic != 0)
+                .collect(),
+        }
+    }
+
+    // 增减存活角色的 HP 和 MP，角色已阵亡时返回 false
+    pub fn increase_hp_mp(&mut self, player_role: usize, hp: i16, mp: i16) -> bool {
+        let roles = &mut self.player_roles;
+        if roles.hp[player_role] == 0 {
+            return false;
+        }
+
+        let value = (roles.hp[player_role] as i32) + (hp as i32);
+        roles.hp[player_role] = value.clamp(0, roles.max_hp[player_role] as i32) as u16;
+
+        let value = (roles.mp[player_role] as i32) + (mp as i32);
+        roles.mp[player_role] = value.clamp(0, roles.max_mp[player_role] as i32) as u16;
+
+        true
+    }
+
+    pub fn set_player_status(&mut self, player_role: usize, status: u16, num_round: u16) {
+        let alive = self.player_roles.hp[player_role] != 0;
+        let current = &mut self.party_state.status[player_role];
+        let i = status as usize;
+
+        match Status::from_u16(status) {
+            // 不良状态：已有该状态时不再设置
+            Some(Status::Confused | Status::Sleep | Status::Silence | Status::Paralyzed)
+                if alive && current[i] == 0 => {
+                current[i] = num_round;
+            }
+            // 傀儡状态只对阵亡的角色有效
+            Some(Status::Puppet) if !alive && current[i] < num_round => {
+                current[i] = num_round;
+            }
+            // 良好状态：持续时间更长时覆盖
+            Some(Status::Bravery | Status::Protect | Status::DualAttack | Status::Haste)
+                if alive && current[i] < num_round => {
+                current[i] = num_round;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn remove_player_status(&mut self, player_role: usize, status: u16) {
+        if let Some(value) = self.party_state.status[player_role].get_mut(status as usize) {
+            // 装备带来的状态不移除
+            if *value <= STATUS_FROM_EQUIPMENT {
+                *value = 0;
+            }
+        }
+    }
+
+    pub fn clear_player_status(&mut self, player_role: usize) {
+        for status in 0..STATUS_ALL {
+            self.remove_player_status(player_role, status as u16);
+        }
+    }
+
+    // 战斗中每回合结束时减少状态的剩余回合数，装备带来的状态不变
+    pub fn decrease_player_status_rounds(&mut self, player_role: usize) {
+        for value in self.party_state.status[player_role].iter_mut() {
+            if *value > 0 && *value <= STATUS_FROM_EQUIPMENT {
+                *value -= 1;
+            }
+        }
+    }
+
+    // 角色中毒，已中同种毒或没有空位时返回 false
+    pub fn add_poison_for_player(&mut self, player_role: usize, poison_id: u16) -> bool {
+        let index = match self.party_index(player_role) {
+            Some(index) if poison_id != 0 => index,
+            _ => {
+                return false;
+            }
+        };
+        if self.poison_status.iter().any(|slots| slots[index].poison_id == poison_id) {
+            return false;
+        }
+
+        let poison_script = self.object_poison(poison_id).map_or(0, |poison| poison.player_script);
+        match self.poison_status.iter_mut().find(|slots| slots[index].poison_id == 0) {
+            Some(slots) => {
+                slots[index] = PoisonStatus { poison_id, poison_script };
+                true
+            }
+            None => false,
+        }
+    }
+
+    // 解除指定种类的毒
+    pub fn cure_poison_by_kind(&mut self, player_role: usize, poison_id: u16) {
+        if let Some(index) = self.party_index(player_role) {
+            for slots in self.poison_status.iter_mut() {
+                if slots[index].poison_id == poison_id {
+                    slots[index] = PoisonStatus::default();
+                }
+            }
+        }
+    }
+
+    // 解除等级不高于 max_level 的毒
+    pub fn cure_poison_by_level(&mut self, player_role: usize, max_level: u16) {
+        let index = match self.party_index(player_role) {
+            Some(index) => index,
+            None => {
+                return;
+            }
+        };
+
+        for slots in self.poison_status.iter_mut() {
+            let poison = &mut slots[index];
+            let level = self.objects
+                .get(poison.poison_id as usize)
+                .map_or(0, |object| object.poison().poison_level);
+            if level <= max_level {
+                *poison = PoisonStatus::default();
+            }
+        }
+    }
+
+    // 学会法术，已经学会或法术栏已满时返回 false
+    pub fn add_magic(&mut self, player_role: usize, magic: u16) -> bool {
+        let magics = &mut self.player_roles.magic;
+        if magics.iter().any(|m| m[player_role] == magic) {
+            return false;
+        }
+
+        match magics.iter_mut().find(|m| m[player_role] == 0) {
+            Some(slot) => {
+                slot[player_role] = magic;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove_magic(&mut self, player_role: usize, magic: u16) {
+        if let Some(slot) = self.player_roles.magic.iter_mut().find(|m| m[player_role] == magic) {
+            slot[player_role] = 0;
+        }
+    }
+
+    pub fn remove_equipment_effect(&mut self, player_role: usize, equip_part: usize) {
+        if let Some(effect) = self.party_state.equipment_effect.get_mut(equip_part) {
+            for attribute in 0..ATTR_ALL {
+                effect.set_attribute(attribute, player_role, 0);
+            }
+        }
+    }
+
+    // 升级，属性随机增长
+    pub fn player_level_up(&mut self, player_role: usize, num_level: u16) {
+        let roles = &mut self.player_roles;
+        let rng = &mut self.rng;
+        let role = player_role;
+
+        roles.level[role] = std::cmp::min(roles.level[role].saturating_add(num_level), MAX_LEVELS as u16);
+
+        let mut random = |max: u16| rng.random_long(0, max as i32) as u16;
+        for _ in 0..num_level {
+            roles.max_hp[role] = roles.max_hp[role].saturating_add(10 + random(8));
+            roles.max_mp[role] = roles.max_mp[role].saturating_add(8 + random(6));
+            roles.attack_strength[role] = roles.attack_strength[role].saturating_add(4 + random(1));
+            roles.magic_strength[role] = roles.magic_strength[role].saturating_add(4 + random(1));
+            roles.defense[role] = roles.defense[role].saturating_add(2 + random(1));
+            roles.dexterity[role] = roles.dexterity[role].saturating_add(2 + random(1));
+            roles.flee_rate[role] = roles.flee_rate[role].saturating_add(2);
+        }
+
+        for stat in [
+            &mut roles.max_hp,
+            &mut roles.max_mp,
+            &mut roles.attack_strength,
+            &mut roles.magic_strength,
+            &mut roles.defense,
+            &mut roles.dexterity,
+            &mut roles.flee_rate,
+        ] {
+            stat[role] = std::cmp::min(stat[role], STAT_LIMIT);
+        }
+
+        self.exp.primary_exp[role].exp = 0;
+        self.exp.primary_exp[role].level = roles.level[role];
+    }
+}
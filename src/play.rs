@@ -1,3 +1,4 @@
+use crate::data::TriggerMode;
 use crate::game::Game;
 use crate::input::PalKey;
 use crate::scene::Map;
@@ -16,14 +17,9 @@ impl Game {
         let scene = &self.state.scenes[i];
         let map = Map::load(&mut self.mkf.map, &mut self.mkf.gop, scene.map_num as u32)?;
 
-        let index = self.state.scenes[i].event_object_index;
-        let event_objects_count = self.state.scenes[i + 1].event_object_index - index;
-
         let mut event_object_sprites: Vec<Vec<SpriteFrame>> = Vec::new();
 
-        for event_object in self.state.event_objects[
-            index as usize..(index + event_objects_count) as usize
-        ].iter() {
+        for event_object in self.state.event_objects[self.state.scene_event_objects()].iter() {
             let sprite_num = event_object.sprite_num;
 
             let chunk = self.mkf.mgo.read_chunk_decompressed(sprite_num as u32)?;
@@ -37,54 +33,199 @@ impl Game {
             event_object_sprites,
             player_sprites: Vec::new(),
         });
+        self.load_player_sprites()?;
 
         Ok(())
     }
 
-    pub fn mainloop(&mut self) -> Result<()> {
-        self.set_palette(0)?;
-        loop {
+    // 加载队伍成员在场景中的精灵
+    pub fn load_player_sprites(&mut self) -> Result<()> {
+        let mut player_sprites = Vec::new();
+        for role in self.state.party_roles() {
+            let sprite_num = self.state.player_roles.sprite_num[role];
+            let chunk = self.mkf.mgo.read_chunk_decompressed(sprite_num as u32)?;
+            player_sprites.push(sprite_get_frames(&chunk)?);
+        }
+
+        if let Some(resource) = self.resource.as_mut() {
+            resource.player_sprites = player_sprites;
+        }
+
+        Ok(())
+    }
+
+    // 搜索队伍面前的事件对象，并执行其触发脚本
+    fn search(&mut self) -> Result<()> {
+        let mut x = self.state.viewport.x + self.state.party_offset.x;
+        let mut y = self.state.viewport.y + self.state.party_offset.y;
+
+        let x_offset = match self.state.party_direction {
+            Dir::North | Dir::East => 16,
+            _ => -16,
+        };
+        let y_offset = match self.state.party_direction {
+            Dir::East | Dir::South => 8,
+            _ => -8,
+        };
+
+        let mut positions = vec![Pos { x, y }];
+        for _ in 0..4 {
+            positions.push(Pos { x: x + x_offset, y: y + y_offset });
+            positions.push(Pos { x, y: y + y_offset * 2 });
+            positions.push(Pos { x: x + x_offset, y });
+            x += x_offset;
+            y += y_offset;
+        }
+
+        let event_objects = self.state.scene_event_objects();
+
+        for (i, pos) in positions.iter().enumerate() {
+            let dh = if pos.x % 32 != 0 { 1 } else { 0 };
+            let dx = pos.x / 32;
+            let dy = pos.y / 16;
+
+            for k in event_objects.clone() {
+                let event_object = &self.state.event_objects[k];
+                let ex = (event_object.x as isize) / 32;
+                let ey = (event_object.y as isize) / 16;
+                let eh = if (event_object.x as isize) % 32 != 0 { 1 } else { 0 };
+
+                let trigger_mode = event_object.trigger_mode as isize;
+                if
+                    (event_object.state as i16) <= 0 ||
+                    trigger_mode >= (TriggerMode::TouchNear as isize) ||
+                    trigger_mode * 6 - 4 < (i as isize) ||
+                    dx != ex ||
+                    dy != ey ||
+                    dh != eh
+                {
+                    continue;
+                }
+
+                let event_object = &mut self.state.event_objects[k];
+                if event_object.sprite_frames * 4 > event_object.current_frame_num {
+                    // 面向队伍
+                    event_object.current_frame_num = 0;
+                    event_object.direction = ((self.state.party_direction.clone() as u16) + 2) % 4;
+                }
+
+                let trigger_script = event_object.trigger_script;
+                self.state.event_objects[k].trigger_script = self.run_trigger_script(
+                    trigger_script,
+                    (k as u16) + 1
+                )?;
+
+                return Ok(());
+            }
+        }
+
+        Ok(())
+    }
+
+    // 运行一个逻辑帧
+    pub fn update(&mut self) -> Result<()> {
+        if let Some(slot) = self.pending_load.take() {
+            if slot == 0 {
+                self.new_game()?;
+            } else {
+                self.load_game(slot)?;
+            }
+        }
+
+        if self.state.need_load_scene {
+            if self.resource.is_some() && !self.state.need_to_fade_in {
+                self.fade_out(1)?;
+                self.state.need_to_fade_in = true;
+            }
+            self.state.need_load_scene = false;
             self.load_resource()?;
-            if self.state.entering_scene {
-                self.state.entering_scene = false;
-                let i = (self.state.scene_num as usize) - 1;
-                println!("i:{} run trigger script:{}", i, self.state.scenes[i].script_on_enter);
-                self.state.scenes[i].script_on_enter = self.run_trigger_script(
-                    self.state.scenes[i].script_on_enter,
-                    0xffff
-                );
+        }
+
+        if self.state.entering_scene {
+            self.state.entering_scene = false;
+            let i = (self.state.scene_num as usize) - 1;
+            self.state.scenes[i].script_on_enter = self.run_trigger_script(
+                self.state.scenes[i].script_on_enter,
+                0xffff
+            )?;
+        }
+
+        // 进入脚本又切换了场景，下一帧再处理
+        if !self.state.need_load_scene && !self.state.entering_scene {
+            self.update_screen_wave();
+
+            match self.input.dir {
+                Dir::North => {
+                    self.state.viewport.y -= 30;
+                }
+                Dir::East => {
+                    self.state.viewport.x += 30;
+                }
+                Dir::South => {
+                    self.state.viewport.y += 30;
+                }
+                Dir::West => {
+                    self.state.viewport.x -= 30;
+                }
+                _ => {}
+            }
+            if self.input.dir != Dir::Unknown {
+                self.state.party_direction = self.input.dir.clone();
+                self.check_encounter()?;
+            }
+
+            if self.input.is_pressed(PalKey::Search) {
+                self.search()?;
+            } else if self.input.is_pressed(PalKey::Menu) {
+                self.in_game_menu()?;
+            } else if self.input.is_pressed(PalKey::Status) {
+                self.player_status()?;
+            } else if self.input.is_pressed(PalKey::UseItem) {
+                self.game_use_item()?;
+            } else if self.input.is_pressed(PalKey::ThrowItem) {
+                self.game_equip_item()?;
+            } else if self.input.is_pressed(PalKey::Force) {
+                self.in_game_magic_menu()?;
             }
+        }
+
+        // 按键只在一个逻辑帧内有效
+        self.input.key_press = 0;
+        self.scheduler.end_frame();
 
-            loop {
+        Ok(())
+    }
+
+    // 连续运行 n 个逻辑帧，不渲染画面
+    pub fn step(&mut self, n: u32) -> Result<()> {
+        for _ in 0..n {
+            self.update()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn mainloop(&mut self) -> Result<()> {
+        let (num_palette, night) = (self.state.num_palette, self.state.night_palette);
+        self.set_palette(num_palette as u32, night)?;
+        while !self.quit {
+            self.process_event()?;
+            for _ in 0..self.frames_due()? {
+                self.update()?;
+            }
+
+            if self.resource.is_some() && !self.state.need_load_scene && !self.state.entering_scene {
                 self.make_scence();
                 self.blit_to_screen()?;
-                self.process_event();
-
-                //println!("dir: {:?}", self.input.dir);
-                match self.input.dir {
-                    Dir::North => {
-                        self.state.viewport.y -= 30;
-                    }
-                    Dir::East => {
-                        self.state.viewport.x += 30;
-                    }
-                    Dir::South => {
-                        self.state.viewport.y += 30;
-                    }
-                    Dir::West => {
-                        self.state.viewport.x -= 30;
-                    }
-                    _ => {}
-                }
-
-                if self.input.is_pressed(PalKey::Search) {
-                    self.state.entering_scene = true;
-                    self.state.scene_num += 1;
-                    break;
+                if self.state.need_to_fade_in {
+                    self.fade_in(1)?;
+                    self.state.need_to_fade_in = false;
                 }
-
-                std::thread::sleep(std::time::Duration::from_millis(30));
             }
+
+            self.scheduler.wait_next_frame();
         }
+
+        Ok(())
     }
 }
@@ -0,0 +1,57 @@
+// 游戏逻辑使用的随机数发生器（xorshift32）。
+// 状态只有 32 位，可以写入存档的保留字段；相同的种子和输入序列会得到完全相同的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameRng {
+    state: u32,
+}
+
+// xorshift 的状态不能为 0
+const ZERO_SEED_STATE: u32 = 0x9e37_79b9;
+
+impl GameRng {
+    pub fn new(seed: u32) -> Self {
+        Self { state: if seed == 0 { ZERO_SEED_STATE } else { seed } }
+    }
+
+    // 以系统随机数为种子
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random::<u32>())
+    }
+
+    pub fn state(&self) -> u32 {
+        self.state
+    }
+
+    pub fn set_state(&mut self, state: u32) {
+        *self = Self::new(state);
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    // [from, to] 之间的随机整数
+    pub fn random_long(&mut self, from: i32, to: i32) -> i32 {
+        if to <= from {
+            return from;
+        }
+        from + ((self.next_u32() % ((to - from + 1) as u32)) as i32)
+    }
+
+    // [from, to) 之间的随机小数
+    pub fn random_float(&mut self, from: f32, to: f32) -> f32 {
+        let unit = ((self.next_u32() >> 8) as f32) / ((1u32 << 24) as f32);
+        from + unit * (to - from)
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
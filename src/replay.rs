@@ -0,0 +1,265 @@
+use std::fmt::Display;
+use std::path::Path;
+
+use crate::game::Game;
+use crate::scheduler::Scheduler;
+use crate::utils::{ Dir, Result };
+
+// 录像：从一个存档开始，按顺序记录每次 process_event 的输入和主循环每次运行的逻辑帧数。
+// 回放时不读取窗口，按录像驱动游戏，结束后比较游戏状态的哈希
+
+const RECORDING_MAGIC: &[u8] = b"PALREC";
+const RECORDING_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayEvent {
+    Input { key_press: u32, dir: u8 }, // 一次 process_event 的结果
+    Frames(u16), // 主循环一次运行的逻辑帧数
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Recording {
+    pub rng_state: u32,
+    pub save: Vec<u8>, // 开始时的存档，格式同 .RPG 文件
+    pub events: Vec<ReplayEvent>,
+    pub final_hash: u64, // 录像结束时游戏状态的哈希
+}
+
+// 回放到录像末尾时由 process_event 返回，用来跳出任意一层循环
+#[derive(Debug)]
+pub struct ReplayFinished;
+
+impl Display for ReplayFinished {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "replay finished")
+    }
+}
+
+impl std::error::Error for ReplayFinished {}
+
+pub enum InputMode {
+    Live,
+    Record(Recording),
+    Replay { recording: Recording, pos: usize },
+}
+
+// 64 位 FNV-1a
+pub fn fnv1a(buf: &[u8]) -> u64 {
+    buf.iter().fold(0xcbf29ce484222325u64, |hash, &b| (hash ^ (b as u64)).wrapping_mul(0x100000001b3))
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.buf.len()).ok_or("truncated recording")?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let b = self.bytes(8)?;
+        Ok(u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+    }
+}
+
+impl Recording {
+    // 相同的连续事件合并为一项：重复次数 u16、类型 u8、内容
+    pub fn encode(&self) -> Vec<u8> {
+        let mut runs: Vec<(u16, ReplayEvent)> = Vec::new();
+        for &event in self.events.iter() {
+            match runs.last_mut() {
+                Some((count, last)) if *last == event && *count < u16::MAX => {
+                    *count += 1;
+                }
+                _ => runs.push((1, event)),
+            }
+        }
+
+        let mut buf = RECORDING_MAGIC.to_vec();
+        buf.push(RECORDING_VERSION);
+        buf.extend_from_slice(&self.rng_state.to_le_bytes());
+        buf.extend_from_slice(&self.final_hash.to_le_bytes());
+        buf.extend_from_slice(&(self.save.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&self.save);
+        buf.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+        for (count, event) in runs {
+            buf.extend_from_slice(&count.to_le_bytes());
+            match event {
+                ReplayEvent::Input { key_press, dir } => {
+                    buf.push(0);
+                    buf.extend_from_slice(&key_press.to_le_bytes());
+                    buf.push(dir);
+                }
+                ReplayEvent::Frames(n) => {
+                    buf.push(1);
+                    buf.extend_from_slice(&n.to_le_bytes());
+                }
+            }
+        }
+
+        buf
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        let mut reader = Reader { buf, pos: 0 };
+        if reader.bytes(RECORDING_MAGIC.len())? != RECORDING_MAGIC {
+            return Err("not a recording".into());
+        }
+        let version = reader.u8()?;
+        if version != RECORDING_VERSION {
+            return Err(format!("unsupported recording version {}", version).into());
+        }
+
+        let rng_state = reader.u32()?;
+        let final_hash = reader.u64()?;
+        let save_len = reader.u32()? as usize;
+        let save = reader.bytes(save_len)?.to_vec();
+
+        let num_runs = reader.u32()?;
+        let mut events = Vec::new();
+        for _ in 0..num_runs {
+            let count = reader.u16()?;
+            let event = match reader.u8()? {
+                0 => ReplayEvent::Input { key_press: reader.u32()?, dir: reader.u8()? },
+                1 => ReplayEvent::Frames(reader.u16()?),
+                kind => {
+                    return Err(format!("invalid recording event {}", kind).into());
+                }
+            };
+            events.extend(std::iter::repeat_n(event, count as usize));
+        }
+
+        Ok(Self { rng_state, save, events, final_hash })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::decode(&std::fs::read(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, self.encode())?;
+
+        Ok(())
+    }
+}
+
+impl Game {
+    // 当前游戏状态的哈希，用于检查回放结果
+    pub fn state_hash(&self) -> Result<u64> {
+        Ok(fnv1a(&self.state.to_rpg()?))
+    }
+
+    // 从当前状态开始录像。先用存档重新读入一次，保证录制和回放的起点完全相同
+    pub fn start_recording(&mut self) -> Result<()> {
+        let save = self.state.to_rpg()?;
+        let rng_state = self.state.rng.state();
+        self.load_state(&save)?;
+        self.state.rng.set_state(rng_state);
+        self.input_mode = InputMode::Record(Recording { rng_state, save, ..Recording::default() });
+
+        Ok(())
+    }
+
+    // 结束录像并写入文件，不在录像时什么也不做
+    pub fn finish_recording<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        if let InputMode::Record(mut recording) = std::mem::replace(&mut self.input_mode, InputMode::Live) {
+            recording.final_hash = self.state_hash()?;
+            recording.save(path)?;
+        }
+
+        Ok(())
+    }
+
+    // 不打开窗口、不等待真实时间地回放录像，返回最终状态的哈希。与录像中的不一致时出错
+    pub fn run_replay(&mut self, recording: Recording) -> Result<u64> {
+        self.load_state(&recording.save)?;
+        self.state.rng.set_state(recording.rng_state);
+        self.scheduler = Scheduler::stepped();
+        let expected = recording.final_hash;
+        self.input_mode = InputMode::Replay { recording, pos: 0 };
+
+        match self.mainloop() {
+            Err(e) if e.is::<ReplayFinished>() => {}
+            Err(e) => {
+                return Err(e);
+            }
+            Ok(()) => {}
+        }
+        self.input_mode = InputMode::Live;
+
+        let hash = self.state_hash()?;
+        if hash != expected {
+            return Err(format!("replay diverged: expected state hash {:016x}, got {:016x}", expected, hash).into());
+        }
+
+        Ok(hash)
+    }
+
+    // 回放时取下一个事件，到末尾时返回 ReplayFinished
+    fn next_replay_event(&mut self) -> Result<Option<ReplayEvent>> {
+        match &mut self.input_mode {
+            InputMode::Replay { recording, pos } => {
+                let event = *recording.events.get(*pos).ok_or(ReplayFinished)?;
+                *pos += 1;
+                Ok(Some(event))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn record_event(&mut self, event: ReplayEvent) {
+        if let InputMode::Record(recording) = &mut self.input_mode {
+            recording.events.push(event);
+        }
+    }
+
+    // 回放时用录像中的输入代替窗口的输入，返回 true 表示已处理
+    pub fn replay_input(&mut self) -> Result<bool> {
+        match self.next_replay_event()? {
+            Some(ReplayEvent::Input { key_press, dir }) => {
+                self.input.key_press = key_press;
+                self.input.dir = Dir::from_u8(dir);
+                Ok(true)
+            }
+            Some(ReplayEvent::Frames(_)) => Err("recording out of sync: expected input".into()),
+            None => Ok(false),
+        }
+    }
+
+    pub fn record_input(&mut self) {
+        let event = ReplayEvent::Input { key_press: self.input.key_press, dir: self.input.dir.clone() as u8 };
+        self.record_event(event);
+    }
+
+    // 主循环这一次需要运行的逻辑帧数，回放时取自录像
+    pub fn frames_due(&mut self) -> Result<u32> {
+        match self.next_replay_event()? {
+            Some(ReplayEvent::Frames(n)) => Ok(n as u32),
+            Some(ReplayEvent::Input { .. }) => Err("recording out of sync: expected frames".into()),
+            None => {
+                let n = self.scheduler.frames_due();
+                self.record_event(ReplayEvent::Frames(n as u16));
+                Ok(n)
+            }
+        }
+    }
+}
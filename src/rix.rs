@@ -0,0 +1,458 @@
+use crate::opl::Opl;
+use crate::utils::Result;
+
+// RIX 是 DOS 版使用的 AdLib 音乐格式，保存在 MUS.MKF 中，每块一首。
+// 播放逻辑移植自 AdPlug 的 CrixPlayer，按 70 Hz 的节拍向 OPL 写寄存器
+
+// 每秒的节拍数
+pub const RIX_REFRESH_RATE: u32 = 70;
+
+// 文件头的标志 0x55aa
+const RIX_SIGNATURE: u16 = 0x55aa;
+
+const AD_FLAG: [u8; 18] = [0, 0, 0, 1, 1, 1, 0, 0, 0, 1, 1, 1, 0, 0, 0, 1, 1, 1];
+const REG_DATA: [u8; 18] = [0, 1, 2, 3, 4, 5, 8, 9, 10, 11, 12, 13, 16, 17, 18, 19, 20, 21];
+const AD_C0_OFFS: [u8; 18] = [0, 1, 2, 0, 1, 2, 3, 4, 5, 3, 4, 5, 6, 7, 8, 6, 7, 8];
+const MODIFY: [u8; 28] = [
+    0, 3, 1, 4, 2, 5, 6, 9, 7, 10, 8, 11, 12, 15, 13, 16, 14, 17, 12, 15, 16, 0, 14, 0, 17, 0, 13, 0,
+];
+const BD_REG_DATA: [u8; 11] = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x08, 0x04, 0x02, 0x01];
+
+// 通道数，包括节奏模式下的打击乐器
+const NUM_VOICES: usize = 11;
+
+pub struct Rix {
+    data: Vec<u8>,
+    pos: usize, // 当前事件的控制字节位置
+    rhythm: u8,
+    mus_block: usize,
+    ins_block: usize,
+    music_on: bool,
+    ended: bool,
+    sustain: i32, // 距离处理下一批事件的时间，每个节拍减 14
+
+    f_buffer: [u16; 300],
+    a0b0_data2: [i16; NUM_VOICES],
+    a0b0_data3: [u16; NUM_VOICES],
+    a0b0_data4: [u16; NUM_VOICES],
+    a0b0_data5: [u8; 96],
+    addrs_head: [u8; 96],
+    insbuf: [u16; 28],
+    displace: [i16; NUM_VOICES],
+    reg_bufs: [[u16; 14]; 18],
+    for40reg: [u16; 18],
+    bd_modify: u8,
+    e0_reg_flag: u8,
+}
+
+impl Rix {
+    pub fn new(data: Vec<u8>) -> Result<Self> {
+        if data.len() < 0x10 || u16::from_le_bytes([data[0], data[1]]) != RIX_SIGNATURE {
+            return Err("not a RIX file".into());
+        }
+
+        let mut rix = Self {
+            data,
+            pos: 0,
+            rhythm: 0,
+            mus_block: 0,
+            ins_block: 0,
+            music_on: false,
+            ended: false,
+            sustain: 0,
+            f_buffer: [0; 300],
+            a0b0_data2: [0; NUM_VOICES],
+            a0b0_data3: [0; NUM_VOICES],
+            a0b0_data4: [0; NUM_VOICES],
+            a0b0_data5: [0; 96],
+            addrs_head: [0; 96],
+            insbuf: [0; 28],
+            displace: [0; NUM_VOICES],
+            reg_bufs: [[0; 14]; 18],
+            for40reg: [0x7f; 18],
+            bd_modify: 0,
+            e0_reg_flag: 0,
+        };
+        rix.init_tables();
+
+        Ok(rix)
+    }
+
+    pub fn is_ended(&self) -> bool {
+        self.ended
+    }
+
+    fn byte(&self, pos: usize) -> u8 {
+        self.data.get(pos).copied().unwrap_or(0)
+    }
+
+    fn word(&self, pos: usize) -> u16 {
+        u16::from_le_bytes([self.byte(pos), self.byte(pos + 1)])
+    }
+
+    // 频率表：25 组 × 12 个半音
+    fn init_tables(&mut self) {
+        for i in 0..25u32 {
+            let mut res = (((i * 24 + 10000) * 52088) / 250000) * 0x24000 / 0x1b503;
+            self.f_buffer[(i as usize) * 12] = ((res as u16).wrapping_add(4)) >> 3;
+            for t in 1..12 {
+                res = ((res as f64) * 1.06) as u32;
+                self.f_buffer[(i as usize) * 12 + t] = ((res as u16).wrapping_add(4)) >> 3;
+            }
+        }
+
+        for k in 0..96 {
+            self.a0b0_data5[k] = (k / 12) as u8;
+            self.addrs_head[k] = (k % 12) as u8;
+        }
+    }
+
+    // 从头开始播放
+    pub fn rewind(&mut self, opl: &mut Opl) {
+        self.a0b0_data2 = [0; NUM_VOICES];
+        self.a0b0_data3 = [0; NUM_VOICES];
+        self.a0b0_data4 = [0; NUM_VOICES];
+        self.insbuf = [0; 28];
+        self.displace = [0; NUM_VOICES];
+        self.reg_bufs = [[0; 14]; 18];
+        self.for40reg = [0x7f; 18];
+        self.sustain = 0;
+        self.ended = false;
+
+        opl.reset();
+        // 允许选择波形
+        opl.write(1, 0x20);
+        self.e0_reg_flag = 0x20;
+
+        self.rhythm = self.byte(2);
+        self.mus_block = self.word(0x0c) as usize;
+        self.ins_block = self.word(0x08) as usize;
+        self.pos = self.mus_block + 1;
+        if self.rhythm != 0 {
+            self.ad_a0b0l_reg(opl, 8, 0x18, 0);
+            self.ad_a0b0l_reg(opl, 7, 0x1f, 0);
+        }
+        self.bd_modify = 0;
+        self.music_on = true;
+    }
+
+    // 推进一个节拍，乐曲结束时返回 false
+    pub fn update(&mut self, opl: &mut Opl) -> bool {
+        loop {
+            if self.sustain <= 0 {
+                let band = self.process(opl);
+                if band == 0 {
+                    self.ended = true;
+                    break;
+                }
+                self.sustain += band as i32;
+            } else {
+                self.sustain -= 14;
+                break;
+            }
+        }
+
+        !self.ended
+    }
+
+    // 处理到下一个延时事件为止，返回延时。乐曲结束时返回 0
+    fn process(&mut self, opl: &mut Opl) -> u16 {
+        if !self.music_on {
+            return 0;
+        }
+
+        while self.byte(self.pos) != 0x80 && self.pos < self.data.len() {
+            let band_low = self.byte(self.pos - 1);
+            let ctrl = self.byte(self.pos);
+            self.pos += 2;
+
+            let voice = (ctrl & 0x0f) as usize;
+            match ctrl & 0xf0 {
+                0x90 => {
+                    self.get_instrument(band_low);
+                    self.set_instrument(opl, voice);
+                }
+                0xa0 => {
+                    self.pitch_bend(opl, voice, (band_low as u16) << 6);
+                }
+                0xb0 => {
+                    self.set_volume(opl, voice, band_low as u16);
+                }
+                0xc0 => {
+                    self.note_off(opl, voice);
+                    if band_low != 0 {
+                        self.note_on(opl, voice, band_low as u16);
+                    }
+                }
+                _ => {
+                    let band = ((ctrl as u16) << 8) + (band_low as u16);
+                    if band != 0 {
+                        return band;
+                    }
+                }
+            }
+        }
+
+        // 乐曲结束，关闭所有声音
+        for voice in 0..NUM_VOICES {
+            self.note_off(opl, voice);
+        }
+        self.pos = self.mus_block + 1;
+        0
+    }
+
+    fn get_instrument(&mut self, band_low: u8) {
+        let base = self.ins_block + ((band_low as usize) << 6);
+        for i in 0..28 {
+            self.insbuf[i] = self.word(base + i * 2);
+        }
+    }
+
+    fn set_instrument(&mut self, opl: &mut Opl, voice: usize) {
+        let (op1, op2) = (self.insbuf[26], self.insbuf[27]);
+        if self.rhythm == 0 || voice < 6 {
+            if let (Some(&m1), Some(&m2)) = (MODIFY.get(voice * 2), MODIFY.get(voice * 2 + 1)) {
+                self.ins_to_reg(opl, m1 as usize, 0, op1);
+                self.ins_to_reg(opl, m2 as usize, 13, op2);
+            }
+        } else if voice > 6 {
+            if let Some(&m) = MODIFY.get(voice * 2 + 6) {
+                self.ins_to_reg(opl, m as usize, 0, op1);
+            }
+        } else {
+            self.ins_to_reg(opl, 12, 0, op1);
+            self.ins_to_reg(opl, 15, 13, op2);
+        }
+    }
+
+    fn pitch_bend(&mut self, opl: &mut Opl, voice: usize, value: u16) {
+        if (self.rhythm == 0 || voice <= 6) && voice < NUM_VOICES {
+            self.prepare_a0b0(voice, std::cmp::min(value, 0x3fff));
+            let (note, key_on) = (self.a0b0_data3[voice], self.a0b0_data4[voice]);
+            self.ad_a0b0l_reg(opl, voice, note, key_on);
+        }
+    }
+
+    fn set_volume(&mut self, opl: &mut Opl, voice: usize, value: u16) {
+        let index = if self.rhythm == 0 || voice < 6 {
+            voice * 2 + 1
+        } else if voice > 6 {
+            voice * 2 + 6
+        } else {
+            voice * 2 + 7
+        };
+        if let Some(&slot) = MODIFY.get(index) {
+            self.for40reg[slot as usize] = std::cmp::min(value, 0x7f);
+            self.ad_40_reg(opl, slot as usize);
+        }
+    }
+
+    fn note_on(&mut self, opl: &mut Opl, voice: usize, note: u16) {
+        let note = note.saturating_sub(12);
+        if voice >= NUM_VOICES {
+            return;
+        }
+
+        if voice < 6 || self.rhythm == 0 {
+            self.ad_a0b0l_reg(opl, voice, note, 1);
+            return;
+        }
+
+        if voice == 6 {
+            self.ad_a0b0l_reg(opl, voice, note, 0);
+        } else if voice == 8 {
+            self.ad_a0b0l_reg(opl, voice, note, 0);
+            self.ad_a0b0l_reg(opl, 7, note + 7, 0);
+        }
+        self.bd_modify |= BD_REG_DATA[voice];
+        self.ad_bd_reg(opl);
+    }
+
+    fn note_off(&mut self, opl: &mut Opl, voice: usize) {
+        if voice >= NUM_VOICES {
+            return;
+        }
+
+        if self.rhythm == 0 || voice < 6 {
+            let note = self.a0b0_data3[voice];
+            self.ad_a0b0l_reg(opl, voice, note, 0);
+        } else {
+            self.bd_modify &= !BD_REG_DATA[voice];
+            self.ad_bd_reg(opl);
+        }
+    }
+
+    // 按 16 位有符号运算的规则计算音高偏移，保持与原版一致
+    fn prepare_a0b0(&mut self, voice: usize, value: u16) {
+        let res1 = ((value as i32) - 0x2000) * 0x19;
+        if res1 == 0xff {
+            return;
+        }
+
+        let mut low = (res1 / 0x2000) as i16;
+        if low < 0 {
+            low = 0x18i16.wrapping_sub(low);
+            let high: u16 = if low < 0 { 0xffff } else { 0 };
+            let res = ((high as u32) << 16) + (low as u16 as u32);
+            self.a0b0_data2[voice] = (res as i16).wrapping_div(-25);
+            low = res as i16;
+            let res = (low as i32 - 0x18) as i16;
+            let high = res % 0x19;
+            low = res / 0x19;
+            if high != 0 {
+                low = 0x19 - high;
+            }
+        } else {
+            let res = low;
+            self.a0b0_data2[voice] = res / 0x19;
+            low = res % 0x19;
+        }
+        self.displace[voice] = low.wrapping_mul(0x18);
+    }
+
+    fn ad_a0b0l_reg(&mut self, opl: &mut Opl, voice: usize, note: u16, key_on: u16) {
+        if voice >= NUM_VOICES {
+            return;
+        }
+        self.a0b0_data4[voice] = key_on;
+        self.a0b0_data3[voice] = note;
+
+        let i = (note.wrapping_add(self.a0b0_data2[voice] as u16) as i16).clamp(0, 0x5f) as usize;
+        let index = (self.addrs_head[i] as isize) + (self.displace[voice] as isize) / 2;
+        let data = usize::try_from(index)
+            .ok()
+            .and_then(|index| self.f_buffer.get(index))
+            .copied()
+            .unwrap_or(0);
+
+        ad_bop(opl, 0xa0 + (voice as u16), data);
+        let data = (self.a0b0_data5[i] as u16) * 4 + (if key_on < 1 { 0 } else { 0x20 }) + ((data >> 8) & 3);
+        ad_bop(opl, 0xb0 + (voice as u16), data);
+    }
+
+    fn ins_to_reg(&mut self, opl: &mut Opl, index: usize, offset: usize, value: u16) {
+        for i in 0..13 {
+            self.reg_bufs[index][i] = self.insbuf[offset + i];
+        }
+        self.reg_bufs[index][13] = value & 3;
+
+        self.ad_bd_reg(opl);
+        ad_bop(opl, 0x08, 0);
+        self.ad_40_reg(opl, index);
+        self.ad_c0_reg(opl, index);
+        self.ad_60_reg(opl, index);
+        self.ad_80_reg(opl, index);
+        self.ad_20_reg(opl, index);
+        self.ad_e0_reg(opl, index);
+    }
+
+    fn ad_bd_reg(&self, opl: &mut Opl) {
+        let data = (if self.rhythm < 1 { 0 } else { 0x20 }) | (self.bd_modify as u16);
+        ad_bop(opl, 0xbd, data);
+    }
+
+    fn ad_20_reg(&self, opl: &mut Opl, index: usize) {
+        let v = &self.reg_bufs[index];
+        let mut data = if v[9] < 1 { 0 } else { 0x80 };
+        data += if v[10] < 1 { 0 } else { 0x40 };
+        data += if v[5] < 1 { 0 } else { 0x20 };
+        data += if v[11] < 1 { 0 } else { 0x10 };
+        data += v[1] & 0x0f;
+        ad_bop(opl, 0x20 + (REG_DATA[index] as u16), data);
+    }
+
+    fn ad_40_reg(&self, opl: &mut Opl, index: usize) {
+        let v = &self.reg_bufs[index];
+        // 按音量缩放总衰减
+        let level = (0x3f - (0x3f & v[8])) as u32;
+        let scaled = (level * (self.for40reg[index] as u32) * 2 + 0x7f) / 0xfe;
+        let data = (0x3fu32.wrapping_sub(scaled) as u16) | (v[0] << 6);
+        ad_bop(opl, 0x40 + (REG_DATA[index] as u16), data);
+    }
+
+    fn ad_60_reg(&self, opl: &mut Opl, index: usize) {
+        let v = &self.reg_bufs[index];
+        let data = (v[6] & 0x0f) | (v[3] << 4);
+        ad_bop(opl, 0x60 + (REG_DATA[index] as u16), data);
+    }
+
+    fn ad_80_reg(&self, opl: &mut Opl, index: usize) {
+        let v = &self.reg_bufs[index];
+        let data = (v[7] & 0x0f) | (v[4] << 4);
+        ad_bop(opl, 0x80 + (REG_DATA[index] as u16), data);
+    }
+
+    fn ad_c0_reg(&self, opl: &mut Opl, index: usize) {
+        if AD_FLAG[index] == 1 {
+            return;
+        }
+        let v = &self.reg_bufs[index];
+        // 与原版一样按 16 位计算，超出的位由寄存器截断
+        let data = v[2].wrapping_mul(2) | (if v[12] < 1 { 1 } else { 0 });
+        ad_bop(opl, 0xc0 + (AD_C0_OFFS[index] as u16), data);
+    }
+
+    fn ad_e0_reg(&self, opl: &mut Opl, index: usize) {
+        let data = if self.e0_reg_flag == 0 { 0 } else { self.reg_bufs[index][13] & 3 };
+        ad_bop(opl, 0xe0 + (REG_DATA[index] as u16), data);
+    }
+}
+
+// 写寄存器，忽略计时器寄存器 2 和 3
+fn ad_bop(opl: &mut Opl, reg: u16, value: u16) {
+    if reg != 2 && reg != 3 && reg <= 0xff {
+        opl.write(reg as u8, value as u8);
+    }
+}
+
+// 带有 OPL 模拟器的 RIX 播放器，输出单声道 PCM
+pub struct RixPlayer {
+    rix: Rix,
+    opl: Opl,
+    looping: bool,
+    samples_per_tick: f64,
+    tick_remain: f64, // 当前节拍还剩的采样数
+}
+
+impl RixPlayer {
+    pub fn new(data: Vec<u8>, looping: bool, sample_rate: u32) -> Result<Self> {
+        let mut rix = Rix::new(data)?;
+        let mut opl = Opl::new(sample_rate);
+        rix.rewind(&mut opl);
+
+        Ok(Self {
+            rix,
+            opl,
+            looping,
+            samples_per_tick: (sample_rate as f64) / (RIX_REFRESH_RATE as f64),
+            tick_remain: 0.0,
+        })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.rix.is_ended() && !self.looping
+    }
+
+    // 填充 out，返回实际生成的采样数。乐曲结束且不循环时少于 out 的长度
+    pub fn render(&mut self, out: &mut [i16]) -> usize {
+        let mut written = 0;
+        while written < out.len() {
+            if self.tick_remain < 1.0 {
+                if !self.rix.update(&mut self.opl) {
+                    if !self.looping {
+                        break;
+                    }
+                    self.rix.rewind(&mut self.opl);
+                }
+                self.tick_remain += self.samples_per_tick;
+            }
+
+            let n = std::cmp::min(self.tick_remain as usize, out.len() - written);
+            self.opl.render(&mut out[written..written + n]);
+            written += n;
+            self.tick_remain -= n as f64;
+        }
+
+        written
+    }
+}
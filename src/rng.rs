@@ -90,7 +90,7 @@ pub fn decode_rng(src: &[u8], dst: &mut [u8]) {
 
 impl Game {
     pub fn play_rng(&mut self, palette_id: u32, rng_id: u32) -> Result<()> {
-        self.set_palette(palette_id)?;
+        self.set_palette(palette_id, false)?;
 
         let rng_frame_count = self.mkf.rng.read_rng_sub_count(rng_id)?;
 
@@ -101,9 +101,9 @@ impl Game {
             });
 
             self.blit_to_screen()?;
-            self.process_event();
+            self.process_event()?;
 
-            std::thread::sleep(std::time::Duration::from_millis(50));
+            self.delay(50);
         }
 
         Ok(())
@@ -0,0 +1,233 @@
+use bincode::{ Decode, Encode };
+
+use crate::data::*;
+use crate::encounter::*;
+use crate::game::Game;
+use crate::utils::*;
+
+pub const MAX_SAVE_SLOTS: u16 = 5;
+
+// 夜晚调色板在 PAT.MKF 调色板块中的偏移
+const NIGHT_PALETTE_OFFSET: u16 = 0x180;
+
+/*
+typedef struct tagSAVEDGAME_DOS
+{
+   SAVEDGAME_COMMON_FIELDS
+   OBJECT_DOS       rgObject[MAX_OBJECTS];
+   EVENTOBJECT      rgEventObject[MAX_EVENT_OBJECTS];
+} SAVEDGAME_DOS;
+
+存档文件中只保存实际数量的事件对象，紧跟在 SavedGame 之后直到文件结束
+*/
+#[derive(Decode, Encode)]
+struct SavedGame {
+    saved_times: u16, // saved times
+    viewport_x: u16, // viewport location
+    viewport_y: u16,
+    max_party_member_index: u16, // number of members in party
+    scene_num: u16, // scene number
+    palette_offset: u16,
+    party_direction: u16, // party direction
+    num_music: u16, // music number
+    num_battle_music: u16, // battle music number
+    num_battle_field: u16, // battle field number
+    screen_wave: u16, // level of screen waving
+    battle_speed: u16, // battle speed
+    collect_value: u16, // value of "collected" items
+    layer: u16,
+    chase_range: u16,
+    chase_speed_change_cycles: u16,
+    follower: u16,
+    reserved: [u16; 3], // unused，前两个字保存随机数发生器的状态
+    cash: u32, // amount of cash
+    party: [PartyMember; MAX_PLAYABLE_PLAYER_ROLES], // player party
+    trail: [Trail; MAX_PLAYABLE_PLAYER_ROLES], // player trail
+    exp: AllExperience, // experience data
+    player_roles: PlayerRoles,
+    poison_status: [[PoisonStatus; MAX_PLAYABLE_PLAYER_ROLES]; MAX_POISONS], // poison status
+    inventory: [Inventory; MAX_INVENTORY], // inventory status
+    scenes: [Scene; MAX_SCENES],
+    objects: [Object; MAX_OBJECTS],
+}
+
+// 不足的部分用默认值补齐，多余的部分丢弃
+fn to_array<T: Clone + Default, const N: usize>(items: &[T]) -> [T; N] {
+    std::array::from_fn(|i| items.get(i).cloned().unwrap_or_default())
+}
+
+fn save_file_name(slot: u16) -> String {
+    format!("{}.RPG", slot)
+}
+
+impl GameState {
+    // 按 DOS 版 .RPG 的格式序列化
+    pub fn to_rpg(&self) -> Result<Vec<u8>> {
+        let saved = SavedGame {
+            saved_times: self.saved_times,
+            viewport_x: self.viewport.x as u16,
+            viewport_y: self.viewport.y as u16,
+            max_party_member_index: self.max_party_member_index,
+            scene_num: self.scene_num,
+            palette_offset: if self.night_palette { NIGHT_PALETTE_OFFSET } else { 0 },
+            party_direction: self.party_direction.clone() as u16,
+            num_music: self.num_music,
+            num_battle_music: self.num_battle_music,
+            num_battle_field: self.num_battle_field,
+            screen_wave: self.screen_wave,
+            battle_speed: self.battle_speed,
+            collect_value: self.collect_value,
+            layer: self.layer,
+            chase_range: self.chase_range,
+            chase_speed_change_cycles: self.chase_speed_change_cycles,
+            follower: self.follower,
+            reserved: [self.rng.state() as u16, (self.rng.state() >> 16) as u16, 0],
+            cash: self.cash,
+            party: to_array(&self.party),
+            trail: to_array(&self.trail),
+            exp: self.exp.clone(),
+            player_roles: self.player_roles.clone(),
+            poison_status: std::array::from_fn(|i| {
+                to_array(self.poison_status.get(i).map_or(&[], |v| v.as_slice()))
+            }),
+            inventory: to_array(&self.inventory),
+            scenes: to_array(&self.scenes),
+            objects: to_array(&self.objects),
+        };
+
+        let mut buf = encode_c_structs(&[saved])?;
+        buf.extend(encode_c_structs(&self.event_objects)?);
+        // 没有随机遇敌时与 DOS 版的存档完全相同
+        if !self.encounters.is_empty() {
+            buf.extend(encode_encounter_table(&self.encounters));
+        }
+
+        Ok(buf)
+    }
+
+    // 从 DOS 版 .RPG 的数据恢复游戏状态。存档中没有随机遇敌表时保留当前的
+    pub fn load_rpg(&mut self, buf: &[u8]) -> Result<()> {
+        let (buf, encounters) = split_encounter_table(buf)?;
+        if let Some(encounters) = encounters {
+            self.encounters = encounters;
+        }
+        self.encounter_steps = 0;
+
+        let (saved, size) = decode_c_struct::<SavedGame>(buf)?;
+
+        self.saved_times = saved.saved_times;
+        self.viewport = Pos {
+            x: (saved.viewport_x as i16) as isize,
+            y: (saved.viewport_y as i16) as isize,
+        };
+        self.max_party_member_index = saved.max_party_member_index;
+        self.scene_num = saved.scene_num;
+        self.night_palette = saved.palette_offset != 0;
+        self.party_direction = Dir::from_u8(saved.party_direction as u8);
+        self.num_music = saved.num_music;
+        self.num_battle_music = saved.num_battle_music;
+        self.num_battle_field = saved.num_battle_field;
+        self.screen_wave = saved.screen_wave;
+        self.wave_progression = 0;
+        self.battle_speed = saved.battle_speed;
+        self.collect_value = saved.collect_value;
+        self.layer = saved.layer;
+        self.chase_range = saved.chase_range;
+        self.chase_speed_change_cycles = saved.chase_speed_change_cycles;
+        self.follower = saved.follower;
+        // 原版存档中为 0，此时保留当前的随机数状态
+        let rng_state = (saved.reserved[0] as u32) | ((saved.reserved[1] as u32) << 16);
+        if rng_state != 0 {
+            self.rng.set_state(rng_state);
+        }
+        self.cash = saved.cash;
+        self.party = saved.party.to_vec();
+        self.trail = saved.trail.to_vec();
+        self.exp = saved.exp;
+        self.player_roles = saved.player_roles;
+        self.poison_status = saved.poison_status
+            .iter()
+            .map(|v| v.to_vec())
+            .collect();
+        self.inventory = saved.inventory.to_vec();
+        // 存档只有前 MAX_SCENES 个场景，保留之后 DATA.MKF 中用来确定最后一个场景事件对象范围的一项
+        let count = MAX_SCENES.min(self.scenes.len());
+        self.scenes.splice(..count, saved.scenes.iter().cloned());
+        self.objects = saved.objects.to_vec();
+
+        let event_objects = &buf[size..];
+        self.event_objects = if event_objects.is_empty() {
+            Vec::new()
+        } else {
+            decode_c_structs::<EventObject>(event_objects)?
+        };
+
+        Ok(())
+    }
+}
+
+impl Game {
+    // 存档的保存次数，存档不存在时为 0
+    pub fn get_saved_times(&self, slot: u16) -> u16 {
+        let buf = match std::fs::read(file_path(&save_file_name(slot))) {
+            Ok(buf) => buf,
+            Err(_) => {
+                return 0;
+            }
+        };
+
+        if buf.len() < 2 {
+            return 0;
+        }
+        u16::from_le_bytes([buf[0], buf[1]])
+    }
+
+    // 重新开始新游戏
+    pub fn new_game(&mut self) -> Result<()> {
+        // 随机数发生器不属于初始数据，新游戏时保留
+        let rng = self.state.rng;
+        self.state = GameState::load_new_game(&mut self.mkf.sss, &self.data)?;
+        self.state.rng = rng;
+        self.update_equipments()?;
+        self.current_save_slot = 0;
+        self.state.need_to_fade_in = true;
+
+        Ok(())
+    }
+
+    pub fn load_game(&mut self, slot: u16) -> Result<()> {
+        let buf = std::fs::read(file_path(&save_file_name(slot)))?;
+        self.load_state(&buf)?;
+        self.current_save_slot = slot;
+
+        Ok(())
+    }
+
+    // 从存档数据恢复游戏状态，下一帧重新载入场景
+    pub fn load_state(&mut self, buf: &[u8]) -> Result<()> {
+        // 原版存档中没有随机遇敌表，使用数据目录中的
+        self.state.encounters = self.data.encounters.clone();
+        self.state.load_rpg(buf)?;
+        self.update_equipments()?;
+
+        self.play_music(self.state.num_music, true, 1.0)?;
+        self.state.entering_scene = false;
+        self.state.need_load_scene = true;
+        self.state.need_to_fade_in = true;
+
+        Ok(())
+    }
+
+    pub fn save_game(&mut self, slot: u16) -> Result<()> {
+        let saved_times = (1..=MAX_SAVE_SLOTS)
+            .map(|i| self.get_saved_times(i))
+            .max()
+            .unwrap_or(0);
+
+        self.state.saved_times = saved_times + 1;
+        std::fs::write(file_path(&save_file_name(slot)), self.state.to_rpg()?)?;
+        self.current_save_slot = slot;
+
+        Ok(())
+    }
+}
@@ -1,7 +1,10 @@
+use std::ops::Range;
+
 use crate::data::{ GameState, ObjectState };
 use crate::game::Game;
 use crate::play::Resource;
 use crate::sprite::{ draw_sprite_frame, sprite_get_frames };
+use crate::ui::TextSurface;
 use crate::utils::{ Rect, Result };
 use crate::{ mkf::MKF, sprite::SpriteFrame };
 
@@ -62,6 +65,26 @@ impl Map {
     }
 }
 
+impl GameState {
+    // 当前场景的事件对象在 event_objects 中的范围，结尾由下一个场景的起始位置确定
+    pub fn scene_event_objects(&self) -> Range<usize> {
+        let i = (self.scene_num as usize) - 1;
+        (self.scenes[i].event_object_index as usize)..(self.scenes[i + 1].event_object_index as usize)
+    }
+
+    // 切换到指定场景，下一帧再加载。场景编号无效或已在该场景中时返回 false
+    pub fn switch_scene(&mut self, scene_num: u16) -> bool {
+        if scene_num == 0 || (scene_num as usize) >= self.scenes.len() || self.scene_num == scene_num {
+            return false;
+        }
+        self.scene_num = scene_num;
+        self.need_load_scene = true;
+        self.entering_scene = true;
+        self.layer = 0;
+        true
+    }
+}
+
 impl Game {
     pub fn draw_map(pixels: &mut [u8], map: &Map, rect: &Rect, layer: usize) {
         let sy = rect.y / 16 - 1;
@@ -129,16 +152,17 @@ impl Game {
     }
 
     pub fn make_scence(&mut self) {
+        self.canvas.effects.wave.level = self.state.screen_wave;
+
         let resource = self.resource.as_ref().unwrap();
         let viewport = &self.state.viewport;
 
         let map = &resource.map;
         let rect = Rect { x: viewport.x, y: viewport.y, w: 320, h: 200 };
 
-        let event_objects_from = (self.state.scene_num as usize) - 1;
-        let event_objects_count =
-            self.state.scenes[event_objects_from + 1].event_object_index -
-            self.state.scenes[event_objects_from].event_object_index;
+        let event_objects = self.state.scene_event_objects();
+        let event_objects_from = event_objects.start;
+        let event_objects_count = event_objects.len();
 
         self.canvas.set_pixels(|pixels: &mut [u8]| {
             Self::draw_map(pixels, &map, &rect, 0);
@@ -149,13 +173,12 @@ impl Game {
                 &self.state,
                 &rect,
                 event_objects_from,
-                event_objects_count as usize
+                event_objects_count
             );
+            let mut surface = TextSurface { pixels, width: 320, height: 200 };
             Self::draw_text(
                 &self.ui,
-                pixels,
-                320,
-                200,
+                &mut surface,
                 0,
                 0,
                 format!("場景{}", self.state.scene_num).as_str(),
@@ -164,9 +187,7 @@ impl Game {
             );
             Self::draw_text(
                 &self.ui,
-                pixels,
-                320,
-                200,
+                &mut surface,
                 0,
                 18,
                 format!("坐標{}，{}", self.state.viewport.x, self.state.viewport.y).as_str(),
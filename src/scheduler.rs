@@ -0,0 +1,132 @@
+use std::time::{ Duration, Instant };
+
+// 游戏逻辑帧的时长，毫秒（10 fps）
+pub const FRAME_TIME: u32 = 100;
+
+// 快进时游戏时间相对真实时间的倍数
+const FAST_FORWARD_SCALE: u32 = 4;
+
+// 渲染过慢时最多追赶的逻辑帧数，超出的时间直接丢弃
+const MAX_CATCH_UP_FRAMES: u32 = 5;
+
+// 游戏时间的调度器。游戏逻辑只使用这里的游戏时间，真实时间只用于控制节奏
+pub struct Scheduler {
+    frame_time: u32,
+    game_time: u32, // 已经过的游戏时间，毫秒
+    frame_count: u32, // 已运行的逻辑帧数
+    budget: u32, // 真实时间已经过去、游戏时间还未推进的毫秒数
+    last_sync: Instant,
+    fast_forward: bool,
+    stepped: bool, // 不等待真实时间，每次循环正好推进一帧
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            frame_time: FRAME_TIME,
+            game_time: 0,
+            frame_count: 0,
+            budget: 0,
+            last_sync: Instant::now(),
+            fast_forward: false,
+            stepped: false,
+        }
+    }
+
+    // 确定性的调度器，用于测试和回放
+    pub fn stepped() -> Self {
+        Self { stepped: true, ..Self::new() }
+    }
+
+    pub fn is_stepped(&self) -> bool {
+        self.stepped
+    }
+
+    // 游戏时间，毫秒
+    pub fn ticks(&self) -> u32 {
+        self.game_time
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    pub fn frame_time(&self) -> u32 {
+        self.frame_time
+    }
+
+    pub fn is_fast_forward(&self) -> bool {
+        self.fast_forward
+    }
+
+    pub fn toggle_fast_forward(&mut self) {
+        self.sync();
+        self.fast_forward = !self.fast_forward;
+    }
+
+    fn scale(&self) -> u32 {
+        if self.fast_forward { FAST_FORWARD_SCALE } else { 1 }
+    }
+
+    // 把流逝的真实时间计入预算
+    fn sync(&mut self) {
+        let now = Instant::now();
+        let elapsed = (now - self.last_sync).as_millis() as u32;
+        self.last_sync = now;
+
+        let budget = self.budget.saturating_add(elapsed.saturating_mul(self.scale()));
+        self.budget = std::cmp::min(budget, self.frame_time * MAX_CATCH_UP_FRAMES);
+    }
+
+    // 等待真实时间，直到预算足够 ms 毫秒
+    fn wait_for(&mut self, ms: u32) {
+        self.sync();
+        if self.budget < ms {
+            let wait = (ms - self.budget) / self.scale();
+            std::thread::sleep(Duration::from_millis(wait as u64));
+            self.sync();
+        }
+    }
+
+    // 主循环中调用，返回现在需要运行的逻辑帧数
+    pub fn frames_due(&mut self) -> u32 {
+        if self.stepped {
+            return 1;
+        }
+
+        self.sync();
+        let frames = self.budget / self.frame_time;
+        self.budget -= frames * self.frame_time;
+
+        frames
+    }
+
+    // 每运行完一个逻辑帧调用一次
+    pub fn end_frame(&mut self) {
+        self.game_time = self.game_time.wrapping_add(self.frame_time);
+        self.frame_count = self.frame_count.wrapping_add(1);
+    }
+
+    // 主循环在两次渲染之间等待下一个逻辑帧
+    pub fn wait_next_frame(&mut self) {
+        if !self.stepped {
+            self.wait_for(self.frame_time);
+        }
+    }
+
+    // 阻塞 ms 毫秒游戏时间，用于菜单、动画等画面。渲染耗费的时间会被扣除
+    pub fn delay(&mut self, ms: u32) {
+        if !self.stepped {
+            self.wait_for(ms);
+            self.budget = self.budget.saturating_sub(ms);
+        }
+
+        self.game_time = self.game_time.wrapping_add(ms);
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,8 +1,21 @@
+use crate::battle::{ Actor, BattleResult };
+use crate::data::{ MAX_PLAYER_EQUIPMENTS, MAX_PLAYER_ROLES };
 use crate::game::Game;
+use crate::party::{ ATTR_ATTACK_STRENGTH, ATTR_EQUIPMENT, ATTR_POISON_RESISTANCE, BODY_PART_EXTRA };
+use crate::utils::{ Pos, Result };
+
+// 操作数为 0 时表示当前角色（event_object_id），否则为角色编号 + 1
+fn script_player_role(operand: u16, event_object_id: u16) -> usize {
+    if operand == 0 { event_object_id as usize } else { (operand as usize) - 1 }
+}
 
 impl Game {
-    pub fn interpret_instruction(&mut self, script_entry: u16, event_object_id: u16) -> u16 {
-        let script = &self.data.script_entries[script_entry as usize];
+    pub fn interpret_instruction(
+        &mut self,
+        script_entry: u16,
+        event_object_id: u16
+    ) -> Result<u16> {
+        let script = self.data.script_entries[script_entry as usize];
         match script.operation {
             // 角色朝某个方向走一步
             0x000b | 0x000c | 0x000d | 0x000e => {}
@@ -23,75 +36,338 @@ impl Game {
             // 设置事件对象的方向和动作
             0x0016 => {}
             // 设置玩家的额外属性
-            0x0017 => {}
+            0x0017 => {
+                // event_object_id 此时为角色编号
+                let part = (script.operands[0] as usize).wrapping_sub(ATTR_EQUIPMENT);
+                if let Some(effect) = self.state.party_state.equipment_effect.get_mut(part) {
+                    effect.set_attribute(
+                        script.operands[1] as usize,
+                        event_object_id as usize,
+                        script.operands[2]
+                    );
+                }
+            }
             // 装备选中的物品
-            0x0018 => {}
+            0x0018 => {
+                let part = (script.operands[0] as usize).wrapping_sub(ATTR_EQUIPMENT);
+                let role = event_object_id as usize;
+                if part < MAX_PLAYER_EQUIPMENTS && role < MAX_PLAYER_ROLES {
+                    self.state.party_state.cur_equip_part = Some(part);
+                    self.state.remove_equipment_effect(role, part);
+
+                    let item = script.operands[1];
+                    let old_item = self.state.player_roles.equipment[part][role];
+                    if old_item != item {
+                        self.state.player_roles.equipment[part][role] = item;
+                        self.state.add_item_to_inventory(item, -1);
+                        if old_item != 0 {
+                            self.state.add_item_to_inventory(old_item, 1);
+                        }
+                        self.state.party_state.last_unequipped_item = old_item;
+                    }
+                }
+            }
             // 增加/减少玩家的属性
-            0x0019 => {}
+            0x0019 => {
+                let role = script_player_role(script.operands[2], event_object_id);
+                let attribute = script.operands[0] as usize;
+                let roles = &mut self.state.player_roles;
+                let value = roles.attribute(attribute, role).wrapping_add(script.operands[1]);
+                roles.set_attribute(attribute, role, value);
+            }
             // 设置玩家的属性值
-            0x001a => {}
-            // 增加/减少玩家的 HP
-            0x001b => {}
-            // 增加/减少玩家的 MP
-            0x001c => {}
-            // 增加/减少玩家的 HP 和 MP
-            0x001d => {}
+            0x001a => {
+                // 执行装备脚本时设置的是装备效果
+                let roles = match self.state.party_state.cur_equip_part {
+                    Some(part) => &mut self.state.party_state.equipment_effect[part],
+                    None => &mut self.state.player_roles,
+                };
+                roles.set_attribute(
+                    script.operands[0] as usize,
+                    script_player_role(script.operands[2], event_object_id),
+                    script.operands[1]
+                );
+            }
+            // 增加/减少玩家的 HP、MP 或 HP 和 MP
+            0x001b..=0x001d => {
+                let value = script.operands[1] as i16;
+                let (hp, mp) = match script.operation {
+                    0x001b => (value, 0),
+                    0x001c => (0, value),
+                    _ => (value, value),
+                };
+
+                if script.operands[0] != 0 {
+                    for role in self.state.party_roles() {
+                        self.state.increase_hp_mp(role, hp, mp);
+                    }
+                } else if
+                    (event_object_id as usize) >= MAX_PLAYER_ROLES ||
+                    !self.state.increase_hp_mp(event_object_id as usize, hp, mp)
+                {
+                    self.script_success = false;
+                }
+            }
             // 增加或减少指定数量的金钱
             0x001e => {}
             // 添加物品到库存
-            0x001f => {}
+            0x001f => {
+                self.state.add_item_to_inventory(script.operands[0], script.operands[1] as i16);
+            }
             // 从库存中移除物品
-            0x0020 => {}
+            0x0020 => {
+                let object_id = script.operands[0];
+                let num = if script.operands[1] == 0 { 1 } else { script.operands[1] };
+                let amount = self.state.item_amount(object_id);
+                // 库存中的数量不够且有跳转地址时跳转，不移除任何物品
+                if num > amount && script.operands[2] != 0 {
+                    return Ok(script.operands[2]);
+                }
+                // 没有跳转地址时先从库存中移除，不够的再从装备上移除
+                let from_inventory = num.min(amount);
+                if from_inventory > 0 {
+                    self.state.add_item_to_inventory(object_id, -(from_inventory as i16));
+                }
+                self.state.remove_equipped_item(object_id, num - from_inventory);
+            }
             // 对敌人造成伤害
-            0x0021 => {}
+            0x0021 => {
+                for index in self.battle_script_targets(script.operands[0], event_object_id) {
+                    self.battle_damage_enemy(index, script.operands[1] as i32)?;
+                }
+            }
             // 复活玩家
-            0x0022 => {}
+            0x0022 => {
+                let roles = if script.operands[0] != 0 {
+                    self.state.party_roles()
+                } else {
+                    vec![event_object_id as usize]
+                };
+
+                self.script_success = false;
+                for role in roles.into_iter().filter(|&role| role < MAX_PLAYER_ROLES) {
+                    if self.state.player_roles.hp[role] == 0 {
+                        let max_hp = self.state.player_roles.max_hp[role] as u32;
+                        self.state.player_roles.hp[role] = (max_hp * (script.operands[1] as u32) / 10) as u16;
+                        self.state.cure_poison_by_level(role, 3);
+                        self.state.clear_player_status(role);
+                        self.script_success = true;
+                    }
+                }
+            }
             // 从指定玩家身上移除装备
-            0x0023 => {}
+            0x0023 => {
+                let role = script.operands[0] as usize;
+                let parts = if script.operands[1] == 0 {
+                    0..MAX_PLAYER_EQUIPMENTS
+                } else {
+                    let part = (script.operands[1] as usize) - 1;
+                    part..part + 1
+                };
+
+                if role < MAX_PLAYER_ROLES && parts.end <= MAX_PLAYER_EQUIPMENTS {
+                    for part in parts {
+                        let item = self.state.player_roles.equipment[part][role];
+                        if item != 0 {
+                            self.state.add_item_to_inventory(item, 1);
+                            self.state.player_roles.equipment[part][role] = 0;
+                        }
+                        self.state.remove_equipment_effect(role, part);
+                    }
+                }
+            }
             // 为事件对象设置自动脚本入口地址
             0x0024 => {}
             // 为事件对象设置触发脚本入口地址
             0x0025 => {}
             // 显示购买物品菜单
-            0x0026 => {}
+            0x0026 => {
+                self.make_scence();
+                self.blit_to_screen()?;
+                self.buy_menu(script.operands[0])?;
+            }
             // 显示出售物品菜单
-            0x0027 => {}
+            0x0027 => {
+                self.make_scence();
+                self.blit_to_screen()?;
+                self.sell_menu()?;
+            }
             // 对敌人施加毒药
-            0x0028 => {}
+            0x0028 => {
+                let poison_id = script.operands[1];
+                let enemy_script = self.state.object_poison(poison_id).map_or(0, |poison| poison.enemy_script);
+                let mut success = false;
+                for index in self.battle_script_targets(script.operands[0], event_object_id) {
+                    // 敌人的巫抗越高越不容易中毒
+                    if self.state.rng.random_long(0, 9) < (self.battle_enemy_resistance(index) as i32) {
+                        continue;
+                    }
+                    if !self.battle_enemy(index as u16).is_some_and(|enemy| enemy.can_add_poison(poison_id)) {
+                        continue;
+                    }
+                    // 中毒时先运行一次毒的脚本，保存其返回的入口供每回合运行
+                    let poison_script = self.run_trigger_script(enemy_script, index as u16)?;
+                    if let Some(enemy) = self.battle_enemy_mut(index as u16) {
+                        success |= enemy.add_poison(poison_id, poison_script);
+                    }
+                }
+                if !success && script.operands[0] == 0 && script.operands[2] != 0 {
+                    return Ok(script.operands[2]);
+                }
+            }
             // 对玩家施加毒药
-            0x0029 => {}
+            0x0029 => {
+                let roles = if script.operands[0] != 0 {
+                    self.state.party_roles()
+                } else {
+                    vec![event_object_id as usize]
+                };
+                for role in roles.into_iter().filter(|&role| role < MAX_PLAYER_ROLES) {
+                    let resistance = self.state.player_stat(ATTR_POISON_RESISTANCE, role);
+                    if self.state.rng.random_long(1, 100) > (resistance as i32) {
+                        self.state.add_poison_for_player(role, script.operands[1]);
+                    }
+                }
+            }
             // 为敌人解除特定类型的毒药
-            0x002a => {}
+            0x002a => {
+                for index in self.battle_script_targets(script.operands[0], event_object_id) {
+                    if let Some(enemy) = self.battle_enemy_mut(index as u16) {
+                        enemy.remove_poison(script.operands[1]);
+                    }
+                }
+            }
             // 为玩家解除特定类型的毒药
-            0x002b => {}
+            0x002b => {
+                if script.operands[0] != 0 {
+                    for role in self.state.party_roles() {
+                        self.state.cure_poison_by_kind(role, script.operands[1]);
+                    }
+                } else {
+                    self.state.cure_poison_by_kind(event_object_id as usize, script.operands[1]);
+                }
+            }
             // 通过等级为玩家解除毒药
-            0x002c => {}
+            0x002c => {
+                if script.operands[0] != 0 {
+                    for role in self.state.party_roles() {
+                        self.state.cure_poison_by_level(role, script.operands[1]);
+                    }
+                } else {
+                    self.state.cure_poison_by_level(event_object_id as usize, script.operands[1]);
+                }
+            }
             // 设置玩家状态
-            0x002d => {}
+            0x002d if (event_object_id as usize) < MAX_PLAYER_ROLES => {
+                self.state.set_player_status(
+                    event_object_id as usize,
+                    script.operands[0],
+                    script.operands[1]
+                );
+            }
             // 设置敌人状态
-            0x002e => {}
+            0x002e => {
+                let index = event_object_id as usize;
+                let status = script.operands[0] as usize;
+                let resisted = self.state.rng.random_long(0, 9) < (self.battle_enemy_resistance(index) as i32);
+                match self.battle_enemy_mut(event_object_id) {
+                    Some(enemy) if !resisted && enemy.is_alive() && enemy.status.get(status) == Some(&0) => {
+                        enemy.status[status] = script.operands[1];
+                    }
+                    _ => {
+                        return Ok(script.operands[2]);
+                    }
+                }
+            }
             // 移除玩家状态
-            0x002f => {}
+            0x002f if (event_object_id as usize) < MAX_PLAYER_ROLES => {
+                self.state.remove_player_status(event_object_id as usize, script.operands[0]);
+            }
             // 暂时增加玩家的属性值
-            0x0030 => {}
+            0x0030 => {
+                let role = script_player_role(script.operands[2], event_object_id);
+                let attribute = script.operands[0] as usize;
+                let value = self.state.player_roles.attribute(attribute, role) as i32;
+                let value = (value * (script.operands[1] as i16 as i32)) / 100;
+                self.state.party_state.equipment_effect[BODY_PART_EXTRA].set_attribute(
+                    attribute,
+                    role,
+                    value as u16
+                );
+            }
             // 临时改变玩家的战斗精灵
             0x0031 => {}
             // 收集敌人的物品
-            0x0033 => {}
+            0x0033 => {
+                match self.battle_enemy(event_object_id).map(|enemy| enemy.enemy.collect_value) {
+                    Some(value) if value != 0 => {
+                        self.state.collect_value = self.state.collect_value.saturating_add(value);
+                    }
+                    _ => {
+                        return Ok(script.operands[0]);
+                    }
+                }
+            }
             // 将收集的敌人转化为物品
-            0x0034 => {}
+            0x0034 if self.state.collect_value > 0 => {
+                // 炼成的物品最多为第 9 种
+                let i = std::cmp::min(self.state.rng.random_long(1, self.state.collect_value as i32), 9) as u16;
+                self.state.collect_value -= i;
+                if let Some(store) = self.data.store(0) {
+                    self.state.add_item_to_inventory(store.items[(i as usize) - 1], 1);
+                }
+            }
+            0x0034 => {
+                return Ok(script.operands[0]);
+            }
             // 震动屏幕
-            0x0035 => {}
+            0x0035 => {
+                let level = if script.operands[1] == 0 { 4 } else { script.operands[1] };
+                self.shake_screen(script.operands[0], level);
+                if script.operands[0] == 0 {
+                    self.blit_to_screen()?;
+                }
+            }
             // 设置当前播放的 RNG 动画
             0x0036 => {}
             // 播放 RNG 动画
             0x0037 => {}
             // 将队伍传送出场景
-            0x0038 => {}
+            0x0038 => {
+                let i = (self.state.scene_num as usize) - 1;
+                if self.state.scenes[i].script_on_teleport != 0 {
+                    self.state.scenes[i].script_on_teleport = self.run_trigger_script(
+                        self.state.scenes[i].script_on_teleport,
+                        0xffff
+                    )?;
+                } else {
+                    // 失败
+                    self.script_success = false;
+                    return Ok(script.operands[0]);
+                }
+            }
             // 从敌人处吸取 HP
-            0x0039 => {}
+            0x0039 => {
+                let drained = match self.battle_enemy_mut(event_object_id) {
+                    Some(enemy) if enemy.is_alive() => {
+                        let drained = std::cmp::min(enemy.enemy.health, script.operands[0]);
+                        enemy.enemy.health -= drained;
+                        drained
+                    }
+                    _ => 0,
+                };
+                if let Some(role) = self.battle_moving_player_role() {
+                    self.state.increase_hp_mp(role, std::cmp::min(drained, i16::MAX as u16) as i16, 0);
+                }
+            }
             // 玩家从战斗中逃跑
-            0x003a => {}
+            0x003a if self.battle.is_some() => {
+                if self.battle_ref().is_boss {
+                    // 不能从头目战中逃跑
+                    return Ok(script.operands[0]);
+                }
+                self.battle_mut().result = Some(BattleResult::Fled);
+            }
             // 以低速将队伍骑在事件对象上移动到指定位置
             0x003f => {}
             // 为事件对象设置触发方式
@@ -101,19 +377,36 @@ impl Game {
             // 模拟玩家施法
             0x0042 => {}
             // 设置背景音乐
-            0x0043 => {}
+            0x0043 => {
+                // 0x3d 是结局音乐，只播放一次
+                self.state.num_music = script.operands[0];
+                self.play_music(script.operands[0], script.operands[0] != 0x3d, script.operands[1] as f32)?;
+            }
             // 以正常速度将队伍骑在事件对象上移动到指定位置
             0x0044 => {}
             // 设置战斗音乐
-            0x0045 => {}
+            0x0045 => {
+                self.state.num_battle_music = script.operands[0];
+            }
             // 设置队伍在地图上的位置
-            0x0046 => {}
+            0x0046 => {
+                let x = (script.operands[0] as isize) * 32 + (script.operands[2] as isize) * 16;
+                let y = (script.operands[1] as isize) * 16 + (script.operands[2] as isize) * 8;
+                self.state.viewport = Pos {
+                    x: x - self.state.party_offset.x,
+                    y: y - self.state.party_offset.y,
+                };
+            }
             // 播放音效
-            0x0047 => {}
+            0x0047 => {
+                self.play_sound(script.operands[0] as i16)?;
+            }
             // 设置事件对象的状态
             0x0049 => {}
             // 设置当前的战场
-            0x004a => {}
+            0x004a => {
+                self.state.num_battle_field = script.operands[0];
+            }
             // 短暂消失事件对象
             0x004b => {}
             // 追击玩家
@@ -121,69 +414,182 @@ impl Game {
             // 等待任何按键
             0x004d => {}
             // 读取上次保存的游戏
-            0x004e => {}
+            0x004e => {
+                // 在下一个逻辑帧开始时读档，当前脚本就此结束
+                self.fade_out(1)?;
+                self.pending_load = Some(self.current_save_slot);
+                return Ok(0);
+            }
             // 屏幕渐变为红色（游戏结束）
-            0x004f => {}
+            0x004f => {
+                self.fade_to_red()?;
+            }
             // 屏幕渐暗
-            0x0050 => {}
+            0x0050 => {
+                self.blit_to_screen()?;
+                self.fade_out(if script.operands[0] != 0 { script.operands[0] as u32 } else { 1 })?;
+                self.state.need_to_fade_in = true;
+            }
             // 屏幕渐亮
-            0x0051 => {}
+            0x0051 => {
+                let delay = script.operands[0] as i16;
+                self.blit_to_screen()?;
+                self.fade_in(if delay > 0 { delay as u32 } else { 1 })?;
+                self.state.need_to_fade_in = false;
+            }
             // 短暂隐藏事件对象
             0x0052 => {}
             // 使用白天调色板
-            0x0053 => {}
+            0x0053 => {
+                self.state.night_palette = false;
+                if !self.state.need_to_fade_in {
+                    self.set_palette(self.state.num_palette as u32, false)?;
+                }
+            }
             // 使用夜晚调色板
-            0x0054 => {}
+            0x0054 => {
+                self.state.night_palette = true;
+                if !self.state.need_to_fade_in {
+                    self.set_palette(self.state.num_palette as u32, true)?;
+                }
+            }
             // 为玩家添加魔法
-            0x0055 => {}
+            0x0055 => {
+                let role = script_player_role(script.operands[1], event_object_id);
+                if role < MAX_PLAYER_ROLES {
+                    self.state.add_magic(role, script.operands[0]);
+                }
+            }
             // 从玩家身上移除魔法
-            0x0056 => {}
+            0x0056 => {
+                let role = script_player_role(script.operands[1], event_object_id);
+                if role < MAX_PLAYER_ROLES {
+                    self.state.remove_magic(role, script.operands[0]);
+                }
+            }
             // 根据 MP 值设置魔法的基础伤害
             0x0057 => {}
             // 如果库存中物品数量少于指定数量，则跳转
-            0x0058 => {}
+            0x0058 if
+                (self.state.item_amount(script.operands[0]) as i32) < (script.operands[1] as i16 as i32)
+            => {
+                return Ok(script.operands[2]);
+            }
             // 切换到指定场景
-            0x0059 => {}
+            0x0059 => {
+                // 下一帧再加载新场景
+                self.state.switch_scene(script.operands[0]);
+            }
             // 将玩家的 HP 减半
-            0x005a => {}
+            0x005a => {
+                if let Some(hp) = self.state.player_roles.hp.get_mut(event_object_id as usize) {
+                    *hp /= 2;
+                }
+            }
             // 将敌人的 HP 减半
-            0x005b => {}
+            0x005b => {
+                if let Some(enemy) = self.battle_enemy_mut(event_object_id) {
+                    let health = enemy.enemy.health;
+                    enemy.enemy.health -= std::cmp::min(health / 2 + 1, std::cmp::min(script.operands[0], health));
+                }
+            }
             // 隐藏一段时间
             0x005c => {}
             // 如果玩家没有中指定的毒，则跳转
             0x005d => {}
             // 如果敌人没有中指定的毒，则跳转
-            0x005e => {}
+            0x005e if !self.battle_enemy(event_object_id).is_some_and(|enemy| enemy.has_poison(script.operands[0])) => {
+                return Ok(script.operands[1]);
+            }
             // 立即杀死玩家
-            0x005f => {}
+            0x005f => {
+                if let Some(hp) = self.state.player_roles.hp.get_mut(event_object_id as usize) {
+                    *hp = 0;
+                }
+            }
             // 立即击败敌人
-            0x0060 => {}
+            0x0060 => {
+                if let Some(enemy) = self.battle_enemy_mut(event_object_id) {
+                    enemy.enemy.health = 0;
+                }
+            }
             // 如果玩家没有中毒，则跳转
             0x0061 => {}
             // 暂停敌人的追击一段时间
-            0x0062 => {}
+            0x0062 => {
+                self.state.chase_range = 0;
+                self.state.chase_speed_change_cycles = script.operands[0];
+            }
             // 加速敌人的追击一段时间
-            0x0063 => {}
+            0x0063 => {
+                self.state.chase_range = 3;
+                self.state.chase_speed_change_cycles = script.operands[0];
+            }
             // 如果敌人的 HP 高于指定百分比，则跳转
-            0x0064 => {}
+            0x0064 if self.battle_enemy(event_object_id).is_some_and(|enemy| {
+                (enemy.enemy.health as u32) * 100 >
+                    (self.battle_enemy_max_health(event_object_id as usize) as u32) * (script.operands[0] as u32)
+            }) => {
+                return Ok(script.operands[1]);
+            }
             // 设置玩家的精灵
-            0x0065 => {}
+            0x0065 => {
+                if let Some(sprite_num) = self.state.player_roles.sprite_num.get_mut(script.operands[0] as usize) {
+                    *sprite_num = script.operands[1];
+                    if script.operands[2] != 0 && self.resource.is_some() {
+                        self.load_player_sprites()?;
+                    }
+                }
+            }
             // 向敌人投掷武器
-            0x0066 => {}
+            0x0066 if self.battle.is_some() => {
+                // 伤害为武器基础伤害加上投掷者攻击力的随机倍数
+                let attack = self.battle_moving_player_role()
+                    .map_or(0, |role| self.state.player_stat(ATTR_ATTACK_STRENGTH, role));
+                let strength = (script.operands[1] as i32) * 5 + (((attack as f32) * self.state.rng.random_float(0.0, 4.0)) as i32);
+                self.battle_simulate_magic(event_object_id, script.operands[0], strength)?;
+            }
             // 敌人使用魔法
-            0x0067 => {}
+            0x0067 => {
+                if let Some(enemy) = self.battle_enemy_mut(event_object_id) {
+                    enemy.enemy.magic = script.operands[0];
+                    enemy.enemy.magic_rate = if script.operands[1] == 0 { 10 } else { script.operands[1] };
+                }
+            }
             // 如果是敌人的回合，则跳转
-            0x0068 => {}
+            0x0068 if matches!(self.battle.as_ref().and_then(|battle| battle.current_actor), Some(Actor::Enemy(_))) => {
+                return Ok(script.operands[0]);
+            }
             // 敌人在战斗中逃跑
-            0x0069 => {}
+            0x0069 => {
+                self.battle_enemy_escape();
+            }
             // 从敌人处偷窃
-            0x006a => {}
+            0x006a => {
+                self.battle_steal(event_object_id as usize, script.operands[0]);
+            }
             // 击退敌人
-            0x006b => {}
+            0x006b => {
+                if let Some(battle) = self.battle.as_mut() {
+                    battle.blow = script.operands[0] as i16;
+                }
+            }
             // NPC 移动一步
             0x006c => {}
             // 为场景设置进入脚本和传送脚本
-            0x006d => {}
+            0x006d if script.operands[0] != 0 => {
+                let scene = &mut self.state.scenes[(script.operands[0] as usize) - 1];
+                if script.operands[1] != 0 {
+                    scene.script_on_enter = script.operands[1];
+                }
+                if script.operands[2] != 0 {
+                    scene.script_on_teleport = script.operands[2];
+                }
+                if script.operands[1] == 0 && script.operands[2] == 0 {
+                    scene.script_on_enter = 0;
+                    scene.script_on_teleport = 0;
+                }
+            }
             // 将玩家移动到指定位置
             0x006e => {}
             // 将当前事件对象的状态与另一个事件对象同步
@@ -191,9 +597,16 @@ impl Game {
             // 将队伍移动到指定位置
             0x0070 => {}
             // 屏幕波动效果
-            0x0071 => {}
+            0x0071 => {
+                self.state.screen_wave = script.operands[0];
+                self.state.wave_progression = script.operands[1] as i16;
+            }
             // 场景渐变
-            0x0073 => {}
+            0x0073 => {
+                self.canvas.backup_screen();
+                self.make_scence();
+                self.fade_screen(script.operands[0])?;
+            }
             // 如果不是所有玩家的 HP 满值，则跳转
             0x0074 => {}
             // 设置玩家队伍
@@ -201,7 +614,11 @@ impl Game {
             // 显示 FBP 图片
             0x0076 => {}
             // 停止当前播放的音乐
-            0x0077 => {}
+            0x0077 => {
+                let fade = if script.operands[0] == 0 { 2.0 } else { script.operands[0] as f32 };
+                self.play_music(0, false, fade)?;
+                self.state.num_music = 0;
+            }
             // 未定义操作
             0x0078 => {}
             // 如果指定玩家在队伍中，则跳转
@@ -219,7 +636,10 @@ impl Game {
             // 移动视口
             0x007f => {}
             // 切换日夜调色板
-            0x0080 => {}
+            0x0080 => {
+                self.state.night_palette = !self.state.night_palette;
+                self.day_night_fade(self.state.night_palette, script.operands[0] == 0)?;
+            }
             // 如果玩家未面向指定事件对象，则跳转
             0x0081 => {}
             // 高速走向指定位置
@@ -231,31 +651,74 @@ impl Game {
             // 延迟一段时间
             0x0085 => {}
             // 如果指定物品未装备，则跳转
-            0x0086 => {}
+            0x0086 if !self.state.is_item_equipped(script.operands[0]) => {
+                return Ok(script.operands[2]);
+            }
             // 动画事件对象
             0x0087 => {}
             // 根据金钱数值设置魔法的基础伤害
             0x0088 => {}
             // 设置战斗结果
-            0x0089 => {}
+            0x0089 => {
+                if let Some(battle) = self.battle.as_mut() {
+                    battle.result = Some(BattleResult::from_u16(script.operands[0]));
+                }
+            }
             // 启用自动战斗
-            0x008a => {}
+            0x008a => {
+                self.auto_battle = true;
+                if let Some(battle) = self.battle.as_mut() {
+                    battle.auto_battle = true;
+                }
+            }
             // 更改当前调色板
-            0x008b => {}
+            0x008b => {
+                self.state.num_palette = script.operands[0];
+                if !self.state.need_to_fade_in {
+                    self.set_palette(self.state.num_palette as u32, false)?;
+                }
+            }
             // 从/到颜色渐变
-            0x008c => {}
+            0x008c => {
+                self.color_fade(
+                    script.operands[1] as u32,
+                    script.operands[0] as u8,
+                    script.operands[2] != 0
+                )?;
+                self.state.need_to_fade_in = false;
+            }
             // 增加玩家等级
-            0x008d => {}
+            0x008d if (event_object_id as usize) < MAX_PLAYER_ROLES => {
+                self.state.player_level_up(event_object_id as usize, script.operands[0]);
+            }
             // 将现金金额减半
             0x008f => {}
             // 设置对象脚本
             0x0090 => {}
             // 如果敌人不是同类的第一个，则跳转
-            0x0091 => {}
+            0x0091 if self.battle.is_some() => {
+                let enemies = &self.battle_ref().enemies;
+                let index = event_object_id as usize;
+                if let Some(enemy) = enemies.get(index) {
+                    if enemies[..index].iter().any(|other| other.object_id == enemy.object_id) {
+                        return Ok(script.operands[0]);
+                    }
+                }
+            }
             // 在战斗中为玩家显示魔法施法动画
-            0x0092 => {}
+            0x0092 if self.battle.is_some() => {
+                if script.operands[0] != 0 {
+                    self.battle_show_player_pre_magic((script.operands[0] - 1) as usize)?;
+                } else {
+                    self.battle_delay(200)?;
+                }
+            }
             // 屏幕渐变，同时更新场景
-            0x0093 => {}
+            0x0093 => {
+                let step = script.operands[0] as i16;
+                self.scene_fade(step)?;
+                self.state.need_to_fade_in = step < 0;
+            }
             // 如果事件对象状态为指定状态，则跳转
             0x0094 => {}
             // 如果当前场景为指定场景，则跳转
@@ -271,19 +734,39 @@ impl Game {
             // 为多个事件对象设置状态
             0x009a => {}
             // 渐变到当前场景
-            0x009b => {}
+            0x009b => {
+                self.canvas.backup_screen();
+                self.make_scence();
+                self.fade_screen(2)?;
+            }
             // 敌人分裂
-            0x009c => {}
+            0x009c if self.battle.is_some() => {
+                let split = self.battle_split_enemy(event_object_id as usize, script.operands[0])?;
+                if !split && script.operands[1] != 0 {
+                    return Ok(script.operands[1]);
+                }
+            }
             // 敌人召唤其他怪物
-            0x009e => {}
+            0x009e if self.battle.is_some() => {
+                let index = event_object_id as usize;
+                let summoned = self.battle_summon_enemies(index, script.operands[0], script.operands[1])?;
+                if !summoned && script.operands[2] != 0 {
+                    return Ok(script.operands[2]);
+                }
+            }
             // 敌人变身为其他形态
-            0x009f => {}
+            0x009f if self.battle.is_some() => {
+                self.battle_transform_enemy(event_object_id as usize, script.operands[0])?;
+            }
             // 退出游戏
             0x00a0 => {}
             // 设置所有队员的位置与第一个相同
             0x00a1 => {}
             // 随机跳转到以下指令之一
-            0x00a2 => {}
+            0x00a2 => {
+                let offset = self.state.rng.random_long(0, (script.operands[0] as i32) - 1) as u16;
+                return Ok(script_entry + 1 + offset);
+            }
             // 播放 CD 音乐，用 RIX 音乐作后备
             0x00a3 => {}
             // 滚动显示 FBP 到屏幕
@@ -291,71 +774,159 @@ impl Game {
             // 显示带有精灵效果的 FBP 图片
             0x00a5 => {}
             // 备份屏幕
-            0x00a6 => {}
+            0x00a6 => {
+                self.canvas.backup_screen();
+            }
             // 默认：无效指令
             _ => (),
         }
-        0
+        Ok(script_entry + 1)
     }
 
-    pub fn run_trigger_script(&mut self, script_entry: u16, event_object_id: u16) -> u16 {
+    pub fn run_trigger_script(&mut self, script_entry: u16, event_object_id: u16) -> Result<u16> {
         let mut event_object_id = event_object_id;
         let mut script_entry = script_entry;
 
-        let next_sciprt_entry = script_entry;
+        let mut next_script_entry = script_entry;
         if event_object_id == 0xffff {
             event_object_id = self.state.last_object_id;
         }
-        let event_object;
         self.state.last_object_id = event_object_id;
-        if event_object_id != 0 {
-            event_object = &self.state.event_objects[(event_object_id as usize) - 1];
-        }
+        self.script_success = true;
 
-        while script_entry != 0 {
-            let script = &self.data.script_entries[script_entry as usize];
-            println!("[SCRIPT] {:04x}: {}", script_entry, script);
+        let mut ended = false;
+        while script_entry != 0 && !ended {
+            let script = self.data.script_entries[script_entry as usize];
             match script.operation {
                 // 停止运行
-                0x0000 => {}
+                0x0000 => {
+                    ended = true;
+                }
                 // 停止运行并将下一条指令替换为下一条指令
-                0x0001 => {}
+                0x0001 => {
+                    ended = true;
+                    next_script_entry = script_entry + 1;
+                }
                 // 停止运行并将下一条指令替换为指定指令
-                0x0002 => {}
+                0x0002 => {
+                    if
+                        script.operands[1] == 0 ||
+                        !self.idle_frame_expired(event_object_id, script.operands[1])
+                    {
+                        ended = true;
+                        next_script_entry = script.operands[0];
+                    } else {
+                        script_entry += 1;
+                    }
+                }
                 // 无条件跳转
-                0x0003 => {}
+                0x0003 => {
+                    if
+                        script.operands[1] == 0 ||
+                        !self.idle_frame_expired(event_object_id, script.operands[1])
+                    {
+                        script_entry = script.operands[0];
+                    } else {
+                        script_entry += 1;
+                    }
+                }
                 // 调用脚本
-                0x0004 => {}
+                0x0004 => {
+                    let object_id = if script.operands[1] == 0 {
+                        event_object_id
+                    } else {
+                        script.operands[1]
+                    };
+                    self.run_trigger_script(script.operands[0], object_id)?;
+                    script_entry += 1;
+                }
                 // 重绘屏幕
-                0x0005 => {}
+                0x0005 => {
+                    script_entry += 1;
+                }
                 // 以指定概率跳转到指定地址
-                0x0006 => {}
-                // 开始战斗
-                0x0007 => {}
+                0x0006 => {
+                    script_entry = if self.state.rng.random_long(1, 100) >= (script.operands[0] as i32) {
+                        script.operands[1]
+                    } else {
+                        script_entry + 1
+                    };
+                }
+                // 开始战斗，失败或逃跑时跳转到指定地址
+                0x0007 => {
+                    let result = self.start_battle(script.operands[0], script.operands[2] == 0)?;
+                    script_entry = match result {
+                        BattleResult::Lost if script.operands[1] != 0 => script.operands[1],
+                        BattleResult::Fled if script.operands[2] != 0 => script.operands[2],
+                        BattleResult::Lost => {
+                            // 全灭，回到最近的存档
+                            self.pending_load = Some(self.current_save_slot);
+                            0
+                        }
+                        _ => script_entry + 1,
+                    };
+                }
                 // 用下一条指令替换当前指令?
-                0x0008 => {}
+                0x0008 => {
+                    script_entry += 1;
+                    next_script_entry = script_entry;
+                }
                 // 等待指定帧数
-                0x0009 => {}
+                0x0009 => {
+                    script_entry += 1;
+                }
                 // 如果玩家选项为否则跳转到指定地址
-                0x000a => {}
+                0x000a => {
+                    script_entry += 1;
+                }
                 // 在屏幕中间显示对话框
-                0x003b => {}
+                0x003b => {
+                    script_entry += 1;
+                }
                 // 在屏幕上方显示对话框
-                0x003c => {}
+                0x003c => {
+                    script_entry += 1;
+                }
                 // 在屏幕下方显示对话框
-                0x003d => {}
+                0x003d => {
+                    script_entry += 1;
+                }
                 // 在屏幕中间显示对话框
-                0x003e => {}
+                0x003e => {
+                    script_entry += 1;
+                }
                 // 恢复屏幕
-                0x008e => {}
+                0x008e => {
+                    self.canvas.restore_screen();
+                    self.blit_to_screen()?;
+                    script_entry += 1;
+                }
                 // 打印对话框文本
-                0xffff => {}
+                0xffff => {
+                    script_entry += 1;
+                }
                 _ => {
-                    script_entry = self.interpret_instruction(script_entry, event_object_id);
+                    script_entry = self.interpret_instruction(script_entry, event_object_id)?;
                 }
             }
-            break;
         }
-        0
+
+        Ok(next_script_entry)
+    }
+
+    // 事件对象的空闲帧计数达到上限时返回 true，并清零计数
+    fn idle_frame_expired(&mut self, event_object_id: u16, limit: u16) -> bool {
+        if event_object_id == 0 {
+            return true;
+        }
+
+        let event_object = &mut self.state.event_objects[(event_object_id as usize) - 1];
+        event_object.script_idle_frame += 1;
+        if event_object.script_idle_frame < limit {
+            return false;
+        }
+
+        event_object.script_idle_frame = 0;
+        true
     }
 }
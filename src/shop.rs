@@ -0,0 +1,130 @@
+use crate::game::Game;
+use crate::inventory::*;
+use crate::ui::*;
+use crate::utils::*;
+
+const BUYMENU_LABEL_CURRENT: u32 = 35;
+const SELLMENU_LABEL_PRICE: u32 = 25;
+
+impl Game {
+    fn item_price(&self, object_id: u16) -> u32 {
+        self.state.object_item(object_id).map_or(0, |item| item.price as u32)
+    }
+
+    // 从 STORE 表中第 store_num 个商店购买物品
+    pub fn buy_menu(&mut self, store_num: u16) -> Result<()> {
+        let items: Vec<u16> = match self.data.store(store_num as usize) {
+            Some(store) => store.items.iter().copied().filter(|&item| item != 0).collect(),
+            None => Vec::new(),
+        };
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let background = self.canvas.get_pixels().to_vec();
+        let mut selected = 0;
+        loop {
+            self.canvas.set_pixels(|pixels: &mut [u8]| {
+                pixels.copy_from_slice(&background);
+            });
+            self.draw_box(Pos { x: 122, y: 8 }, 8, 8);
+            self.draw_cash();
+
+            let menu_items: Vec<MenuItem> = items
+                .iter()
+                .enumerate()
+                .map(|(i, &item)| MenuItem {
+                    value: i as u16,
+                    num_word: item as u32,
+                    enabled: self.item_price(item) <= self.state.cash,
+                    x: 150,
+                    y: 22 + 18 * (i as u16),
+                })
+                .collect();
+
+            let value = self.read_menu_with(&menu_items, selected, |game, value| {
+                for (i, &item) in items.iter().enumerate() {
+                    game.draw_number(
+                        game.item_price(item),
+                        6,
+                        Pos { x: 235, y: 25 + 18 * (i as isize) },
+                        NumColor::Cyan,
+                        NumAlign::Right
+                    );
+                }
+
+                // 现有数量
+                let amount = game.state.item_amount(items[value as usize]);
+                game.draw_signle_linebox_with_shadow(Pos { x: 20, y: 105 }, 5);
+                game.draw_word_at(BUYMENU_LABEL_CURRENT, Pos { x: 30, y: 115 }, MENUITEM_COLOR);
+                game.draw_number(amount as u32, 6, Pos { x: 83, y: 119 }, NumColor::Yellow, NumAlign::Right);
+
+                Ok(())
+            })?;
+            if value == MENUITEM_VALUE_CANCELLED {
+                break;
+            }
+
+            selected = value as usize;
+            let item = items[selected];
+            let price = self.item_price(item);
+            let room = MAX_ITEM_AMOUNT.saturating_sub(self.state.item_amount(item));
+            let max = match price {
+                0 => room,
+                _ => std::cmp::min(self.state.cash / price, room as u32) as u16,
+            };
+
+            if let Some(num) = self.read_quantity(Pos { x: 20, y: 145 }, max)? {
+                self.state.cash -= price * (num as u32);
+                self.state.add_item_to_inventory(item, num as i16);
+            }
+        }
+
+        self.canvas.set_pixels(|pixels: &mut [u8]| {
+            pixels.copy_from_slice(&background);
+        });
+
+        Ok(())
+    }
+
+    // 以半价卖出库存中的物品
+    pub fn sell_menu(&mut self) -> Result<()> {
+        let background = self.canvas.get_pixels().to_vec();
+        loop {
+            self.canvas.set_pixels(|pixels: &mut [u8]| {
+                pixels.copy_from_slice(&background);
+            });
+
+            let item = self.item_select_menu(ITEM_FLAG_SELLABLE, |game, item| {
+                game.draw_cash();
+                game.draw_signle_linebox_with_shadow(Pos { x: 100, y: 150 }, 5);
+                game.draw_word_at(SELLMENU_LABEL_PRICE, Pos { x: 110, y: 160 }, MENUITEM_COLOR);
+                game.draw_number(game.item_price(item) / 2, 6, Pos { x: 149, y: 164 }, NumColor::Cyan, NumAlign::Right);
+
+                Ok(())
+            })?;
+            if item == 0 {
+                break;
+            }
+
+            // 正在使用中的物品不能卖出
+            let max = self.state.inventory
+                .iter()
+                .find(|inventory| inventory.item == item)
+                .map_or(0, |inventory| inventory.amount.saturating_sub(inventory.amount_in_use));
+
+            if let Some(num) = self.read_quantity(Pos { x: 20, y: 150 }, max)? {
+                if self.state.add_item_to_inventory(item, -(num as i16)) {
+                    self.state.cash += (self.item_price(item) / 2) * (num as u32);
+                }
+                self.state.compress_inventory();
+            }
+        }
+
+        self.canvas.set_pixels(|pixels: &mut [u8]| {
+            pixels.copy_from_slice(&background);
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+use std::path::Path;
+
+use encoding_rs::Encoding;
+use serde::{ Deserialize, Serialize };
+
+use crate::data::ScriptEntry;
+use crate::encoding::TextEncoding;
+use crate::game::Game;
+use crate::mkf;
+use crate::utils::*;
+
+// 翻译文件：导出全部对话（M.MSG）和词条（WORD.DAT）供翻译，导入时重新生成这些文件。
+// 格式为 JSON，translation 为空的条目保留原文
+
+const WORD_LENGTH: usize = 10;
+const CHUNKNUM_MSG_OFFSETS: u32 = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageEntry {
+    pub index: usize, // 对话序号，即脚本中引用的编号
+    pub offset: u32, // 导出时在 M.MSG 中的位置（SSS.MKF 第 3 块中的值）
+    #[serde(default)]
+    pub context: Vec<String>, // 显示这条对话的脚本
+    pub source: String,
+    #[serde(default)]
+    pub translation: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WordEntry {
+    pub index: usize,
+    pub source: String,
+    #[serde(default)]
+    pub translation: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Translation {
+    pub messages: Vec<MessageEntry>,
+    pub words: Vec<WordEntry>,
+}
+
+// 由翻译生成的文件内容
+pub struct TextFiles {
+    pub msg: Vec<u8>, // M.MSG
+    pub msg_offsets: Vec<u8>, // SSS.MKF 第 3 块
+    pub words: Vec<u8>, // WORD.DAT
+}
+
+// 编码不变时原来的文字数据，未翻译的条目直接沿用其中的字节
+pub struct OriginalText<'a> {
+    pub msg: &'a [u8], // M.MSG
+    pub msg_offsets: &'a [u32], // 每条对话的起始位置，最后一项为结尾
+    pub words: &'a [u8], // WORD.DAT
+}
+
+impl OriginalText<'_> {
+    // 第 index 条对话的原始字节，导出时的位置 offset 与原来的不一致时返回 None
+    fn message(&self, index: usize, offset: u32) -> Option<&[u8]> {
+        let start = *self.msg_offsets.get(index)?;
+        let end = *self.msg_offsets.get(index + 1)?;
+        if start != offset {
+            return None;
+        }
+        self.msg.get(start as usize..end as usize)
+    }
+
+    fn word(&self, index: usize) -> Option<&[u8]> {
+        self.words.get(index * WORD_LENGTH..(index + 1) * WORD_LENGTH)
+    }
+}
+
+// 每条对话被哪些脚本显示：0xffff 指令的地址，以及之前 0x003b～0x003e 打开的对话框
+pub fn message_contexts(scripts: &[ScriptEntry], msg_count: usize) -> Vec<Vec<String>> {
+    let mut contexts = vec![Vec::new(); msg_count];
+    let mut dialog = String::from("no dialog");
+    for (address, entry) in scripts.iter().enumerate() {
+        match entry.operation {
+            0x0000 => {
+                dialog = String::from("no dialog");
+            }
+            0x003b | 0x003e => {
+                dialog = String::from("center dialog");
+            }
+            0x003c => {
+                dialog = format!("upper dialog, face {}", entry.operands[0]);
+            }
+            0x003d => {
+                dialog = format!("lower dialog, face {}", entry.operands[0]);
+            }
+            0xffff => {
+                if let Some(context) = contexts.get_mut(entry.operands[0] as usize) {
+                    context.push(format!("script {:04x}: {}", address, dialog));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    contexts
+}
+
+fn encode_text(encoding: &'static Encoding, text: &str, what: &str) -> Result<Vec<u8>> {
+    let (bytes, _, had_errors) = encoding.encode(text);
+    if had_errors {
+        return Err(format!("{} cannot be encoded in {}: {}", what, encoding.name(), text).into());
+    }
+
+    Ok(bytes.into_owned())
+}
+
+impl Translation {
+    pub fn new(msgs: &[String], msg_offsets: &[u32], words: &[String], scripts: &[ScriptEntry]) -> Self {
+        let contexts = message_contexts(scripts, msgs.len());
+        let messages = msgs
+            .iter()
+            .zip(contexts)
+            .enumerate()
+            .map(|(index, (source, context))| MessageEntry {
+                index,
+                offset: msg_offsets.get(index).copied().unwrap_or(0),
+                context,
+                source: source.clone(),
+                translation: String::new(),
+            })
+            .collect();
+        let words = words
+            .iter()
+            .enumerate()
+            .map(|(index, source)| WordEntry { index, source: source.clone(), translation: String::new() })
+            .collect();
+
+        Self { messages, words }
+    }
+
+    pub fn from_json(text: &str) -> Result<Self> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, self.to_json()?)?;
+
+        Ok(())
+    }
+
+    // 按 encoding 生成 M.MSG、对话位置表和 WORD.DAT。条目必须按序号排列且不能缺少。
+    // 编码不变时传入 original，未翻译的对话和词条原样保留，无法解码的字节也不受影响；
+    // 换成其他编码时未翻译的条目重新编码，UTF-8 下每个汉字占 3 字节，超过 3 个汉字的词条必须翻译
+    pub fn build(&self, encoding: &'static Encoding, original: Option<&OriginalText>) -> Result<TextFiles> {
+        let mut msg = Vec::new();
+        let mut msg_offsets = Vec::new();
+        for (i, entry) in self.messages.iter().enumerate() {
+            if entry.index != i {
+                return Err(format!("message {} is missing or out of order", i).into());
+            }
+            msg_offsets.extend_from_slice(&(msg.len() as u32).to_le_bytes());
+            if entry.translation.is_empty() {
+                if let Some(bytes) = original.and_then(|original| original.message(i, entry.offset)) {
+                    msg.extend_from_slice(bytes);
+                    continue;
+                }
+            }
+            let text = if entry.translation.is_empty() { &entry.source } else { &entry.translation };
+            msg.extend(encode_text(encoding, text, &format!("message {}", i))?);
+        }
+        msg_offsets.extend_from_slice(&(msg.len() as u32).to_le_bytes());
+
+        let mut words = Vec::new();
+        for (i, entry) in self.words.iter().enumerate() {
+            if entry.index != i {
+                return Err(format!("word {} is missing or out of order", i).into());
+            }
+            if entry.translation.is_empty() {
+                if let Some(bytes) = original.and_then(|original| original.word(i)) {
+                    words.extend_from_slice(bytes);
+                    continue;
+                }
+            }
+            let text = if entry.translation.is_empty() { &entry.source } else { &entry.translation };
+            let mut bytes = encode_text(encoding, text, &format!("word {}", i))?;
+            if bytes.len() > WORD_LENGTH {
+                let hint = if entry.translation.is_empty() { ", translate or shorten it" } else { "" };
+                return Err(
+                    format!("word {} is longer than {} bytes in {}{}: {}", i, WORD_LENGTH, encoding.name(), hint, text).into()
+                );
+            }
+            bytes.resize(WORD_LENGTH, b' ');
+            words.extend(bytes);
+        }
+
+        Ok(TextFiles { msg, msg_offsets, words })
+    }
+}
+
+// 覆盖数据文件前先备份为 .bak，已有备份时保留最初的那份
+fn write_with_backup(name: &str, buf: &[u8]) -> Result<()> {
+    let path = file_path(name);
+    let backup = file_path(&format!("{}.bak", name));
+    if !backup.exists() {
+        std::fs::copy(&path, &backup)?;
+    }
+    std::fs::write(&path, buf)?;
+
+    Ok(())
+}
+
+impl Game {
+    pub fn export_text<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let ui = &self.ui;
+        Translation::new(&ui.msgs, &ui.msg_offsets, &ui.words, &self.data.script_entries).save(path)
+    }
+
+    // 导入翻译，重新生成 M.MSG、WORD.DAT 和 SSS.MKF。
+    // 编码由 import_encoding 指定，auto 时沿用原来的编码；WOR16.ASC 也转换为同样的编码
+    pub fn import_text<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let translation = Translation::load(path)?;
+        let encoding = match self.config.import_encoding {
+            TextEncoding::Auto => self.ui.encoding,
+            import_encoding => import_encoding.resolve(&[]),
+        };
+        let msg = std::fs::read(file_path("M.MSG"))?;
+        let words = std::fs::read(file_path("WORD.DAT"))?;
+        let original = OriginalText { msg: &msg, msg_offsets: &self.ui.msg_offsets, words: &words };
+        let files = translation.build(encoding, (encoding == self.ui.encoding).then_some(&original))?;
+
+        let buf = std::fs::read(file_path("WOR16.ASC"))?;
+        let (font_chars, _, _) = self.ui.encoding.decode(&buf);
+        let asc = encode_text(encoding, &font_chars, "WOR16.ASC")?;
+
+        let mut chunks = self.mkf.sss.read_all_chunks()?;
+        *chunks.get_mut(CHUNKNUM_MSG_OFFSETS as usize).ok_or("SSS.MKF has no message offsets")? = files.msg_offsets;
+
+        write_with_backup("M.MSG", &files.msg)?;
+        write_with_backup("WORD.DAT", &files.words)?;
+        write_with_backup("WOR16.ASC", &asc)?;
+        write_with_backup("SSS.MKF", &mkf::build(&chunks))?;
+
+        Ok(())
+    }
+}
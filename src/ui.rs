@@ -1,11 +1,10 @@
-use std::time::Duration;
-use chardetng::EncodingDetector;
 use encoding_rs::Encoding;
+use std::collections::HashMap;
 use std::io::Read;
 use std::io::Seek;
 use std::io::SeekFrom;
 
-use crate::{ game::Game, input::PalKey, sprite::*, utils::*, mkf::MKF };
+use crate::{ encoding::*, font::*, game::Game, input::PalKey, sprite::*, utils::*, mkf::MKF };
 
 pub struct MenuItem {
     pub value: u16,
@@ -18,42 +17,81 @@ pub struct MenuItem {
 pub const CHUNKNUM_SPRITEUI: u32 = 9;
 
 pub const MAINMENU_BACKGROUND_FBPNUM: u32 = 60;
-pub const RIX_NUM_OPENINGMENU: u32 = 4;
+pub const RIX_NUM_OPENINGMENU: u16 = 4;
 
 pub const MAINMENU_LABEL_NEWGAME: u32 = 7;
 pub const MAINMENU_LABEL_LOADGAME: u32 = 8;
 pub const LOADMENU_LABEL_SLOT_FIRST: u32 = 43;
 
 pub const MENUITEM_COLOR: u8 = 0x4f;
+pub const MENUITEM_COLOR_INACTIVE: u8 = 0x1c;
+pub const MENUITEM_COLOR_SELECTED_INACTIVE: u8 = 0x1f;
+pub const MENUITEM_COLOR_CONFIRMED: u8 = 0x2c;
 pub const MENUITEM_COLOR_SELECTED_FIRST: u32 = 0xf9;
 pub const MENUITEM_COLOR_SELECTED_TOTALNUM: u32 = 6;
 
+// 按下菜单键取消时 read_menu 的返回值
+pub const MENUITEM_VALUE_CANCELLED: u16 = 0xffff;
+
+pub const CASH_LABEL: u32 = 21;
+
+// 数字精灵的颜色，值为 0 在 UI 精灵中的序号
+#[derive(Clone, Copy)]
+pub enum NumColor {
+    Yellow = 19,
+    Blue = 29,
+    Cyan = 56,
+}
+
+#[derive(Clone, Copy)]
+pub enum NumAlign {
+    Left,
+    Mid,
+    Right,
+}
+
+// 文字相对于给定位置的对齐方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextAlign {
+    Left,
+    Mid,
+    Right,
+}
+
+// 对话等多行文字的行距
+pub const TEXT_LINE_HEIGHT: u32 = 18;
+
+// 绘制文字的目标：宽 width、高 height 的 8 位像素缓冲区
+pub struct TextSurface<'a> {
+    pub pixels: &'a mut [u8],
+    pub width: u32,
+    pub height: u32,
+}
+
 pub struct UI {
     pub font_chars: Vec<char>,
     pub fonts: Vec<Vec<u8>>,
+    pub glyph_index: HashMap<char, usize>, // 字符在 font_chars 中的序号
     pub msgs: Vec<String>,
+    pub msg_offsets: Vec<u32>, // 每条对话在 M.MSG 中的起始位置（SSS.MKF 第 3 块）
     pub words: Vec<String>,
     pub encoding: &'static Encoding,
+    pub decode_issues: Vec<DecodeIssue>, // 按所选编码无法解码的字节
     pub sprite: Sprite, // ui sprites
 }
 
 impl UI {
-    pub fn load(data_mkf: &mut MKF) -> Result<Self> {
-        let mut asc_file = open_file("WOR16.ASC")?;
-        let bytes = asc_file.seek(SeekFrom::End(0))?;
-        let mut buf = vec![0; bytes as usize];
-
-        asc_file.seek(SeekFrom::Start(0))?;
-        asc_file.read_exact(&mut buf)?;
-
-        let mut detector = EncodingDetector::new();
-        detector.feed(&buf, true);
-        let encoding = detector.guess(None, true);
-        //println!("{:?}", encoding);
-
-        let (decoded, _, _) = encoding.decode(&buf);
+    // text_encoding 为 Auto 时根据文字数据猜测编码
+    pub fn load(data_mkf: &mut MKF, text_encoding: TextEncoding) -> Result<Self> {
+        let asc = std::fs::read(file_path("WOR16.ASC"))?;
+        let word_buf = std::fs::read(file_path("WORD.DAT"))?;
+        let msg_buf = std::fs::read(file_path("M.MSG"))?;
+        let encoding = text_encoding.resolve(&[&asc, &word_buf, &msg_buf]);
+
+        let (decoded, _, _) = encoding.decode(&asc);
         let font_chars: Vec<char> = decoded.chars().collect();
         let n_chars = font_chars.len();
+        let glyph_index = build_glyph_index(&font_chars);
 
         // 16*16 font
         let mut font_file = open_file("WOR16.FON")?;
@@ -67,76 +105,71 @@ impl UI {
             }
         }
 
-        let mut word_file = open_file("WORD.DAT")?;
-        let bytes = word_file.seek(SeekFrom::End(0))?;
-        let mut buf = vec![0; bytes as usize];
-        word_file.seek(SeekFrom::Start(0))?;
-        word_file.read_exact(&mut buf)?;
-
-        let mut words: Vec<String> = Vec::new();
-        for i in 0..buf.len() / 10 {
-            let (s, _, _) = encoding.decode(&buf[i * 10..i * 10 + 10]);
-            words.push(s.into_owned());
-        }
-
+        // 每项固定 10 字节，不足的部分用空格填充
+        let word_entries: Vec<&[u8]> = word_buf.chunks_exact(10).collect();
+        let words = word_entries
+            .iter()
+            .map(|entry| {
+                let (s, _, _) = encoding.decode(entry);
+                s.trim_end_matches([' ', '\0']).to_string()
+            })
+            .collect();
+
+        // SSS.MKF 第 3 块是每条对话在 M.MSG 中的起始位置
         let mut sss_mfk = open_mkf("SSS.MKF")?;
         let buf = sss_mfk.read_chunk(3)?;
-        let msg_count = buf.len() / 4;
-        let mut offsets = vec![0; msg_count];
-        for i in 0..msg_count {
-            offsets[i] = u32::from_le_bytes(buf[i * 4..i * 4 + 4].try_into().unwrap());
+        let msg_offsets: Vec<u32> = buf
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+
+        let mut msg_entries: Vec<&[u8]> = Vec::new();
+        for pair in msg_offsets.windows(2) {
+            let entry = msg_buf.get(pair[0] as usize..pair[1] as usize).ok_or("invalid message offset")?;
+            msg_entries.push(entry);
         }
+        let msgs = msg_entries
+            .iter()
+            .map(|entry| encoding.decode(entry).0.into_owned())
+            .collect();
 
-        let mut msg_file = open_file("M.MSG")?;
-        let mut msgs = Vec::new();
-
-        for i in 0..msg_count - 1 {
-            let mut buf = vec![0; (offsets[i + 1] - offsets[i]) as usize];
-            msg_file.seek(SeekFrom::Start(offsets[i] as u64))?;
-            msg_file.read_exact(&mut buf)?;
-            let (s, _, _) = encoding.decode(&buf);
-            let s = s.into_owned();
-            msgs.push(s);
-        }
+        let mut decode_issues = check_entries(encoding, TextSource::Word, word_entries);
+        decode_issues.extend(check_entries(encoding, TextSource::Message, msg_entries));
 
         let chunk = data_mkf.read_chunk(CHUNKNUM_SPRITEUI)?;
         let sprite = sprite_get_frames(&chunk)?;
 
-        Ok(Self { font_chars, fonts, words, msgs, encoding, sprite })
+        Ok(Self { font_chars, fonts, glyph_index, msgs, msg_offsets, words, encoding, decode_issues, sprite })
     }
 
-    pub fn draw_char(
-        &self,
-        pixels: &mut [u8],
-        dest_width: u32,
-        dest_height: u32,
-        x: i32,
-        y: i32,
-        c: char,
-        color: u8
-    ) {
-        let r = self.font_chars.iter().position(|&r| r == c);
-        if r.is_none() {
-            return;
-        }
-
-        let index = r.unwrap();
-        let font = &self.fonts[index];
-
-        for i in (0..32).step_by(2) {
-            let byte = u16::from_le_bytes(font[i..i + 2].try_into().unwrap());
-            let sy = y + ((i / 2) as i32);
+    // 字符的 16x16 全角字形，每行两个字节（小端序）
+    pub fn glyph(&self, c: char) -> Option<&[u8]> {
+        self.glyph_index.get(&c).map(|&index| self.fonts[index].as_slice())
+    }
 
-            for bit in 0..16 {
+    // ASCII 字符使用内置的半角字形，其他字符使用 WOR16 或补充的字形
+    pub fn draw_char(&self, surface: &mut TextSurface, x: i32, y: i32, c: char, color: u8) {
+        let mut draw_row = |row: usize, bits: u16, width: u32| {
+            let sy = y + (row as i32);
+            if sy < 0 || sy >= (surface.height as i32) {
+                return;
+            }
+            for bit in 0..width {
                 let sx = x + (bit as i32);
-                if sx >= (dest_width as i32) || sy >= (dest_height as i32) || sx < 0 || sy < 0 {
-                    continue;
-                }
-                let index = (sy * (dest_width as i32) + sx) as usize;
-                if (byte & (1 << bit)) != 0 {
-                    pixels[index] = color;
+                if sx >= 0 && sx < (surface.width as i32) && (bits & (1 << bit)) != 0 {
+                    surface.pixels[(sy as usize) * (surface.width as usize) + (sx as usize)] = color;
                 }
             }
+        };
+
+        if let Some(glyph) = ascii_glyph(c) {
+            for (row, &bits) in glyph.iter().enumerate() {
+                draw_row(row, bits as u16, HALF_WIDTH);
+            }
+        } else if let Some(glyph) = self.glyph(c) {
+            for (row, bits) in glyph.chunks_exact(2).enumerate() {
+                draw_row(row, u16::from_le_bytes([bits[0], bits[1]]), FULL_WIDTH);
+            }
         }
     }
 
@@ -147,6 +180,33 @@ impl UI {
         &self.words[index]
     }
 
+    // 用 font 补充对话和词条中 WOR16 没有的字形，返回补充的个数
+    pub fn add_fallback_glyphs(&mut self, font: &FallbackFont) -> usize {
+        let mut chars: Vec<char> = self.msgs.iter().chain(self.words.iter()).flat_map(|text| text.chars()).collect();
+        chars.sort_unstable();
+        chars.dedup();
+
+        let mut count = 0;
+        for c in chars {
+            if c.is_ascii() || self.glyph_index.contains_key(&c) {
+                continue;
+            }
+            if let Some(glyph) = font.render(c) {
+                self.glyph_index.insert(c, self.font_chars.len());
+                self.font_chars.push(c);
+                self.fonts.push(glyph.to_vec());
+                count += 1;
+            }
+        }
+
+        count
+    }
+
+    // 文字编码的诊断报告
+    pub fn text_report(&self) -> String {
+        format_report(self.encoding, &self.decode_issues)
+    }
+
     pub fn get_msg(&self, index: usize) -> &str {
         if index >= self.msgs.len() {
             return "";
@@ -155,60 +215,119 @@ impl UI {
     }
 }
 
+// 同一个字符出现多次时使用第一个字形
+pub fn build_glyph_index(chars: &[char]) -> HashMap<char, usize> {
+    let mut index = HashMap::with_capacity(chars.len());
+    for (i, &c) in chars.iter().enumerate() {
+        index.entry(c).or_insert(i);
+    }
+    index
+}
+
+// 字符占用的宽度：数字（用数字精灵绘制）、其他 ASCII 字符和西文字母为半角，其余为全角
+pub fn char_width(c: char) -> u32 {
+    if is_half_width(c) { HALF_WIDTH } else { FULL_WIDTH }
+}
+
+pub fn measure_text(text: &str) -> u32 {
+    text.chars().map(char_width).sum()
+}
+
+// 不能出现在行首的标点，换行时随前一个字符留在上一行
+fn is_closing_punctuation(c: char) -> bool {
+    "，。、！？：；」』）》,.!?:;)".contains(c)
+}
+
+// 按宽度折行。全角字符之间可以任意断开，ASCII 单词只在空格处断开（单词本身超过宽度时除外），
+// 行首和行尾的空格被去掉
+pub fn wrap_text(text: &str, width: u32) -> Vec<String> {
+    // 先切分成不可再分的片段
+    let mut pieces: Vec<String> = Vec::new();
+    for c in text.chars() {
+        let joins = match pieces.last() {
+            Some(last) => {
+                let prev = last.chars().last().unwrap();
+                is_closing_punctuation(c) ||
+                    (c.is_ascii_graphic() && prev.is_ascii_graphic())
+            }
+            None => false,
+        };
+        if joins {
+            pieces.last_mut().unwrap().push(c);
+        } else {
+            pieces.push(c.to_string());
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for piece in pieces {
+        if piece == " " {
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            continue;
+        }
+        if measure_text(&line) + measure_text(&piece) <= width {
+            line.push_str(&piece);
+            continue;
+        }
+
+        if !line.trim_end().is_empty() {
+            lines.push(line.trim_end().to_string());
+        }
+        line = String::new();
+        // 一行放不下的片段逐字断开
+        for c in piece.chars() {
+            if !line.is_empty() && measure_text(&line) + char_width(c) > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            line.push(c);
+        }
+    }
+    if !line.trim_end().is_empty() {
+        lines.push(line.trim_end().to_string());
+    }
+
+    lines
+}
+
+// 按对齐方式计算文字左端的横坐标
+pub fn align_text(x: i32, text: &str, align: TextAlign) -> i32 {
+    let width = measure_text(text) as i32;
+    match align {
+        TextAlign::Left => x,
+        TextAlign::Mid => x - width / 2,
+        TextAlign::Right => x - width,
+    }
+}
+
 impl Game {
-    pub fn draw_text(
-        ui: &UI,
-        pixels: &mut [u8],
-        dest_width: u32,
-        dest_height: u32,
-        x: i32,
-        y: i32,
-        text: &str,
-        color: u8,
-        shadow: bool
-    ) {
+    pub fn draw_text(ui: &UI, surface: &mut TextSurface, x: i32, y: i32, text: &str, color: u8, shadow: bool) {
         let mut x = x;
         for c in text.chars() {
-            if c.is_numeric() {
-                if let Some(i) = c.to_digit(10) {
-                    let frame = &ui.sprite[(i as usize) + 29];
-                    draw_sprite_frame(
-                        frame,
-                        pixels,
-                        dest_width as usize,
-                        dest_height as usize,
-                        x as isize,
-                        y as isize + 4
-                    );
-                    x += 8;
-                }
+            if let Some(i) = c.to_digit(10) {
+                let frame = &ui.sprite[(i as usize) + 29];
+                draw_sprite_frame(
+                    frame,
+                    surface.pixels,
+                    surface.width as usize,
+                    surface.height as usize,
+                    x as isize,
+                    y as isize + 4
+                );
             } else {
                 if shadow {
-                    ui.draw_char(pixels, dest_width, dest_height, x + 1, y + 1, c, 0);
-                    ui.draw_char(pixels, dest_width, dest_height, x + 1, y, c, 0);
-                    ui.draw_char(pixels, dest_width, dest_height, x, y + 1, c, 0);
+                    ui.draw_char(surface, x + 1, y + 1, c, 0);
+                    ui.draw_char(surface, x + 1, y, c, 0);
+                    ui.draw_char(surface, x, y + 1, c, 0);
                 }
-                ui.draw_char(pixels, dest_width, dest_height, x, y, c, color);
-                x += 16;
+                ui.draw_char(surface, x, y, c, color);
             }
+            x += char_width(c) as i32;
         }
     }
 
-    pub fn draw_word(
-        ui: &UI,
-        pixels: &mut [u8],
-        dest_width: u32,
-        dest_height: u32,
-        x: i32,
-        y: i32,
-        index: usize,
-        color: u8,
-        shadow: bool
-    ) {
-        let text = ui.get_word(index);
-        Self::draw_text(ui, pixels, dest_width, dest_height, x, y, text, color, shadow);
-    }
-
     pub fn menu_color_selected(&self) -> u8 {
         let ticks = self.ticks();
         let ticks = ticks / (600 / MENUITEM_COLOR_SELECTED_TOTALNUM);
@@ -217,38 +336,46 @@ impl Game {
     }
 
     pub fn read_menu(&mut self, menu_items: &[MenuItem]) -> Result<u16> {
-        let mut selected_index = 0;
-        let mut pixels = self.canvas.get_pixels().to_vec();
+        self.read_menu_with(menu_items, 0, |_, _| Ok(()))
+    }
+
+    // 读取菜单选择。每帧先恢复进入菜单时的画面，再调用 on_draw 绘制与当前选项相关的内容。
+    // 按下菜单键时返回 MENUITEM_VALUE_CANCELLED
+    pub fn read_menu_with<F>(
+        &mut self,
+        menu_items: &[MenuItem],
+        default_index: usize,
+        mut on_draw: F
+    ) -> Result<u16>
+        where F: FnMut(&mut Game, u16) -> Result<()>
+    {
+        if menu_items.is_empty() {
+            return Ok(MENUITEM_VALUE_CANCELLED);
+        }
+
+        let mut selected_index = std::cmp::min(default_index, menu_items.len() - 1);
+        let background = self.canvas.get_pixels().to_vec();
         loop {
-            for i in 0..menu_items.len() {
-                let item = &menu_items[i];
-                let color = if i == selected_index {
-                    self.menu_color_selected()
+            self.canvas.set_pixels(|pixels: &mut [u8]| {
+                pixels.copy_from_slice(&background);
+            });
+            on_draw(self, menu_items[selected_index].value)?;
+
+            let selected_color = self.menu_color_selected();
+            for (i, item) in menu_items.iter().enumerate() {
+                let color = if !item.enabled {
+                    MENUITEM_COLOR_INACTIVE
+                } else if i == selected_index {
+                    selected_color
                 } else {
                     MENUITEM_COLOR
                 };
 
-                if item.enabled {
-                    Self::draw_word(
-                        &self.ui,
-                        &mut pixels,
-                        320,
-                        200,
-                        item.x as i32,
-                        item.y as i32,
-                        item.num_word as usize,
-                        color,
-                        true
-                    );
-                }
+                self.draw_word_at(item.num_word, Pos { x: item.x as isize, y: item.y as isize }, color);
             }
 
-            self.canvas.set_pixels(|_pixels: &mut [u8]| {
-                _pixels.copy_from_slice(&pixels);
-            });
-
             self.blit_to_screen()?;
-            self.process_event();
+            self.process_event()?;
 
             if self.input.is_pressed(PalKey::Down) || self.input.is_pressed(PalKey::Right) {
                 selected_index = (menu_items.len() + selected_index + 1) % menu_items.len();
@@ -256,11 +383,130 @@ impl Game {
                 selected_index = (menu_items.len() + selected_index - 1) % menu_items.len();
             }
 
-            if self.input.is_pressed(PalKey::Search) {
+            if self.input.is_pressed(PalKey::Search) && menu_items[selected_index].enabled {
                 return Result::Ok(menu_items[selected_index].value);
             }
 
-            std::thread::sleep(Duration::from_millis(30));
+            if self.input.is_pressed(PalKey::Menu) {
+                return Result::Ok(MENUITEM_VALUE_CANCELLED);
+            }
+
+            self.delay(30);
+        }
+    }
+
+    // 用 UI 精灵 0～8 拼出的方框，rows 和 columns 为中间部分的格数
+    pub fn draw_box(&mut self, pos: Pos, rows: u32, columns: u32) {
+        let sprite = &self.ui.sprite;
+        let mut y = pos.y;
+
+        self.canvas.set_pixels(|pixels: &mut [u8]| {
+            for i in 0..rows + 2 {
+                let m = if i == 0 { 0 } else if i == rows + 1 { 2 } else { 1 };
+                let mut x = pos.x;
+                for j in 0..columns + 2 {
+                    let n = if j == 0 { 0 } else if j == columns + 1 { 2 } else { 1 };
+                    let frame = &sprite[m * 3 + n];
+                    draw_sprite_frame(frame, pixels, 320, 200, x, y);
+                    x += frame.width as isize;
+                }
+                y += sprite[m * 3].height as isize;
+            }
+        });
+    }
+
+    // 用数字精灵绘制最多 length 位的数字
+    pub fn draw_number(&mut self, num: u32, length: u32, pos: Pos, color: NumColor, align: NumAlign) {
+        let actual_length = std::cmp::min(std::cmp::max(num.checked_ilog10().unwrap_or(0) + 1, 1), length);
+
+        let mut x = pos.x - 6;
+        x += match align {
+            NumAlign::Left => 6 * (actual_length as isize),
+            NumAlign::Mid => 3 * ((length + actual_length) as isize),
+            NumAlign::Right => 6 * (length as isize),
+        };
+
+        let sprite = &self.ui.sprite;
+        self.canvas.set_pixels(|pixels: &mut [u8]| {
+            let mut num = num;
+            for _ in 0..length {
+                let frame = &sprite[((num % 10) as usize) + (color as usize)];
+                draw_sprite_frame(frame, pixels, 320, 200, x, pos.y);
+                x -= 6;
+                num /= 10;
+                if num == 0 {
+                    break;
+                }
+            }
+        });
+    }
+
+    // 按对齐方式在 pos 处绘制一行文字
+    pub fn draw_text_at(&mut self, text: &str, pos: Pos, color: u8, align: TextAlign) {
+        let x = align_text(pos.x as i32, text, align);
+        self.canvas.set_pixels(|pixels: &mut [u8]| {
+            let mut surface = TextSurface { pixels, width: 320, height: 200 };
+            Self::draw_text(&self.ui, &mut surface, x, pos.y as i32, text, color, true);
+        });
+    }
+
+    pub fn draw_word_at(&mut self, index: u32, pos: Pos, color: u8) {
+        let text = self.ui.get_word(index as usize).to_string();
+        self.draw_text_at(&text, pos, color, TextAlign::Left);
+    }
+
+    // 显示现有金钱
+    pub fn draw_cash(&mut self) {
+        self.draw_signle_linebox_with_shadow(Pos { x: 0, y: 0 }, 5);
+        self.draw_word_at(CASH_LABEL, Pos { x: 10, y: 10 }, MENUITEM_COLOR);
+        self.draw_number(self.state.cash, 6, Pos { x: 49, y: 14 }, NumColor::Yellow, NumAlign::Right);
+    }
+
+    // 在 pos 处的单行框中选择 1～max 的数量，取消时返回 None
+    pub fn read_quantity(&mut self, pos: Pos, max: u16) -> Result<Option<u16>> {
+        if max == 0 {
+            return Ok(None);
+        }
+
+        let background = self.canvas.get_pixels().to_vec();
+        let mut num: u16 = 1;
+        loop {
+            self.canvas.set_pixels(|pixels: &mut [u8]| {
+                pixels.copy_from_slice(&background);
+            });
+            self.draw_signle_linebox_with_shadow(pos, 2);
+            self.draw_number(
+                num as u32,
+                2,
+                Pos { x: pos.x + 22, y: pos.y + 14 },
+                NumColor::Cyan,
+                NumAlign::Mid
+            );
+
+            self.blit_to_screen()?;
+            self.process_event()?;
+
+            if self.input.is_pressed(PalKey::Up) || self.input.is_pressed(PalKey::Right) {
+                num = if num >= max { 1 } else { num + 1 };
+            } else if self.input.is_pressed(PalKey::Down) || self.input.is_pressed(PalKey::Left) {
+                num = if num <= 1 { max } else { num - 1 };
+            } else if self.input.is_pressed(PalKey::PgUp) {
+                num = std::cmp::min(num.saturating_add(10), max);
+            } else if self.input.is_pressed(PalKey::PgDn) {
+                num = std::cmp::max(num.saturating_sub(10), 1);
+            }
+
+            if self.input.is_pressed(PalKey::Search) {
+                return Ok(Some(num));
+            }
+            if self.input.is_pressed(PalKey::Menu) {
+                self.canvas.set_pixels(|pixels: &mut [u8]| {
+                    pixels.copy_from_slice(&background);
+                });
+                return Ok(None);
+            }
+
+            self.delay(30);
         }
     }
 
@@ -1,7 +1,8 @@
 use std::fs::File;
 use std::path;
+use std::sync::RwLock;
 
-use bincode::{config, decode_from_slice, Decode};
+use bincode::{ config, decode_from_slice, encode_into_std_write, Decode, Encode };
 
 use crate::game::Game;
 use crate::mkf;
@@ -31,6 +32,7 @@ impl Dir {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Pos {
     pub x: isize,
     pub y: isize,
@@ -44,11 +46,23 @@ pub struct Rect {
     pub h: usize,
 }
 
-const BASE_PATH: &str = "/home/rocky/Code/Game/PAL95/";
+// 游戏数据文件所在的目录，由 Config::data_dir 设置
+static DATA_DIR: RwLock<Option<path::PathBuf>> = RwLock::new(None);
+
+pub fn set_data_dir<P: AsRef<path::Path>>(dir: P) {
+    *DATA_DIR.write().unwrap_or_else(|e| e.into_inner()) = Some(dir.as_ref().to_path_buf());
+}
+
+pub fn data_dir() -> path::PathBuf {
+    DATA_DIR.read().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_else(|| path::PathBuf::from("."))
+}
+
+pub fn file_path(filename: &str) -> path::PathBuf {
+    data_dir().join(filename)
+}
 
 pub fn open_file(filename: &str) -> Result<File> {
-    let filepath = path::Path::new(BASE_PATH).join(filename);
-    let file = std::fs::File::open(filepath)?;
+    let file = std::fs::File::open(file_path(filename))?;
 
     Ok(file)
 }
@@ -69,11 +83,28 @@ pub fn reverse_bits(u8: u8) -> u8 {
 }
 
 impl Game {
+    // 游戏时间，毫秒
     pub fn ticks(&self) -> u32 {
-        self.start_time.elapsed().as_millis() as u32
+        self.scheduler.ticks()
+    }
+
+    // 等待 ms 毫秒游戏时间
+    pub fn delay(&mut self, ms: u32) {
+        self.scheduler.delay(ms);
     }
 }
 
+// 解码单个结构，同时返回占用的字节数
+pub fn decode_c_struct<T: Decode>(buf: &[u8]) -> Result<(T, usize)> {
+    let c = config::standard()
+        .with_little_endian()
+        .with_fixed_int_encoding();
+
+    let (obj, size): (T, usize) = decode_from_slice(buf, c)?;
+
+    Ok((obj, size))
+}
+
 pub fn decode_c_structs<T: Decode>(buf: &[u8]) -> Result<Vec<T>> {
     let c = config::standard()
         .with_little_endian()
@@ -93,3 +124,16 @@ pub fn decode_c_structs<T: Decode>(buf: &[u8]) -> Result<Vec<T>> {
 
     Ok(objects)
 }
+
+pub fn encode_c_structs<T: Encode>(objects: &[T]) -> Result<Vec<u8>> {
+    let c = config::standard()
+        .with_little_endian()
+        .with_fixed_int_encoding();
+
+    let mut buf = Vec::new();
+    for obj in objects {
+        encode_into_std_write(obj, &mut buf, c)?;
+    }
+
+    Ok(buf)
+}
@@ -0,0 +1,180 @@
+use crate::game::Game;
+use crate::utils::*;
+
+// 屏幕震动
+#[derive(Default, Clone, Copy)]
+pub struct Shake {
+    pub time: u16, // 剩余的震动帧数
+    pub level: u16, // 震动幅度（像素）
+}
+
+// 屏幕波动
+#[derive(Default, Clone, Copy)]
+pub struct Wave {
+    pub level: u16, // 波动幅度，0 为不波动，最大 255
+    index: usize, // 当前相位
+}
+
+// 在画布像素和最终输出之间的后期处理
+#[derive(Default)]
+pub struct PostEffects {
+    pub shake: Shake,
+    pub wave: Wave,
+}
+
+impl Wave {
+    // 每行的水平偏移量，32 行为一个周期
+    fn offsets(&self, width: usize) -> [usize; 32] {
+        let mut offsets = [0; 32];
+        let mut a: isize = 0;
+        let mut b: isize = 60 + 8;
+
+        for i in 0..16 {
+            b -= 8;
+            a += b;
+            // 先对宽度取模，幅度大于画面宽度时也不会溢出
+            let offset = ((a * (self.level as isize)) / 256).rem_euclid(width as isize);
+            offsets[i] = offset as usize;
+            offsets[i + 16] = (-offset).rem_euclid(width as isize) as usize;
+        }
+
+        offsets
+    }
+
+    fn apply(&mut self, pixels: &mut [u8], width: usize, height: usize) {
+        let offsets = self.offsets(width);
+
+        let mut a = self.index;
+        for y in 0..height {
+            let b = offsets[a];
+            if b > 0 {
+                pixels[y * width..(y + 1) * width].rotate_left(b);
+            }
+            a = (a + 1) % 32;
+        }
+
+        self.index = (self.index + 1) % 32;
+    }
+}
+
+impl Shake {
+    // 奇数帧画面上移，偶数帧画面下移，空出的部分填黑
+    fn apply(&mut self, pixels: &mut [u8], width: usize, height: usize) {
+        let level = std::cmp::min(self.level as usize, height);
+        let moved = (height - level) * width;
+
+        if self.time & 1 != 0 {
+            pixels.copy_within(level * width..height * width, 0);
+            pixels[moved..].fill(0);
+        } else {
+            pixels.copy_within(0..moved, level * width);
+            pixels[..level * width].fill(0);
+        }
+
+        self.time -= 1;
+    }
+}
+
+impl PostEffects {
+    pub fn is_active(&self) -> bool {
+        self.shake.time > 0 || self.wave.level > 0
+    }
+
+    // 把 src 经过后期处理后写入 dst，每调用一次算作显示一帧
+    pub fn apply(&mut self, src: &[u8], dst: &mut [u8], width: usize, height: usize) {
+        dst.copy_from_slice(src);
+
+        if self.wave.level > 0 {
+            self.wave.apply(dst, width, height);
+        }
+
+        if self.shake.time > 0 {
+            self.shake.apply(dst, width, height);
+        }
+    }
+}
+
+// 两个画面之间的像素溶解过渡
+pub struct Dissolve {
+    target: Vec<u8>,
+    current: Vec<u8>,
+    step: usize,
+}
+
+impl Dissolve {
+    const STEPS: usize = 12 * 6;
+    const INDEX: [usize; 6] = [0, 3, 1, 5, 2, 4];
+
+    pub fn new(from: &[u8], to: &[u8]) -> Self {
+        Self { target: to.to_vec(), current: from.to_vec(), step: 0 }
+    }
+
+    // 计算下一帧，过渡结束时返回 None
+    pub fn next_frame(&mut self) -> Option<&[u8]> {
+        if self.step >= Self::STEPS {
+            return None;
+        }
+
+        let i = self.step / 6;
+        let j = self.step % 6;
+        for k in (Self::INDEX[j]..self.current.len()).step_by(6) {
+            let a = self.target[k];
+            let mut b = self.current[k];
+
+            // 高 4 位为色系，直接替换；低 4 位为亮度，逐步接近
+            if i > 0 {
+                if (a & 0x0f) > (b & 0x0f) {
+                    b += 1;
+                } else if (a & 0x0f) < (b & 0x0f) {
+                    b -= 1;
+                }
+            }
+            self.current[k] = (a & 0xf0) | (b & 0x0f);
+        }
+
+        self.step += 1;
+        Some(&self.current)
+    }
+}
+
+impl Game {
+    // 震动屏幕 time 帧
+    pub fn shake_screen(&mut self, time: u16, level: u16) {
+        self.canvas.effects.shake = Shake { time, level };
+    }
+
+    // 从备份的画面溶解过渡到当前画面，speed 越大越慢
+    pub fn fade_screen(&mut self, speed: u16) -> Result<()> {
+        let target = self.canvas.get_pixels().to_vec();
+        let mut dissolve = Dissolve::new(self.canvas.get_backup(), &target);
+        let frame_time = ((speed as u32) + 1) * 10;
+
+        while let Some(frame) = dissolve.next_frame() {
+            self.canvas.set_pixels(|pixels: &mut [u8]| {
+                pixels.copy_from_slice(frame);
+            });
+            self.blit_to_screen()?;
+            self.process_event()?;
+
+            self.delay(frame_time);
+        }
+
+        self.canvas.set_pixels(|pixels: &mut [u8]| {
+            pixels.copy_from_slice(&target);
+        });
+        self.blit_to_screen()?;
+
+        Ok(())
+    }
+
+    // 推进屏幕波动，波动幅度减到 0 或超过 255 时停止
+    pub fn update_screen_wave(&mut self) {
+        let wave = (self.state.screen_wave as i32) + (self.state.wave_progression as i32);
+        if wave <= 0 || wave >= 256 {
+            self.state.screen_wave = 0;
+            self.state.wave_progression = 0;
+        } else {
+            self.state.screen_wave = wave as u16;
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use crate::utils::Result;
+
+// Creative Voice File（.VOC）的解析。VOC.MKF 中每块是一个完整的 VOC 文件
+
+const VOC_SIGNATURE: &[u8] = b"Creative Voice File\x1a";
+
+// 解码后的单声道 16 位采样
+#[derive(Debug, Clone, PartialEq)]
+pub struct Voc {
+    pub sample_rate: u32,
+    pub samples: Vec<i16>,
+}
+
+// 块 8 给出的格式，作用于紧随其后的块 1
+#[derive(Debug, Clone, Copy)]
+struct Extended {
+    sample_rate: u32,
+    channels: u16,
+}
+
+fn u8_to_i16(sample: u8) -> i16 {
+    ((sample as i16) - 128) << 8
+}
+
+// 多声道交错的采样混为单声道
+fn push_mixed(samples: &mut Vec<i16>, frames: impl Iterator<Item = i16>, channels: u16) {
+    let channels = std::cmp::max(channels, 1) as i32;
+    let mut sum = 0i32;
+    for (i, sample) in frames.enumerate() {
+        sum += sample as i32;
+        if ((i as i32) + 1) % channels == 0 {
+            samples.push((sum / channels) as i16);
+            sum = 0;
+        }
+    }
+}
+
+impl Voc {
+    pub fn parse(buf: &[u8]) -> Result<Self> {
+        if buf.len() < 0x1a || &buf[..VOC_SIGNATURE.len()] != VOC_SIGNATURE {
+            return Err("not a VOC file".into());
+        }
+        let mut pos = u16::from_le_bytes([buf[0x14], buf[0x15]]) as usize;
+
+        let mut sample_rate = 0;
+        let mut channels = 1;
+        let mut extended: Option<Extended> = None;
+        let mut samples = Vec::new();
+
+        while pos < buf.len() {
+            let block_type = buf[pos];
+            // 终止块没有长度
+            if block_type == 0 || pos + 4 > buf.len() {
+                break;
+            }
+            let len = u32::from_le_bytes([buf[pos + 1], buf[pos + 2], buf[pos + 3], 0]) as usize;
+            let start = pos + 4;
+            let end = std::cmp::min(start + len, buf.len());
+            let block = &buf[start..end];
+            pos = end;
+
+            match block_type {
+                // 声音数据
+                1 if block.len() >= 2 => {
+                    if block[1] != 0 {
+                        return Err(format!("unsupported VOC codec {}", block[1]).into());
+                    }
+                    match extended.take() {
+                        Some(ext) => {
+                            sample_rate = ext.sample_rate;
+                            channels = ext.channels;
+                        }
+                        None => {
+                            sample_rate = 1000000 / (256 - (block[0] as u32));
+                            channels = 1;
+                        }
+                    }
+                    push_mixed(&mut samples, block[2..].iter().map(|&s| u8_to_i16(s)), channels);
+                }
+                // 沿用前一块格式的后续数据
+                2 => {
+                    push_mixed(&mut samples, block.iter().map(|&s| u8_to_i16(s)), channels);
+                }
+                // 扩展格式
+                8 if block.len() >= 4 => {
+                    let time_constant = u16::from_le_bytes([block[0], block[1]]) as u32;
+                    if block[2] != 0 {
+                        return Err(format!("unsupported VOC codec {}", block[2]).into());
+                    }
+                    let channels = (block[3] as u16) + 1;
+                    let rate = 256000000 / ((65536 - std::cmp::min(time_constant, 65535)) * (channels as u32));
+                    extended = Some(Extended { sample_rate: rate, channels });
+                }
+                // 新格式的声音数据，带完整的采样率
+                9 if block.len() >= 12 => {
+                    sample_rate = u32::from_le_bytes([block[0], block[1], block[2], block[3]]);
+                    let bits = block[4];
+                    channels = block[5] as u16;
+                    let codec = u16::from_le_bytes([block[6], block[7]]);
+                    let data = &block[12..];
+                    match (bits, codec) {
+                        (8, 0) => push_mixed(&mut samples, data.iter().map(|&s| u8_to_i16(s)), channels),
+                        (16, 4) => push_mixed(
+                            &mut samples,
+                            data.chunks_exact(2).map(|s| i16::from_le_bytes([s[0], s[1]])),
+                            channels
+                        ),
+                        _ => {
+                            return Err(format!("unsupported VOC format: {} bits, codec {}", bits, codec).into());
+                        }
+                    }
+                }
+                // 其他块（静音、标记、文字、循环等）忽略
+                _ => {}
+            }
+        }
+
+        if sample_rate == 0 {
+            return Err("VOC file has no sound data".into());
+        }
+
+        Ok(Self { sample_rate, samples })
+    }
+
+    // 线性插值重采样到 sample_rate
+    pub fn resample(&self, sample_rate: u32) -> Vec<i16> {
+        resample(&self.samples, self.sample_rate, sample_rate)
+    }
+}
+
+pub fn resample(samples: &[i16], from: u32, to: u32) -> Vec<i16> {
+    if from == to || samples.is_empty() || from == 0 {
+        return samples.to_vec();
+    }
+
+    let len = ((samples.len() as u64) * (to as u64) / (from as u64)) as usize;
+    let step = (from as f64) / (to as f64);
+    (0..len)
+        .map(|i| {
+            let pos = (i as f64) * step;
+            let index = pos as usize;
+            let frac = pos - (index as f64);
+            let a = samples[std::cmp::min(index, samples.len() - 1)] as f64;
+            let b = samples[std::cmp::min(index + 1, samples.len() - 1)] as f64;
+            (a + (b - a) * frac).round() as i16
+        })
+        .collect()
+}
@@ -0,0 +1,69 @@
+use std::io::Cursor;
+use std::thread;
+use std::time::Duration;
+
+use pal::audio::*;
+use pal::audio_sink::*;
+use pal::config::AudioOutput;
+use pal::mixer::Mixer;
+
+fn read_u32(buf: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([buf[pos], buf[pos + 1], buf[pos + 2], buf[pos + 3]])
+}
+
+#[test]
+fn test_wav_sink() {
+    let mut sink = WavSink::new(Cursor::new(Vec::new()), 22050).unwrap();
+    assert_eq!(sink.sample_rate(), 22050);
+    sink.write(&[1, 2, 3]).unwrap();
+    sink.write(&[-1]).unwrap();
+
+    let buf = sink.into_inner().unwrap().into_inner();
+    assert_eq!(buf.len(), 44 + 8);
+    assert_eq!(&buf[0..4], b"RIFF");
+    assert_eq!(read_u32(&buf, 4), 36 + 8);
+    assert_eq!(read_u32(&buf, 24), 22050);
+    assert_eq!(read_u32(&buf, 40), 8);
+    assert_eq!(&buf[44..], &[1, 0, 2, 0, 3, 0, 0xff, 0xff]);
+}
+
+#[test]
+fn test_volume() {
+    let mut audio = Audio::new(SAMPLE_RATE);
+    audio.volume = Volume { master: 0.5, music: 1.0, sound: 0.5 };
+    audio.play_sound(vec![20000; 2].into(), 1.0);
+
+    let mut out = [0i16; 2];
+    audio.render(&mut out);
+    assert_eq!(out, [5000, 5000]);
+}
+
+#[test]
+fn test_mixer_capture() {
+    let path = std::env::temp_dir().join("pal_test_mixer_capture.wav");
+    let sink = open_audio_sink(&AudioOutput::Wav(path.clone()), SAMPLE_RATE).unwrap();
+
+    let mut audio = Audio::new(SAMPLE_RATE);
+    audio.play_sound(vec![1000; 256].into(), 1.0);
+    let mut mixer = Mixer::start(audio, sink);
+    thread::sleep(Duration::from_millis(100));
+    mixer.stop().unwrap();
+    assert!(mixer.stop().is_err());
+
+    // 混音按实际时间的节奏进行，不会一下子写出很多
+    let buf = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let data_size = read_u32(&buf, 40) as usize;
+    assert_eq!(buf.len(), 44 + data_size);
+    assert!(data_size >= 256 * 2);
+    assert!(data_size < (SAMPLE_RATE as usize) * 2);
+    assert_eq!(&buf[44..46], &1000i16.to_le_bytes());
+}
+
+#[test]
+fn test_null_sink() {
+    let mut sink = open_audio_sink(&AudioOutput::Null, 8000).unwrap();
+    assert_eq!(sink.sample_rate(), 8000);
+    sink.write(&[0; 16]).unwrap();
+    sink.finish().unwrap();
+}
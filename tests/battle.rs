@@ -0,0 +1,54 @@
+use pal::battle::*;
+use pal::data::*;
+
+#[test]
+fn test_base_damage() {
+    assert_eq!(calc_base_damage(100, 50), 120);
+    // 攻击不高于防御时伤害较低
+    assert_eq!(calc_base_damage(50, 60), 14);
+    assert_eq!(calc_base_damage(10, 60), 0);
+
+    assert_eq!(calc_physical_attack_damage(100, 50, 2), 60);
+    assert_eq!(calc_physical_attack_damage(100, 50, 0), 120);
+}
+
+#[test]
+fn test_magic_damage() {
+    let mut magic = Magic { base_damage: 50, ..Magic::default() };
+    let mut battle_field = BattleField::default();
+    let resistance = [5, 0, 0, 0, 0];
+
+    // 无属性
+    assert_eq!(calc_magic_damage(100, 50, &resistance, 10, 1, &magic, &battle_field), 80);
+
+    // 属性抗性减半，战场加成 50%
+    magic.elemental = 1;
+    assert_eq!(calc_magic_damage(100, 50, &resistance, 10, 1, &magic, &battle_field), 80);
+    battle_field.magic_effect[0] = 5;
+    assert_eq!(calc_magic_damage(100, 50, &resistance, 10, 1, &magic, &battle_field), 120);
+
+    // 毒系只看毒抗性，不受战场影响
+    magic.elemental = (NUM_MAGIC_ELEMENTAL as u16) + 1;
+    assert_eq!(calc_magic_damage(100, 50, &resistance, 10, 1, &magic, &battle_field), 0);
+    assert_eq!(calc_magic_damage(100, 50, &resistance, 0, 1, &magic, &battle_field), 160);
+}
+
+#[test]
+fn test_turn_order() {
+    let speeds = [
+        (Actor::Player(0), 30),
+        (Actor::Enemy(0), 50),
+        (Actor::Player(1), i32::MAX),
+        (Actor::Enemy(1), 30),
+    ];
+
+    assert_eq!(turn_order(&speeds), vec![Actor::Player(1), Actor::Enemy(0), Actor::Player(0), Actor::Enemy(1)]);
+}
+
+#[test]
+fn test_battle_result_from_script() {
+    assert_eq!(BattleResult::from_u16(3), BattleResult::Won);
+    assert_eq!(BattleResult::from_u16(1), BattleResult::Lost);
+    assert_eq!(BattleResult::from_u16(0xffff), BattleResult::Fled);
+    assert_eq!(BattleResult::from_u16(0), BattleResult::Terminated);
+}
@@ -0,0 +1,114 @@
+use pal::audio::MusicType;
+use pal::config::*;
+use pal::input::*;
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn test_config_round_trip() {
+    let mut config = Config::default();
+    config.window_scale = 4;
+    config.music_type = MusicType::Midi;
+    config.audio_output = AudioOutput::Wav("capture.wav".into());
+    config.volume.music = 0.25;
+
+    let text = config.to_toml().unwrap();
+    assert_eq!(Config::from_toml(&text).unwrap(), config);
+}
+
+#[test]
+fn test_partial_config() {
+    let config = Config::from_toml("skip_intro = true\n[volume]\nsound = 0.5\n").unwrap();
+    assert!(config.skip_intro);
+    assert_eq!(config.volume.sound, 0.5);
+    assert_eq!(config.volume.master, 1.0);
+    assert_eq!(config.window_scale, 2);
+    assert_eq!(config.keys, default_key_names());
+
+    // 只列出部分按键时，其余按键保持默认
+    let config = Config::from_toml("[keys]\nflee = [\"F12\"]\n[gamepad]\nmenu = []\n").unwrap();
+    assert_eq!(config.keys["flee"], vec!["F12".to_string()]);
+    assert_eq!(config.keys["search"], default_key_names()["search"]);
+    assert_eq!(config.keys.len(), default_key_names().len());
+    assert!(config.gamepad["menu"].is_empty());
+    assert_eq!(config.gamepad["search"], default_gamepad_names()["search"]);
+
+    assert!(Config::from_toml("window_scale = 3").is_err());
+    assert!(Config::from_toml("[volume]\nmaster = 2.0").is_err());
+    assert!(Config::from_toml("[keys]\njump = [\"Space\"]").is_err());
+    assert!(Config::from_toml("[keys]\nsearch = [\"Hyper\"]").is_err());
+}
+
+#[test]
+fn test_command_line_overrides() {
+    let mut config = Config::default();
+    config.apply_args(args(&[
+        "--data-dir", "/games/pal",
+        "--scale", "1",
+        "--skip-intro",
+        "--music", "midi",
+        "--no-sound",
+        "--music-volume", "0.5",
+        "--capture-wav", "out.wav",
+    ])).unwrap();
+    assert_eq!(config.data_dir, std::path::PathBuf::from("/games/pal"));
+    assert_eq!(config.window_scale, 1);
+    assert!(config.skip_intro);
+    assert_eq!(config.music_type, MusicType::Midi);
+    assert!(!config.sound_enabled);
+    assert_eq!(config.volume.music, 0.5);
+    assert_eq!(config.audio_output, AudioOutput::Wav("out.wav".into()));
+
+    assert!(Config::default().apply_args(args(&["--scale"])).is_err());
+    assert!(Config::default().apply_args(args(&["--scale", "x"])).is_err());
+    assert!(Config::default().apply_args(args(&["--fullscreen"])).is_err());
+}
+
+#[test]
+fn test_load_and_save() {
+    let dir = std::env::temp_dir().join("pal_test_config");
+    let path = dir.join("config.toml");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    // 文件不存在时使用默认设置，也不会创建文件
+    let config = Config::from_args(args(&["--config", path.to_str().unwrap(), "--scale", "8"])).unwrap();
+    assert_eq!(config.path, None);
+    assert_eq!(config.window_scale, 8);
+    config.save_setting("music_enabled", false).unwrap();
+    assert!(!path.exists());
+
+    // 只写回修改的一项，命令行参数和回放等运行时的设置不写入
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(&path, "window_scale = 4\nskip_intro = true\n").unwrap();
+    let config = Config::from_args(args(&["--config", path.to_str().unwrap(), "--scale", "8", "--replay", "a.rec"])).unwrap();
+    assert_eq!(config.path.as_deref(), Some(path.as_path()));
+    assert_eq!(config.window_scale, 8);
+    config.save_setting("music_enabled", false).unwrap();
+
+    let loaded = Config::load(&path).unwrap();
+    assert_eq!(loaded.window_scale, 4);
+    assert!(loaded.skip_intro && !loaded.music_enabled && !loaded.headless);
+    assert_eq!(loaded.audio_output, Config::default().audio_output);
+    assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_key_bindings() {
+    let bindings = key_bindings(&default_key_names()).unwrap();
+    assert_eq!(bindings.len(), 33);
+    assert!(bindings.contains(&KeyBinding { code: minifb::Key::NumPad8, key: PalKey::Up }));
+
+    let mut names = default_key_names();
+    names.insert("flee".to_string(), vec!["escape".to_string(), "F12".to_string()]);
+    let bindings = key_bindings(&names).unwrap();
+    assert!(bindings.contains(&KeyBinding { code: minifb::Key::Escape, key: PalKey::Flee }));
+    assert!(bindings.contains(&KeyBinding { code: minifb::Key::F12, key: PalKey::Flee }));
+    assert!(!bindings.contains(&KeyBinding { code: minifb::Key::Q, key: PalKey::Flee }));
+
+    assert_eq!(PalKey::from_name("UseItem"), None);
+    assert_eq!(PalKey::from_name("use_item"), Some(PalKey::UseItem));
+    assert_eq!(key_from_name("numpadenter"), Some(minifb::Key::NumPadEnter));
+}
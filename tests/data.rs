@@ -0,0 +1,41 @@
+use pal::data::*;
+use pal::utils::encode_c_structs;
+
+#[test]
+fn test_table_bounds() {
+    let buf: Vec<u8> = [3u16, 5, 8].iter().flat_map(|v| v.to_le_bytes()).collect();
+    let table = Table::<u16>::decode(&buf).unwrap();
+
+    assert_eq!(table.len(), 3);
+    assert_eq!(table.get(2), Some(&8));
+    assert_eq!(table.get(3), None);
+    assert_eq!(table.as_slice(), &[3, 5, 8]);
+
+    assert!(Table::<u16>::decode(&[]).unwrap().is_empty());
+}
+
+#[test]
+fn test_record_sizes() {
+    let size = |buf: Vec<u8>| buf.len();
+
+    assert_eq!(size(encode_c_structs(&[Store::default()]).unwrap()), 18);
+    assert_eq!(size(encode_c_structs(&[Enemy::default()]).unwrap()), 70);
+    assert_eq!(size(encode_c_structs(&[EnemyTeam::default()]).unwrap()), 10);
+    assert_eq!(size(encode_c_structs(&[PlayerRoles::default()]).unwrap()), 900);
+    assert_eq!(size(encode_c_structs(&[Magic::default()]).unwrap()), 32);
+    assert_eq!(size(encode_c_structs(&[BattleField::default()]).unwrap()), 12);
+    assert_eq!(size(encode_c_structs(&[LevelUpMagicAll::default()]).unwrap()), 20);
+    assert_eq!(size(encode_c_structs(&[EnemyPos::default()]).unwrap()), 100);
+}
+
+#[test]
+fn test_decode_enemy_team() {
+    let teams = [
+        EnemyTeam { enemies: [0x18e, 0x18e, 0xffff, 0xffff, 0xffff] },
+        EnemyTeam { enemies: [0x190, 0xffff, 0xffff, 0xffff, 0xffff] },
+    ];
+    let table = Table::<EnemyTeam>::decode(&encode_c_structs(&teams).unwrap()).unwrap();
+
+    assert_eq!(table.as_slice(), &teams);
+    assert_eq!(table.get(1).unwrap().enemies[0], 0x190);
+}
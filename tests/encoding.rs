@@ -0,0 +1,64 @@
+use encoding_rs::{ BIG5, GBK };
+use pal::config::*;
+use pal::encoding::*;
+
+const TRADITIONAL: &str = "李逍遙自幼父母雙亡，由嬸嬸撫養長大。這一天，客棧裡來了一位苗族少女，\
+    說要找一種叫做水靈珠的東西。逍遙不知道的是，一段奇妙的旅程就要開始了。";
+const SIMPLIFIED: &str = "李逍遥自幼父母双亡，由婶婶抚养长大。这一天，客栈里来了一位苗族少女，\
+    说要找一种叫做水灵珠的东西。逍遥不知道的是，一段奇妙的旅程就要开始了。";
+
+#[test]
+fn test_explicit_encoding() {
+    let big5 = BIG5.encode(TRADITIONAL).0;
+    let gbk = GBK.encode(SIMPLIFIED).0;
+    assert_eq!(TextEncoding::Big5.resolve(&[&gbk]), BIG5);
+    assert_eq!(TextEncoding::Gbk.resolve(&[&big5]), GBK);
+
+    assert_eq!(TextEncoding::Auto.resolve(&[&big5[..40], &big5[40..]]), BIG5);
+    assert_eq!(TextEncoding::Auto.resolve(&[&gbk]), GBK);
+}
+
+#[test]
+fn test_find_undecodable() {
+    let mut bytes = BIG5.encode("仙劍").0.into_owned();
+    assert!(find_undecodable(BIG5, &bytes).is_empty());
+
+    // 0x80 不是 Big5 的首字节，结尾只有半个字
+    bytes.insert(2, 0x80);
+    bytes.push(0xa4);
+    assert_eq!(find_undecodable(BIG5, &bytes), vec![(2, vec![0x80]), (5, vec![0xa4])]);
+}
+
+#[test]
+fn test_decode_report() {
+    let words: Vec<&[u8]> = vec![b"ok", b"\xff\xff"];
+    let msgs: Vec<&[u8]> = vec![b"fine", b"", b"a\x80b"];
+    let mut issues = check_entries(BIG5, TextSource::Word, words);
+    issues.extend(check_entries(BIG5, TextSource::Message, msgs));
+    assert_eq!(issues[0], DecodeIssue { source: TextSource::Word, index: 1, offset: 0, bytes: vec![0xff] });
+    assert_eq!(issues.last().unwrap(), &DecodeIssue {
+        source: TextSource::Message,
+        index: 2,
+        offset: 1,
+        bytes: vec![0x80],
+    });
+
+    let report = format_report(BIG5, &issues);
+    assert!(report.starts_with("encoding: Big5\n"));
+    assert!(report.contains("message 2 offset 1: 80\n"));
+    assert!(report.ends_with(&format!("{} undecodable sequences\n", issues.len())));
+}
+
+#[test]
+fn test_encoding_option() {
+    assert_eq!(Config::default().text_encoding, TextEncoding::Auto);
+
+    let config = Config::from_toml("text_encoding = \"gbk\"").unwrap();
+    assert_eq!(config.text_encoding, TextEncoding::Gbk);
+
+    let mut config = Config::default();
+    config.apply_args(["--encoding", "Big5", "--check-text"].iter().map(|arg| arg.to_string())).unwrap();
+    assert_eq!(config.text_encoding, TextEncoding::Big5);
+    assert!(config.check_text && config.headless);
+    assert!(Config::default().apply_args(["--encoding", "latin1"].iter().map(|arg| arg.to_string())).is_err());
+}
@@ -0,0 +1,99 @@
+use pal::data::*;
+use pal::encounter::*;
+
+fn new_state() -> GameState {
+    let mut state = GameState::new(vec![Object::default(); MAX_OBJECTS], Vec::new(), Vec::new());
+    state.scene_num = 3;
+    state
+}
+
+#[test]
+fn test_no_encounter() {
+    let mut state = new_state();
+    for _ in 0..100 {
+        assert_eq!(state.encounter_step(), None);
+    }
+
+    // 没有敌人队伍或遇敌率为 0 时不遇敌
+    state.set_encounter(3, Encounter { rate: 1, enemy_teams: Vec::new() });
+    assert_eq!(state.encounter_step(), None);
+    state.set_encounter(3, Encounter { rate: 0, enemy_teams: vec![5] });
+    assert_eq!(state.encounter_step(), None);
+
+    // 只对设置的场景有效
+    state.set_encounter(4, Encounter { rate: 1, enemy_teams: vec![5] });
+    assert_eq!(state.encounter_step(), None);
+}
+
+#[test]
+fn test_encounter_rate() {
+    let mut state = new_state();
+
+    // 每一步都遇敌
+    state.set_encounter(3, Encounter { rate: 1, enemy_teams: vec![5] });
+    for _ in 0..10 {
+        assert_eq!(state.encounter_step(), Some(5));
+    }
+
+    // 遇敌间隔在 5～15 步之间
+    state.set_encounter(3, Encounter { rate: 10, enemy_teams: vec![5, 6] });
+    let mut steps = 0;
+    let mut intervals = Vec::new();
+    while intervals.len() < 20 {
+        steps += 1;
+        if let Some(team) = state.encounter_step() {
+            assert!(team == 5 || team == 6);
+            intervals.push(steps);
+            steps = 0;
+        }
+    }
+    assert!(intervals.iter().all(|&steps| (5..=15).contains(&steps)));
+}
+
+#[test]
+fn test_encounter_table_file() {
+    let table = parse_encounter_table("
+[[scene]]
+scene = 3
+rate = 30
+enemy_teams = [5, 6]
+
+[[scene]]
+scene = 7
+rate = 0
+").unwrap();
+    assert_eq!(table[&3], Encounter { rate: 30, enemy_teams: vec![5, 6] });
+    assert_eq!(table[&7], Encounter { rate: 0, enemy_teams: Vec::new() });
+    assert!(parse_encounter_table("").unwrap().is_empty());
+
+    assert!(parse_encounter_table("[[scene]]\nscene = 3\nrate = 1\n[[scene]]\nscene = 3\nrate = 2\n").is_err());
+}
+
+#[test]
+fn test_encounter_table_in_save() {
+    let mut table = EncounterTable::new();
+    table.insert(3, Encounter { rate: 30, enemy_teams: vec![5, 6] });
+    table.insert(1, Encounter { rate: 10, enemy_teams: Vec::new() });
+
+    let mut buf = vec![0xaa; 40];
+    buf.extend(encode_encounter_table(&table));
+    let (rest, loaded) = split_encounter_table(&buf).unwrap();
+    assert_eq!(rest, &[0xaa; 40][..]);
+    assert_eq!(loaded, Some(table));
+
+    // 原版存档没有随机遇敌表
+    assert_eq!(split_encounter_table(&[0xaa; 40]).unwrap(), (&[0xaa; 40][..], None));
+    let mut broken = vec![0xaa; 8];
+    broken.extend(100u32.to_le_bytes());
+    broken.extend(b"ENCT");
+    assert!(split_encounter_table(&broken).is_err());
+
+    // 随机遇敌表随存档保存和读取
+    let mut state = new_state();
+    state.set_encounter(3, Encounter { rate: 1, enemy_teams: vec![5] });
+    let buf = state.to_rpg().unwrap();
+    let mut loaded = new_state();
+    loaded.load_rpg(&buf).unwrap();
+    assert_eq!(loaded.encounters, state.encounters);
+    assert_eq!(loaded.to_rpg().unwrap(), buf);
+}
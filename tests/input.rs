@@ -0,0 +1,93 @@
+use pal::gamepad::*;
+use pal::input::*;
+use pal::utils::Dir;
+
+fn keyboard_only() -> InputState {
+    let bindings = vec![
+        KeyBinding { code: minifb::Key::Up, key: PalKey::Up },
+        KeyBinding { code: minifb::Key::Left, key: PalKey::Left },
+        KeyBinding { code: minifb::Key::Space, key: PalKey::Search },
+    ];
+    InputState::with_bindings(bindings, Vec::new())
+}
+
+#[test]
+fn test_key_repeat() {
+    let mut input = keyboard_only();
+    let search = [(PalKey::Up, false), (PalKey::Left, false), (PalKey::Search, true)];
+
+    input.update_keys(&search, 1000);
+    assert!(input.is_pressed(PalKey::Search));
+
+    // 按住时 200ms 后开始重复，之后每 75ms 一次
+    for (ticks, pressed) in [(1100, false), (1201, true), (1250, false), (1277, true)] {
+        input.key_press = 0;
+        input.update_keys(&search, ticks);
+        assert_eq!(input.is_pressed(PalKey::Search), pressed, "ticks {}", ticks);
+    }
+}
+
+#[test]
+fn test_direction_priority() {
+    let mut input = keyboard_only();
+    input.update_keys(&[(PalKey::Up, true), (PalKey::Left, false), (PalKey::Search, false)], 10);
+    assert_eq!(input.dir, Dir::North);
+
+    // 后按下的方向优先，松开后回到之前仍按着的方向
+    input.update_keys(&[(PalKey::Up, true), (PalKey::Left, true), (PalKey::Search, false)], 20);
+    assert_eq!(input.dir, Dir::West);
+    input.update_keys(&[(PalKey::Up, true), (PalKey::Left, false), (PalKey::Search, false)], 30);
+    assert_eq!(input.dir, Dir::North);
+    input.update_keys(&[(PalKey::Up, false), (PalKey::Left, false), (PalKey::Search, false)], 40);
+    assert_eq!(input.dir, Dir::Unknown);
+}
+
+#[test]
+fn test_gamepad_state() {
+    let mut state = GamepadState::default();
+    state.set_button(GamepadInput::South, true);
+    state.stick_x = -0.8;
+    state.stick_y = 0.3;
+    assert!(state.is_down(GamepadInput::South));
+    assert!(!state.is_down(GamepadInput::East));
+    assert!(state.is_down(GamepadInput::StickLeft));
+    assert!(!state.is_down(GamepadInput::StickUp));
+
+    state.set_button(GamepadInput::South, false);
+    assert!(!state.is_down(GamepadInput::South));
+}
+
+#[test]
+fn test_gamepad_shares_direction_logic() {
+    let bindings = gamepad_bindings(&default_gamepad_names()).unwrap();
+    let mut input = InputState::with_bindings(Vec::new(), bindings.clone());
+
+    // 摇杆和十字键都映射到同一组方向
+    let mut state = GamepadState { stick_y: 1.0, ..GamepadState::default() };
+    let keys = |state: &GamepadState| -> Vec<(PalKey, bool)> {
+        bindings.iter().map(|binding| (binding.key, state.is_down(binding.input))).collect()
+    };
+    input.update_keys(&keys(&state), 10);
+    assert_eq!(input.dir, Dir::North);
+
+    state.set_button(GamepadInput::DPadRight, true);
+    state.set_button(GamepadInput::South, true);
+    input.update_keys(&keys(&state), 20);
+    assert_eq!(input.dir, Dir::East);
+    assert!(input.is_pressed(PalKey::Search));
+}
+
+#[test]
+fn test_gamepad_bindings() {
+    let names = default_gamepad_names();
+    assert_eq!(names["menu"], vec!["east".to_string(), "start".to_string()]);
+
+    let mut names = names;
+    names.insert("flee".to_string(), vec!["right_trigger2".to_string()]);
+    let bindings = gamepad_bindings(&names).unwrap();
+    assert!(bindings.contains(&GamepadBinding { input: GamepadInput::RightTrigger2, key: PalKey::Flee }));
+
+    names.insert("flee".to_string(), vec!["turbo".to_string()]);
+    assert!(gamepad_bindings(&names).is_err());
+    assert_eq!(GamepadInput::from_name("DPad_Up"), Some(GamepadInput::DPadUp));
+}
@@ -0,0 +1,79 @@
+use pal::data::*;
+use pal::inventory::*;
+
+fn new_state() -> GameState {
+    let mut objects = vec![Object::default(); MAX_OBJECTS];
+    objects[0x3d] = Object::from_words([0, 100, 0, 0, 0, ITEM_FLAG_USABLE | ITEM_FLAG_CONSUMING]);
+    objects[0x3e] = Object::from_words([0, 200, 0, 0, 0, ITEM_FLAG_EQUIPABLE]);
+    objects[0x3f] = Object::from_words([0, 300, 0, 0, 0, ITEM_FLAG_THROWABLE]);
+
+    let mut state = GameState::new(objects, Vec::new(), Vec::new());
+    state.max_party_member_index = 1;
+    state.party[1].player_role = 3;
+    state
+}
+
+#[test]
+fn test_add_and_remove_items() {
+    let mut state = new_state();
+
+    assert!(state.add_item_to_inventory(0x3d, 0));
+    assert!(state.add_item_to_inventory(0x3d, 2));
+    assert!(state.add_item_to_inventory(0x3e, 1));
+    assert_eq!(state.item_amount(0x3d), 3);
+
+    assert!(state.add_item_to_inventory(0x3d, 200));
+    assert_eq!(state.item_amount(0x3d), MAX_ITEM_AMOUNT);
+
+    assert!(!state.add_item_to_inventory(0x3f, -1));
+    assert!(!state.add_item_to_inventory(0x3e, -2));
+    assert_eq!(state.item_amount(0x3e), 0);
+
+    state.compress_inventory();
+    assert_eq!(state.inventory[0].item, 0x3d);
+    assert_eq!(state.inventory[1].item, 0);
+    assert_eq!(state.inventory.len(), MAX_INVENTORY);
+}
+
+#[test]
+fn test_inventory_full() {
+    let mut state = new_state();
+    for i in 0..MAX_INVENTORY {
+        state.inventory[i] = Inventory { item: 0x100 + (i as u16), amount: 1, amount_in_use: 0 };
+    }
+
+    assert!(!state.add_item_to_inventory(0x3d, 1));
+    assert!(state.add_item_to_inventory(0x100, 1));
+}
+
+#[test]
+fn test_items_with_flag() {
+    let mut state = new_state();
+    state.add_item_to_inventory(0x3d, 1);
+    state.add_item_to_inventory(0x3e, 1);
+    state.add_item_to_inventory(0x3f, 1);
+
+    let items: Vec<u16> = state.items_with_flag(ITEM_FLAG_USABLE | ITEM_FLAG_THROWABLE)
+        .iter()
+        .map(|item| item.item)
+        .collect();
+    assert_eq!(items, vec![0x3d, 0x3f]);
+}
+
+#[test]
+fn test_equipped_items() {
+    let mut state = new_state();
+    state.player_roles.equipment[2][3] = 0x3e;
+
+    assert!(state.is_item_equipped(0x3e));
+    assert_eq!(state.remove_equipped_item(0x3e, 2), 1);
+    assert!(!state.is_item_equipped(0x3e));
+}
+
+#[test]
+fn test_equipable_by_role() {
+    let item = ObjectItem { flags: ITEM_FLAG_EQUIPABLE_BY_PLAYER_ROLE_FIRST << 2, ..Default::default() };
+
+    assert!(item.is_equipable_by(2));
+    assert!(!item.is_equipable_by(0));
+}
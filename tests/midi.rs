@@ -1,13 +1,135 @@
-use std::fs;
-
+use pal::audio::*;
+use pal::midi::*;
 use pal::utils::open_mkf;
 
+const RATE: u32 = 44100;
+
+fn chunk(id: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut buf = id.to_vec();
+    buf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    buf.extend_from_slice(data);
+    buf
+}
+
+// format 1：第一个音轨放速度，第二个音轨用 running status 奏两个音符
+fn synthetic_midi() -> Vec<u8> {
+    let mut buf = chunk(b"MThd", &[0, 1, 0, 2, 0, 96]);
+    // 开头 120 BPM，第 96 tick 起改为 60 BPM
+    buf.extend(chunk(b"MTrk", &[
+        0x00, 0xff, 0x51, 0x03, 0x07, 0xa1, 0x20,
+        0x60, 0xff, 0x51, 0x03, 0x0f, 0x42, 0x40,
+        0x00, 0xff, 0x2f, 0x00,
+    ]));
+    buf.extend(chunk(b"MTrk", &[
+        0x00, 0xc0, 0x00,
+        0x00, 0x90, 60, 100,
+        0x60, 60, 0, // running status，力度 0 即松开
+        0x00, 64, 100,
+        0x60, 0x80, 64, 0,
+        0x00, 0xff, 0x2f, 0x00,
+    ]));
+    buf
+}
+
+#[test]
+fn test_parse_running_status() {
+    let midi = MidiFile::parse(&synthetic_midi()).unwrap();
+    assert_eq!(midi.format, 1);
+    assert_eq!(midi.division, 96);
+    assert_eq!(midi.tracks.len(), 2);
+
+    let notes: Vec<_> = midi.tracks[1]
+        .iter()
+        .filter(|event| !matches!(event.message, MidiMessage::ProgramChange { .. } | MidiMessage::EndOfTrack))
+        .copied()
+        .collect();
+    assert_eq!(notes, vec![
+        MidiEvent { tick: 0, message: MidiMessage::NoteOn { channel: 0, key: 60, velocity: 100 } },
+        MidiEvent { tick: 96, message: MidiMessage::NoteOff { channel: 0, key: 60 } },
+        MidiEvent { tick: 96, message: MidiMessage::NoteOn { channel: 0, key: 64, velocity: 100 } },
+        MidiEvent { tick: 192, message: MidiMessage::NoteOff { channel: 0, key: 64 } },
+    ]);
+}
+
+#[test]
+fn test_tempo_change_timing() {
+    let midi = MidiFile::parse(&synthetic_midi()).unwrap();
+    let offs: Vec<f64> = midi
+        .timeline()
+        .iter()
+        .filter(|(_, message)| matches!(message, MidiMessage::NoteOff { .. }))
+        .map(|&(seconds, _)| seconds)
+        .collect();
+
+    // 第一拍 0.5 秒，速度减半后第二拍 1 秒
+    assert_eq!(offs.len(), 2);
+    assert!((offs[0] - 0.5).abs() < 1e-9);
+    assert!((offs[1] - 1.5).abs() < 1e-9);
+}
+
+#[test]
+fn test_reject_invalid() {
+    assert!(MidiFile::parse(b"RIFF0000").is_err());
+    // 没有前导状态字节的 running status
+    let mut buf = chunk(b"MThd", &[0, 0, 0, 1, 0, 96]);
+    buf.extend(chunk(b"MTrk", &[0x00, 60, 100]));
+    assert!(MidiFile::parse(&buf).is_err());
+}
+
+#[test]
+fn test_render_midi() {
+    let samples = render_midi(&synthetic_midi(), 3.0, RATE).unwrap();
+    // 最后一个音符在 1.5 秒松开，释音结束后停止
+    assert!(samples.len() > RATE as usize * 3 / 2);
+    assert!(samples.len() < RATE as usize * 3);
+    assert!(samples[..RATE as usize].iter().any(|&s| s.abs() > 1000));
+}
+
+#[test]
+fn test_midi_looping() {
+    let mut player = MidiPlayer::new(&synthetic_midi(), true, RATE).unwrap();
+    assert!((player.duration() - 1.5).abs() < 1e-9);
+
+    let mut out = vec![0; RATE as usize * 4];
+    assert_eq!(player.render(&mut out), out.len());
+    assert!(!player.is_finished());
+    // 循环后的第二遍也有声音
+    assert!(out[RATE as usize * 2..RATE as usize * 5 / 2].iter().any(|&s| s.abs() > 1000));
+}
+
+#[test]
+fn test_zero_length_loop() {
+    // 所有事件都在开头：只有速度和结束标记时不会一直重复
+    let mut buf = chunk(b"MThd", &[0, 0, 0, 1, 0, 96]);
+    buf.extend(chunk(b"MTrk", &[0x00, 0xff, 0x51, 0x03, 0x07, 0xa1, 0x20, 0x00, 0xff, 0x2f, 0x00]));
+    let mut player = MidiPlayer::new(&buf, true, RATE).unwrap();
+    let mut out = vec![0; 1024];
+    assert_eq!(player.render(&mut out), 0);
+
+    // 开头奏响的音符释音完后重新开始
+    let mut buf = chunk(b"MThd", &[0, 0, 0, 1, 0, 96]);
+    buf.extend(chunk(b"MTrk", &[0x00, 0x90, 60, 100, 0x00, 0x80, 60, 0, 0x00, 0xff, 0x2f, 0x00]));
+    let mut player = MidiPlayer::new(&buf, true, RATE).unwrap();
+    assert_eq!(player.render(&mut out), out.len());
+}
+
+#[test]
+fn test_smpte_division() {
+    // -128 帧/秒不会溢出，按 128 帧处理
+    let mut buf = chunk(b"MThd", &[0, 0, 0, 1, 0x80, 4]);
+    buf.extend(chunk(b"MTrk", &[0x00, 0x90, 60, 100, 0x81, 0x00, 0x80, 60, 0, 0x00, 0xff, 0x2f, 0x00]));
+    let timeline = MidiFile::parse(&buf).unwrap().timeline();
+    let (seconds, _) = timeline.iter().find(|(_, message)| matches!(message, MidiMessage::NoteOff { .. })).unwrap();
+    assert!((seconds - 128.0 / 512.0).abs() < 1e-9);
+}
 
 #[test]
 fn test_decompress_midi() {
     let mut midi_mkf = open_mkf("MIDI.MKF").unwrap();
     for i in 0..midi_mkf.chunk_count() {
         let chunk = midi_mkf.read_chunk(i).unwrap();
-        fs::write(format!("/tmp/midi/chunk_{}.mid", i), &chunk).unwrap();
+        if !chunk.is_empty() {
+            MidiFile::parse(&chunk).unwrap();
+        }
     }
-}
\ No newline at end of file
+}
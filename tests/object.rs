@@ -0,0 +1,52 @@
+use pal::data::*;
+
+#[test]
+fn test_object_kind() {
+    assert_eq!(ObjectKind::from_id(0x0023), ObjectKind::Other);
+    assert_eq!(ObjectKind::from_id(0x0024), ObjectKind::Player);
+    assert_eq!(ObjectKind::from_id(0x0029), ObjectKind::Player);
+    assert_eq!(ObjectKind::from_id(0x003d), ObjectKind::Item);
+    assert_eq!(ObjectKind::from_id(0x0126), ObjectKind::Item);
+    assert_eq!(ObjectKind::from_id(0x0127), ObjectKind::Magic);
+    assert_eq!(ObjectKind::from_id(0x018e), ObjectKind::Enemy);
+    assert_eq!(ObjectKind::from_id(0x0234), ObjectKind::Poison);
+    assert_eq!(ObjectKind::from_id(0x0235), ObjectKind::Other);
+}
+
+#[test]
+fn test_object_views() {
+    let mut object = Object::from_words([1, 2, 3, 4, 5, 6]);
+
+    let item = object.item();
+    assert_eq!(item.bitmap, 1);
+    assert_eq!(item.flags, 6);
+
+    // ObjectPlayer 只覆盖前 4 个字，其余的字保持不变
+    let mut player = object.player();
+    assert_eq!(player.script_on_dying, 4);
+    player.script_on_friend_death = 0x1234;
+    object.set_player(&player);
+    assert_eq!(object.words(), &[1, 2, 0x1234, 4, 5, 6]);
+
+    let mut enemy = object.enemy();
+    enemy.script_on_ready = 0xffff;
+    object.set_enemy(&enemy);
+    assert_eq!(object.words(), &[1, 2, 0x1234, 4, 0xffff, 6]);
+}
+
+#[test]
+fn test_typed_object_lookup() {
+    let objects = (0..MAX_OBJECTS).map(|i| Object::from_words([i as u16, 0, 0, 0, 0, 0])).collect();
+    let mut state = GameState::new(objects, Vec::new(), Vec::new());
+
+    assert_eq!(state.object_item(0x3d).unwrap().bitmap, 0x3d);
+    assert!(state.object_item(0x127).is_none());
+    assert_eq!(state.object_magic(0x127).unwrap().magic_number, 0x127);
+    assert!(state.object_poison(0x1000).is_none());
+
+    let object = state.object_of_kind_mut(0x3d, ObjectKind::Item).unwrap();
+    let mut item = object.item();
+    item.price = 100;
+    object.set_item(&item);
+    assert_eq!(state.object_item(0x3d).unwrap().price, 100);
+}
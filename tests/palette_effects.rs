@@ -0,0 +1,76 @@
+use pal::canvas::{ Canvas, Color, Palette };
+use pal::palette_effects::{ decode_palette, PaletteFade };
+
+fn gray_palette() -> Palette {
+    Palette::with_colors((0..256).map(|i| Color::from_rgb(i as u8, i as u8, i as u8)).collect())
+}
+
+#[test]
+fn test_fade_out_and_in() {
+    let pal = gray_palette();
+
+    let fade = PaletteFade::fade_out(&pal, 10);
+    assert_eq!(fade.frames().len(), 64);
+    assert_eq!(fade.duration(), 640);
+    assert!(fade.last().colors.iter().all(|&c| c == Color(0)));
+
+    let fade = PaletteFade::fade_in(&pal, 10);
+    assert_eq!(fade.palette_at(0).colors[200], Color::from_rgb(3, 3, 3));
+    assert_eq!(fade.palette_at(320).colors[200], Color::from_rgb(103, 103, 103));
+    assert_eq!(fade.palette_at(10_000).colors[200], Color::from_rgb(200, 200, 200));
+}
+
+#[test]
+fn test_fade_to_red() {
+    let pal = gray_palette();
+    let fade = PaletteFade::to_red(&pal);
+    let last = fade.last();
+
+    let (r, g, b) = last.colors[0x80].to_rgb();
+    assert_eq!((r, g, b), (160, 0, 0));
+
+    // 文字颜色不受影响
+    assert_eq!(last.colors[0x4f], pal.colors[0x4f]);
+}
+
+#[test]
+fn test_fade_to_color_headless() {
+    let mut canvas = Canvas::new(4, 1);
+    canvas.set_pixels(|pixels: &mut [u8]| {
+        pixels.copy_from_slice(&[0, 1, 2, 255]);
+    });
+
+    let pal = gray_palette();
+    let white = Color::from_rgb(255, 255, 255);
+    let fade = PaletteFade::to_color(&pal, white, 10);
+
+    canvas.set_palette(fade.palette_at(0));
+    assert_eq!(canvas.get_buffer()[3], white.0);
+
+    canvas.set_palette(fade.last());
+    assert!(canvas.get_buffer().iter().all(|&c| c == white.0));
+
+    let fade = PaletteFade::from_color(white, &pal, 10);
+    canvas.set_palette(fade.last());
+    assert_eq!(canvas.get_buffer(), &[0, 0x010101, 0x020202, 0xffffff]);
+}
+
+#[test]
+fn test_day_night_palette() {
+    let mut buf = vec![0u8; 256 * 3 * 2];
+    buf[0..3].copy_from_slice(&[63, 0, 0]);
+    buf[768..771].copy_from_slice(&[0, 0, 63]);
+
+    let day = decode_palette(&buf, false);
+    let night = decode_palette(&buf, true);
+    assert_eq!(day.colors[0], Color::from_rgb(252, 0, 0));
+    assert_eq!(night.colors[0], Color::from_rgb(0, 0, 252));
+
+    // 没有夜晚调色板时使用白天调色板
+    assert_eq!(decode_palette(&buf[..768], true).colors[0], day.colors[0]);
+
+    let fade = PaletteFade::day_night(&day, &night);
+    assert_eq!(fade.frames().len(), 32);
+    assert_eq!(fade.palette_at(1500).colors[0], Color::from_rgb(126, 0, 126));
+    assert_eq!(fade.last().colors[0], night.colors[0]);
+}
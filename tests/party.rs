@@ -0,0 +1,142 @@
+use pal::data::*;
+use pal::party::*;
+
+fn new_state() -> GameState {
+    let mut state = GameState::new(vec![Object::default(); MAX_OBJECTS], Vec::new(), Vec::new());
+    state.max_party_member_index = 1;
+    state.party[0].player_role = 0;
+    state.party[1].player_role = 2;
+    for role in 0..MAX_PLAYER_ROLES {
+        state.player_roles.max_hp[role] = 100;
+        state.player_roles.hp[role] = 50;
+        state.player_roles.max_mp[role] = 40;
+        state.player_roles.mp[role] = 20;
+    }
+    state
+}
+
+#[test]
+fn test_attribute_words() {
+    let mut state = new_state();
+
+    assert_eq!(state.player_roles.attribute(ATTR_HP, 2), 50);
+    state.player_roles.set_attribute(ATTR_ATTACK_STRENGTH, 2, 30);
+    assert_eq!(state.player_roles.attack_strength[2], 30);
+    assert_eq!(state.player_roles.attribute(ATTR_EQUIPMENT + 1, 2), state.player_roles.equipment[1][2]);
+
+    // 字段顺序与 WORD 数组一致
+    state.player_roles.set_attribute(ATTR_ELEMENTAL_RESISTANCE + 4, 2, 7);
+    assert_eq!(state.player_roles.elemental_resistance[4][2], 7);
+    state.player_roles.set_attribute(74, 2, 9);
+    assert_eq!(state.player_roles.dying_sound[2], 9);
+
+    // 越界访问被忽略
+    state.player_roles.set_attribute(1000, 2, 1);
+    assert_eq!(state.player_roles.attribute(1000, 2), 0);
+
+    // 装备效果计入属性
+    state.party_state.equipment_effect[BODY_PART_EXTRA].set_attribute(ATTR_ATTACK_STRENGTH, 2, 5);
+    assert_eq!(state.player(2).attack_strength, 35);
+}
+
+#[test]
+fn test_increase_hp_mp() {
+    let mut state = new_state();
+
+    assert!(state.increase_hp_mp(0, 80, -30));
+    assert_eq!(state.player_roles.hp[0], 100);
+    assert_eq!(state.player_roles.mp[0], 0);
+
+    state.player_roles.hp[2] = 0;
+    assert!(!state.increase_hp_mp(2, 10, 10));
+    assert_eq!(state.player_roles.hp[2], 0);
+}
+
+#[test]
+fn test_player_status() {
+    let mut state = new_state();
+
+    state.set_player_status(0, Status::Sleep as u16, 3);
+    state.set_player_status(0, Status::Sleep as u16, 5);
+    assert_eq!(state.player(0).status[Status::Sleep as usize], 3);
+
+    state.set_player_status(0, Status::Haste as u16, 3);
+    state.set_player_status(0, Status::Haste as u16, 5);
+    assert_eq!(state.player(0).status[Status::Haste as usize], 5);
+
+    // 傀儡状态只对阵亡的角色有效
+    state.set_player_status(0, Status::Puppet as u16, 3);
+    assert_eq!(state.player(0).status[Status::Puppet as usize], 0);
+
+    state.clear_player_status(0);
+    assert_eq!(state.player(0).status, [0; STATUS_ALL]);
+}
+
+#[test]
+fn test_learn_magic() {
+    let mut state = new_state();
+
+    assert!(state.add_magic(2, 0x127));
+    assert!(!state.add_magic(2, 0x127));
+    assert!(state.add_magic(2, 0x128));
+    assert_eq!(state.player(2).magics, vec![0x127, 0x128]);
+
+    state.remove_magic(2, 0x127);
+    assert_eq!(state.player(2).magics, vec![0x128]);
+}
+
+#[test]
+fn test_level_up() {
+    let mut state = new_state();
+    state.player_roles.level[0] = 98;
+    state.player_roles.flee_rate[0] = 998;
+
+    state.player_level_up(0, 3);
+    assert_eq!(state.player_roles.level[0], MAX_LEVELS as u16);
+    assert_eq!(state.player_roles.flee_rate[0], 999);
+    assert!(state.player_roles.max_hp[0] >= 130);
+    assert_eq!(state.exp.primary_exp[0].level, MAX_LEVELS as u16);
+}
+
+#[test]
+fn test_party_roles() {
+    let state = new_state();
+
+    assert_eq!(state.party_roles(), vec![0, 2]);
+    assert_eq!(state.party_index(2), Some(1));
+    assert_eq!(state.party_index(1), None);
+}
+
+#[test]
+fn test_decrease_status_rounds() {
+    let mut state = new_state();
+
+    state.set_player_status(0, Status::Sleep as u16, 2);
+    state.party_state.status[0][Status::Haste as usize] = 1000;
+    state.decrease_player_status_rounds(0);
+    assert_eq!(state.party_state.status[0][Status::Sleep as usize], 1);
+    state.decrease_player_status_rounds(0);
+    state.decrease_player_status_rounds(0);
+    assert_eq!(state.party_state.status[0][Status::Sleep as usize], 0);
+
+    // 装备带来的状态不减少
+    assert_eq!(state.party_state.status[0][Status::Haste as usize], 1000);
+}
+
+#[test]
+fn test_poison_by_kind() {
+    let mut state = new_state();
+
+    assert!(state.add_poison_for_player(2, 0x0200));
+    // 同种毒不重复
+    assert!(!state.add_poison_for_player(2, 0x0200));
+    assert!(state.add_poison_for_player(2, 0x0201));
+    // 不在队伍中的角色不会中毒
+    assert!(!state.add_poison_for_player(1, 0x0200));
+    assert_eq!(state.player(2).poisons.len(), 2);
+
+    state.cure_poison_by_kind(2, 0x0200);
+    let poisons = state.player(2).poisons;
+    assert_eq!(poisons.len(), 1);
+    assert_eq!(poisons[0].poison_id, 0x0201);
+}
@@ -0,0 +1,48 @@
+use pal::data::*;
+use pal::random::GameRng;
+
+#[test]
+fn test_same_seed_same_sequence() {
+    let mut a = GameRng::new(42);
+    let mut b = GameRng::new(42);
+    for _ in 0..100 {
+        assert_eq!(a.next_u32(), b.next_u32());
+    }
+
+    // 种子为 0 时也能正常工作
+    let mut zero = GameRng::new(0);
+    assert_ne!(zero.next_u32(), 0);
+}
+
+#[test]
+fn test_ranges() {
+    let mut rng = GameRng::new(7);
+    for _ in 0..1000 {
+        let n = rng.random_long(3, 5);
+        assert!((3..=5).contains(&n));
+        let f = rng.random_float(1.0, 1.125);
+        assert!((1.0..1.125).contains(&f));
+    }
+    assert_eq!(rng.random_long(4, 4), 4);
+    assert_eq!(rng.random_long(4, 2), 4);
+}
+
+#[test]
+fn test_restore_state() {
+    let mut rng = GameRng::new(1234);
+    rng.next_u32();
+    let mut restored = GameRng::new(1);
+    restored.set_state(rng.state());
+    assert_eq!(restored.random_long(0, 1000), rng.random_long(0, 1000));
+}
+
+#[test]
+fn test_level_up_is_deterministic() {
+    let level_up = |seed: u32| {
+        let mut state = GameState::new(vec![Object::default(); MAX_OBJECTS], Vec::new(), Vec::new());
+        state.rng = GameRng::new(seed);
+        state.player_level_up(0, 5);
+        state.player_roles
+    };
+    assert_eq!(level_up(99), level_up(99));
+}
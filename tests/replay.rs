@@ -0,0 +1,68 @@
+use pal::config::*;
+use pal::replay::*;
+
+fn sample_recording() -> Recording {
+    let mut events = Vec::new();
+    for _ in 0..500 {
+        events.push(ReplayEvent::Input { key_press: 0, dir: 4 });
+        events.push(ReplayEvent::Frames(1));
+    }
+    events.push(ReplayEvent::Input { key_press: 1 << 1, dir: 2 });
+    events.extend(std::iter::repeat_n(ReplayEvent::Input { key_press: 0, dir: 2 }, 300));
+    events.push(ReplayEvent::Frames(3));
+
+    Recording { rng_state: 0x1234_5678, save: vec![7; 100], events, final_hash: 0xdead_beef_0bad_f00d }
+}
+
+#[test]
+fn test_recording_round_trip() {
+    let recording = sample_recording();
+    let buf = recording.encode();
+    assert_eq!(Recording::decode(&buf).unwrap(), recording);
+
+    // 连续相同的事件合并存储
+    let compact = Recording { events: recording.events[1000..].to_vec(), ..recording.clone() };
+    assert!(compact.encode().len() < 200);
+}
+
+#[test]
+fn test_recording_file() {
+    let path = std::env::temp_dir().join("pal_test_recording.rec");
+    let recording = sample_recording();
+    recording.save(&path).unwrap();
+    assert_eq!(Recording::load(&path).unwrap(), recording);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_reject_invalid_recording() {
+    assert!(Recording::decode(b"RIFF").is_err());
+
+    let buf = sample_recording().encode();
+    assert!(Recording::decode(&buf[..buf.len() - 1]).is_err());
+
+    let mut bad_version = buf.clone();
+    bad_version[6] = 99;
+    assert!(Recording::decode(&bad_version).is_err());
+}
+
+#[test]
+fn test_state_hash_function() {
+    assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+    assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+}
+
+#[test]
+fn test_replay_options() {
+    let mut config = Config::default();
+    config.apply_args(["--replay", "bug.rec"].iter().map(|arg| arg.to_string())).unwrap();
+    assert_eq!(config.replay, Some("bug.rec".into()));
+    assert!(config.headless);
+    assert!(config.skip_intro);
+    assert_eq!(config.audio_output, AudioOutput::Null);
+
+    // 录像相关的选项不写入配置文件
+    let mut config = Config::default();
+    config.apply_args(["--record", "bug.rec"].iter().map(|arg| arg.to_string())).unwrap();
+    assert_eq!(Config::from_toml(&config.to_toml().unwrap()).unwrap().record, None);
+}
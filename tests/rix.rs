@@ -0,0 +1,127 @@
+use pal::audio::*;
+use pal::opl::Opl;
+use pal::rix::*;
+
+const RATE: u32 = 44100;
+
+// 一个音色、一个音符的 RIX：奏响约 0.26 秒后松开，再等待约 0.53 秒结束
+fn synthetic_rix() -> Vec<u8> {
+    let mut data = vec![0u8; 0x80];
+    data[0..2].copy_from_slice(&0x55aau16.to_le_bytes());
+    data[0x08..0x0a].copy_from_slice(&0x20u16.to_le_bytes());
+    data[0x0c..0x0e].copy_from_slice(&0x60u16.to_le_bytes());
+
+    // KSL, MULT, FB, AR, SL, EGT, DR, RR, TL, AM, VIB, KSR, CON
+    let modulator = [0, 1, 0, 15, 0, 1, 0, 15, 63, 0, 0, 0, 1];
+    let carrier = [0, 1, 0, 15, 0, 1, 0, 15, 0, 0, 0, 0, 1];
+    for (i, word) in modulator.iter().chain(carrier.iter()).enumerate() {
+        data[0x20 + i * 2..0x22 + i * 2].copy_from_slice(&(*word as u16).to_le_bytes());
+    }
+
+    let events: [(u8, u8); 6] = [(0, 0x90), (60, 0xc0), (0, 0x01), (0, 0xc0), (0, 0x02), (0, 0x80)];
+    for (i, (low, ctrl)) in events.iter().enumerate() {
+        data[0x60 + i * 2] = *low;
+        data[0x61 + i * 2] = *ctrl;
+    }
+
+    data
+}
+
+fn zero_crossings(samples: &[i16]) -> usize {
+    samples.windows(2).filter(|w| (w[0] < 0) != (w[1] < 0)).count()
+}
+
+#[test]
+fn test_reject_non_rix() {
+    assert!(Rix::new(vec![0; 0x80]).is_err());
+    assert!(Rix::new(vec![0xaa, 0x55]).is_err());
+}
+
+#[test]
+fn test_render_note() {
+    let samples = render_rix(synthetic_rix(), 2.0, RATE).unwrap();
+
+    // 乐曲在约 0.8 秒后结束
+    assert!(samples.len() > (RATE as usize) * 7 / 10);
+    assert!(samples.len() < (RATE as usize) * 9 / 10);
+
+    // 中央 C 附近的正弦波
+    let note = &samples[..(RATE as usize) / 5];
+    assert!(note.iter().any(|&s| s.abs() > 1000));
+    let crossings = zero_crossings(note);
+    assert!((90..=120).contains(&crossings), "{}", crossings);
+
+    // 松开后很快静音
+    let tail = &samples[samples.len() - (RATE as usize) / 5..];
+    assert!(tail.iter().all(|&s| s.abs() < 16));
+}
+
+#[test]
+fn test_looping_player() {
+    let mut player = RixPlayer::new(synthetic_rix(), true, RATE).unwrap();
+    let mut buf = vec![0; (RATE as usize) * 2];
+    assert_eq!(player.render(&mut buf), buf.len());
+    assert!(!player.is_finished());
+
+    // 第二遍重新奏响
+    let second = &buf[(RATE as usize) * 9 / 10..(RATE as usize) * 11 / 10];
+    assert!(second.iter().any(|&s| s.abs() > 1000));
+}
+
+#[test]
+fn test_opl_key_off() {
+    let mut opl = Opl::new(RATE);
+    // 通道 0 的载波：最快的起音和释放，总衰减为 0
+    opl.write(0x23, 0x21);
+    opl.write(0x43, 0x00);
+    opl.write(0x63, 0xf0);
+    opl.write(0x83, 0x0f);
+    opl.write(0x40, 0x3f);
+    opl.write(0xa0, 0x44);
+    opl.write(0xb0, 0x32);
+
+    let mut buf = vec![0; 4410];
+    opl.render(&mut buf);
+    assert!(buf.iter().any(|&s| s.abs() > 2000));
+
+    opl.write(0xb0, 0x12);
+    opl.render(&mut buf);
+    opl.render(&mut buf);
+    assert!(buf.iter().all(|&s| s == 0));
+}
+
+#[test]
+fn test_wav_header() {
+    let wav = encode_wav(&[1, -1, 2], RATE, 1);
+    assert_eq!(wav.len(), 44 + 6);
+    assert_eq!(&wav[0..4], b"RIFF");
+    assert_eq!(u32::from_le_bytes([wav[4], wav[5], wav[6], wav[7]]), 36 + 6);
+    assert_eq!(&wav[8..16], b"WAVEfmt ");
+    assert_eq!(u32::from_le_bytes([wav[24], wav[25], wav[26], wav[27]]), RATE);
+    assert_eq!(&wav[36..40], b"data");
+    assert_eq!(&wav[44..], &[1, 0, 0xff, 0xff, 2, 0]);
+}
+
+#[test]
+fn test_music_fade_out() {
+    let mut audio = Audio::new(RATE);
+    audio.play_music(Music::Rix(Box::new(RixPlayer::new(synthetic_rix(), true, RATE).unwrap())), 0.0);
+    let mut buf = vec![0; 4410];
+    audio.render(&mut buf);
+    assert!(audio.is_music_playing());
+
+    audio.stop_music(0.05);
+    audio.render(&mut buf);
+    assert!(!audio.is_music_playing());
+    // 淡出到末尾时接近无声
+    assert!(buf[buf.len() / 2..].iter().all(|&s| s.abs() < 1000));
+}
+
+#[test]
+fn test_out_of_range_instrument() {
+    // 反馈量超出 16 位的一半时按原版截断，不会溢出
+    let mut data = synthetic_rix();
+    data[0x24..0x26].copy_from_slice(&0x8001u16.to_le_bytes());
+    let samples = render_rix(data, 1.0, RATE).unwrap();
+    assert!(!samples.is_empty());
+}
@@ -0,0 +1,66 @@
+use pal::data::*;
+use pal::random::GameRng;
+use pal::utils::{ decode_c_structs, Pos };
+
+fn synthetic_state() -> GameState {
+    // DATA.MKF 中的场景表比存档多一项
+    let scenes = (0..=MAX_SCENES)
+        .map(|i| Scene { map_num: i as u16, event_object_index: (i * 2) as u16, ..Default::default() })
+        .collect();
+    let buf: Vec<u8> = (0..MAX_OBJECTS * 6).flat_map(|i| (i as u16).to_le_bytes()).collect();
+    let objects = decode_c_structs::<Object>(&buf).unwrap();
+    let event_objects = (0..10)
+        .map(|i| EventObject { x: (i * 16) as u16, y: (i * 8) as u16, ..Default::default() })
+        .collect();
+
+    GameState::new(objects, scenes, event_objects)
+}
+
+#[test]
+fn test_save_round_trip() {
+    let mut state = synthetic_state();
+    state.saved_times = 7;
+    state.viewport = Pos { x: -16, y: 320 };
+    state.scene_num = 12;
+    state.night_palette = true;
+    state.cash = 123456;
+    state.inventory[3] = Inventory { item: 0x3d, amount: 5, amount_in_use: 1 };
+    state.poison_status[2][1] = PoisonStatus { poison_id: 0x227, poison_script: 0x100 };
+    state.party[0].player_role = 2;
+    state.scenes[5].script_on_enter = 0x1234;
+    state.rng = GameRng::new(0x12345678);
+
+    let buf = state.to_rpg().unwrap();
+
+    let mut loaded = synthetic_state();
+    loaded.event_objects.clear();
+    loaded.load_rpg(&buf).unwrap();
+
+    assert_eq!(loaded.saved_times, 7);
+    assert_eq!(loaded.viewport, Pos { x: -16, y: 320 });
+    assert_eq!(loaded.scene_num, 12);
+    assert!(loaded.night_palette);
+    assert_eq!(loaded.cash, 123456);
+    assert_eq!(loaded.inventory, state.inventory);
+    assert_eq!(loaded.poison_status, state.poison_status);
+    assert_eq!(loaded.party, state.party);
+    assert_eq!(loaded.scenes, state.scenes);
+    assert_eq!(loaded.scenes.len(), MAX_SCENES + 1);
+    assert_eq!(loaded.objects, state.objects);
+    assert_eq!(loaded.event_objects, state.event_objects);
+    assert_eq!(loaded.rng, state.rng);
+
+    // 再次保存的内容完全相同
+    assert_eq!(loaded.to_rpg().unwrap(), buf);
+}
+
+#[test]
+fn test_save_layout() {
+    let state = synthetic_state();
+    let buf = state.to_rpg().unwrap();
+
+    // 头部 + 队伍 + 足迹 + 经验 + 角色属性 + 中毒 + 物品 + 场景 + 对象 + 事件对象
+    let size = 20 * 2 + 4 + 5 * 10 + 5 * 6 + 8 * 6 * 8 + 900 + 16 * 5 * 4 + 256 * 6 +
+        300 * 4 * 2 + 600 * 6 * 2 + 10 * 32;
+    assert_eq!(buf.len(), size);
+}
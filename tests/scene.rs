@@ -0,0 +1,42 @@
+use pal::data::*;
+
+fn new_state() -> GameState {
+    // 最后一项只用来确定最后一个场景的事件对象范围
+    let scenes = [0, 2, 5, 5, 7]
+        .iter()
+        .map(|&index| Scene { event_object_index: index, ..Default::default() })
+        .collect();
+    GameState::new(Vec::new(), scenes, vec![EventObject::default(); 7])
+}
+
+#[test]
+fn test_scene_event_objects() {
+    let mut state = new_state();
+    assert_eq!(state.scene_event_objects(), 0..2);
+    state.scene_num = 3;
+    assert!(state.scene_event_objects().is_empty());
+    state.scene_num = 4;
+    assert_eq!(state.scene_event_objects(), 5..7);
+}
+
+#[test]
+fn test_switch_scene() {
+    let mut state = new_state();
+    state.entering_scene = false;
+    state.need_load_scene = false;
+    state.layer = 1;
+
+    assert!(state.switch_scene(2));
+    assert_eq!(state.scene_num, 2);
+    assert!(state.entering_scene && state.need_load_scene);
+    assert_eq!(state.layer, 0);
+    assert_eq!(state.scene_event_objects(), 2..5);
+
+    // 无效的场景编号和当前场景不切换
+    state.need_load_scene = false;
+    assert!(!state.switch_scene(0));
+    assert!(!state.switch_scene(2));
+    assert!(!state.switch_scene(5));
+    assert!(!state.need_load_scene);
+    assert!(state.switch_scene(4));
+}
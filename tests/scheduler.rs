@@ -0,0 +1,35 @@
+use std::time::Instant;
+
+use pal::scheduler::{ Scheduler, FRAME_TIME };
+
+#[test]
+fn test_stepped_scheduler() {
+    let mut scheduler = Scheduler::stepped();
+
+    for _ in 0..10 {
+        assert_eq!(scheduler.frames_due(), 1);
+        scheduler.end_frame();
+        scheduler.wait_next_frame();
+    }
+    assert_eq!(scheduler.frame_count(), 10);
+    assert_eq!(scheduler.ticks(), 10 * FRAME_TIME);
+
+    // 不等待真实时间
+    let begin = Instant::now();
+    scheduler.delay(60_000);
+    assert!(begin.elapsed().as_millis() < 1000);
+    assert_eq!(scheduler.ticks(), 10 * FRAME_TIME + 60_000);
+    assert_eq!(scheduler.frame_count(), 10);
+}
+
+#[test]
+fn test_render_time_is_absorbed() {
+    let mut scheduler = Scheduler::new();
+
+    // 模拟渲染花费了 60 毫秒，等待 50 毫秒时不应再休眠
+    std::thread::sleep(std::time::Duration::from_millis(60));
+    let begin = Instant::now();
+    scheduler.delay(50);
+    assert!(begin.elapsed().as_millis() < 40);
+    assert_eq!(scheduler.ticks(), 50);
+}
@@ -0,0 +1,109 @@
+use pal::font::*;
+use pal::ui::*;
+
+#[test]
+fn test_glyph_index() {
+    let index = build_glyph_index(&['仙', '劍', '奇', '俠', '劍']);
+    assert_eq!(index.len(), 4);
+    assert_eq!(index[&'仙'], 0);
+    assert_eq!(index[&'劍'], 1);
+    assert_eq!(index.get(&'傳'), None);
+}
+
+#[test]
+fn test_ascii_glyphs() {
+    assert_eq!(ascii_glyph(' '), Some(&[0; 16]));
+    assert!(ascii_glyph('A').unwrap().iter().any(|&row| row != 0));
+    assert_eq!(ascii_glyph('\n'), None);
+    assert_eq!(ascii_glyph('仙'), None);
+}
+
+#[test]
+fn test_measure_text() {
+    assert_eq!(measure_text(""), 0);
+    assert_eq!(measure_text("李逍遙"), 48);
+    assert_eq!(measure_text("Lv 99"), 40);
+    assert_eq!(measure_text("坐標12，34"), 32 + 16 + 16 + 16);
+
+    assert_eq!(align_text(100, "李逍遙", TextAlign::Left), 100);
+    assert_eq!(align_text(100, "李逍遙", TextAlign::Mid), 76);
+    assert_eq!(align_text(100, "李逍遙", TextAlign::Right), 52);
+}
+
+#[test]
+fn test_wrap_text() {
+    // 全角字符可以在任意位置断开，但标点不出现在行首
+    assert_eq!(wrap_text("客官，要住店嗎？", 48), vec!["客官，", "要住店", "嗎？"]);
+
+    // ASCII 单词只在空格处断开
+    assert_eq!(wrap_text("Hello brave  new world", 80), vec!["Hello", "brave  new", "world"]);
+    assert_eq!(wrap_text("abcdefghij", 32), vec!["abcd", "efgh", "ij"]);
+    assert_eq!(wrap_text("李逍遙 Li Xiaoyao", 80), vec!["李逍遙 Li", "Xiaoyao"]);
+
+    assert!(wrap_text("   ", 64).is_empty());
+    for line in wrap_text("這是一段很長很長的對話，需要折成好幾行才能顯示完。", 160) {
+        assert!(measure_text(&line) <= 160);
+    }
+}
+
+const BDF: &str = "STARTFONT 2.1
+FONT test
+CHARS 3
+STARTCHAR eacute
+ENCODING 233
+BBX 4 3 1 -1
+BITMAP
+F0
+90
+60
+ENDCHAR
+STARTCHAR wide
+ENCODING 12354
+BBX 16 2 0 10
+BITMAP
+8001
+FFFF
+ENDCHAR
+STARTCHAR zhe
+ENCODING 1046
+BBX 12 1 2 0
+BITMAP
+FFF0
+ENDCHAR
+ENDFONT
+";
+
+fn pixel(cell: &[u8; 32], x: usize, y: usize) -> bool {
+    u16::from_le_bytes([cell[y * 2], cell[y * 2 + 1]]) & (1 << x) != 0
+}
+
+#[test]
+fn test_bdf_fallback() {
+    let font = BdfFont::parse(BDF).unwrap();
+    assert!(font.render('x').is_none());
+
+    // 基线在第 14 行之上，BBX 的偏移以基线为准
+    let cell = font.render('é').unwrap();
+    assert!(pixel(&cell, 1, 12) && pixel(&cell, 4, 12));
+    assert!(pixel(&cell, 1, 13) && !pixel(&cell, 2, 13) && pixel(&cell, 4, 13));
+    assert!(!pixel(&cell, 1, 14) && pixel(&cell, 2, 14));
+    assert_eq!(cell.iter().map(|b| b.count_ones()).sum::<u32>(), 8);
+
+    let cell = font.render('あ').unwrap();
+    assert!(pixel(&cell, 0, 2) && pixel(&cell, 15, 2) && !pixel(&cell, 7, 2));
+    assert!((0..16).all(|x| pixel(&cell, x, 3)));
+
+    // 半角字形太宽时去掉偏移并缩小到 8 点宽
+    let cell = font.render('Ж').unwrap();
+    assert!((0..8).all(|x| pixel(&cell, x, 13)));
+    assert!((8..16).all(|x| !pixel(&cell, x, 13)));
+
+    assert!(BdfFont::parse("STARTFONT 2.1\nENDFONT\n").is_err());
+}
+
+#[test]
+fn test_half_width_letters() {
+    assert!(is_half_width('a') && is_half_width('é') && is_half_width('Ж'));
+    assert!(!is_half_width('仙') && !is_half_width('あ'));
+    assert_eq!(measure_text("Café"), 32);
+}
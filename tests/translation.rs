@@ -0,0 +1,136 @@
+use encoding_rs::{ BIG5, UTF_8 };
+use pal::config::*;
+use pal::data::ScriptEntry;
+use pal::encoding::TextEncoding;
+use pal::mkf;
+use pal::translation::*;
+
+fn script(operation: u16, operand: u16) -> ScriptEntry {
+    ScriptEntry { operation, operands: [operand, 0, 0] }
+}
+
+fn sample() -> Translation {
+    let msgs = vec!["李逍遙：".to_string(), "嬸嬸！".to_string(), "".to_string()];
+    let words = vec!["".to_string(), "李逍遙".to_string()];
+    let scripts = vec![
+        script(0x003c, 5),
+        script(0xffff, 0),
+        script(0xffff, 1),
+        script(0x0000, 0),
+        script(0xffff, 1),
+    ];
+    Translation::new(&msgs, &[0, 8, 14, 14], &words, &scripts)
+}
+
+#[test]
+fn test_export_contexts() {
+    let translation = sample();
+    assert_eq!(translation.messages[1].offset, 8);
+    assert_eq!(translation.messages[0].context, vec!["script 0001: upper dialog, face 5"]);
+    assert_eq!(translation.messages[1].context, vec![
+        "script 0002: upper dialog, face 5".to_string(),
+        "script 0004: no dialog".to_string(),
+    ]);
+    assert!(translation.messages[2].context.is_empty());
+
+    let json = translation.to_json().unwrap();
+    assert_eq!(Translation::from_json(&json).unwrap(), translation);
+}
+
+#[test]
+fn test_rebuild_text_files() {
+    // 未翻译时与原来的文件相同
+    let files = sample().build(BIG5, None).unwrap();
+    assert_eq!(BIG5.decode(&files.msg[8..14]).0, "嬸嬸！");
+    let offsets: Vec<u32> = files.msg_offsets.chunks(4).map(|b| u32::from_le_bytes(b.try_into().unwrap())).collect();
+    assert_eq!(offsets, vec![0, 8, 14, 14]);
+    assert_eq!(&files.words[10..], &BIG5.encode("李逍遙    ").0[..]);
+
+    let mut translation = sample();
+    translation.messages[0].translation = "Li Xiaoyao:".to_string();
+    translation.words[1].translation = "Xiaoyao".to_string();
+    let files = translation.build(UTF_8, None).unwrap();
+    assert_eq!(&files.msg[..11], b"Li Xiaoyao:");
+    assert_eq!(&files.msg_offsets[4..8], &11u32.to_le_bytes());
+    assert_eq!(&files.words[10..], b"Xiaoyao   ");
+}
+
+#[test]
+fn test_keep_original_text() {
+    // 编码不变时未翻译的条目沿用原来的字节，包括词条结尾的 '\0' 和无法解码的字节
+    let mut msg = BIG5.encode("李逍遙：").0.into_owned();
+    msg.extend([0xff; 6]);
+    let mut words = vec![b' '; 10];
+    words.extend(BIG5.encode("李逍遙").0.iter());
+    words.extend([0; 4]);
+    let original = OriginalText { msg: &msg, msg_offsets: &[0, 8, 14, 14], words: &words };
+
+    let mut translation = sample();
+    translation.messages[1].source = BIG5.decode(&msg[8..]).0.into_owned();
+    assert!(translation.build(BIG5, None).is_err());
+    let files = translation.build(BIG5, Some(&original)).unwrap();
+    assert_eq!(files.msg, msg);
+    assert_eq!(&files.msg_offsets[4..8], &8u32.to_le_bytes());
+    assert_eq!(files.words, words);
+
+    translation.messages[0].translation = "Li:".to_string();
+    translation.words[1].translation = "Xiaoyao".to_string();
+    let files = translation.build(BIG5, Some(&original)).unwrap();
+    assert_eq!(&files.msg[..3], b"Li:");
+    assert_eq!(&files.msg[3..], &msg[8..]);
+    assert_eq!(&files.words[10..], b"Xiaoyao   ");
+
+    // 换成 UTF-8 时超过 3 个汉字的未翻译词条放不下
+    let mut translation = sample();
+    translation.words[1].source = "李逍遙哥".to_string();
+    assert!(translation.build(UTF_8, None).is_err());
+    translation.words[1].translation = "Xiaoyao".to_string();
+    assert!(translation.build(UTF_8, None).is_ok());
+}
+
+#[test]
+fn test_reject_bad_translation() {
+    let mut translation = sample();
+    translation.words[1].translation = "Li Xiaoyao!".to_string();
+    assert!(translation.build(UTF_8, None).is_err());
+
+    let mut translation = sample();
+    translation.messages[1].translation = "สวัสดี".to_string();
+    assert!(translation.build(BIG5, None).is_err());
+
+    let mut translation = sample();
+    translation.messages.remove(1);
+    assert!(translation.build(BIG5, None).is_err());
+}
+
+#[test]
+fn test_build_mkf() {
+    let chunks = vec![vec![1, 2, 3], vec![], vec![4; 10]];
+    let buf = mkf::build(&chunks);
+    assert_eq!(&buf[..4], &16u32.to_le_bytes());
+
+    let path = std::env::temp_dir().join("pal_test_build.mkf");
+    std::fs::write(&path, &buf).unwrap();
+    let mut file = mkf::open(std::fs::File::open(&path).unwrap()).unwrap();
+    assert_eq!(file.chunk_count(), 3);
+    assert_eq!(file.read_all_chunks().unwrap(), chunks);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_translation_options() {
+    let mut config = Config::default();
+    config.apply_args(
+        ["--import-text", "en.json", "--import-encoding", "utf-8", "--fallback-font", "font.bdf"]
+            .iter()
+            .map(|arg| arg.to_string())
+    ).unwrap();
+    assert_eq!(config.import_text, Some("en.json".into()));
+    assert_eq!(config.import_encoding, TextEncoding::Utf8);
+    assert!(config.headless);
+
+    // 补充字体写入配置文件，导入导出不写入
+    let loaded = Config::from_toml(&config.to_toml().unwrap()).unwrap();
+    assert_eq!(loaded.fallback_font, Some("font.bdf".into()));
+    assert_eq!(loaded.import_text, None);
+}
@@ -0,0 +1,60 @@
+use pal::canvas::{ Canvas, Color, Palette };
+use pal::video::{ Dissolve, Shake };
+
+fn index_palette() -> Palette {
+    Palette::with_colors((0..256).map(|i| Color(i as u32)).collect())
+}
+
+#[test]
+fn test_shake_screen() {
+    let mut canvas = Canvas::new(2, 4);
+    canvas.set_palette(&index_palette());
+    canvas.set_pixels(|pixels: &mut [u8]| {
+        pixels.copy_from_slice(&[1, 1, 2, 2, 3, 3, 4, 4]);
+    });
+
+    canvas.effects.shake = Shake { time: 2, level: 1 };
+    assert_eq!(canvas.get_buffer(), &[0, 0, 1, 1, 2, 2, 3, 3]);
+    assert_eq!(canvas.get_buffer(), &[2, 2, 3, 3, 4, 4, 0, 0]);
+    assert_eq!(canvas.get_buffer(), &[1, 1, 2, 2, 3, 3, 4, 4]);
+
+    // 画布本身不受影响
+    assert_eq!(canvas.get_pixels(), &[1, 1, 2, 2, 3, 3, 4, 4]);
+}
+
+#[test]
+fn test_wave_screen() {
+    let mut canvas = Canvas::new(320, 2);
+    canvas.set_palette(&index_palette());
+    canvas.set_pixels(|pixels: &mut [u8]| {
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            *pixel = (i % 320) as u8;
+        }
+    });
+
+    canvas.effects.wave.level = 128;
+    let buffer = canvas.get_buffer().to_vec();
+    assert_eq!(buffer[0], 30);
+    assert_eq!(buffer[320], 56);
+}
+
+#[test]
+fn test_dissolve() {
+    let from = vec![0x10; 12];
+    let to = vec![0x24; 12];
+    let mut dissolve = Dissolve::new(&from, &to);
+
+    let first = dissolve.next_frame().unwrap().to_vec();
+    assert_eq!(first[0], 0x20);
+    assert_eq!(first[1], 0x10);
+
+    let mut frames = 1;
+    let mut last = first;
+    while let Some(frame) = dissolve.next_frame() {
+        last = frame.to_vec();
+        frames += 1;
+    }
+
+    assert_eq!(frames, 72);
+    assert_eq!(last, to);
+}
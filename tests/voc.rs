@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use pal::audio::*;
+use pal::voc::*;
+
+fn voc_file(blocks: &[(u8, Vec<u8>)]) -> Vec<u8> {
+    let mut buf = b"Creative Voice File\x1a".to_vec();
+    buf.extend_from_slice(&0x1au16.to_le_bytes());
+    buf.extend_from_slice(&0x010au16.to_le_bytes());
+    buf.extend_from_slice(&0x1129u16.to_le_bytes());
+    for (block_type, data) in blocks {
+        buf.push(*block_type);
+        buf.extend_from_slice(&(data.len() as u32).to_le_bytes()[..3]);
+        buf.extend_from_slice(data);
+    }
+    buf.push(0);
+    buf
+}
+
+#[test]
+fn test_voc_sound_data() {
+    // 时间常数 156 即 10000Hz，块 2 接着块 1 的格式
+    let buf = voc_file(&[
+        (1, vec![156, 0, 0x80, 0xff, 0x00]),
+        (2, vec![0xc0]),
+    ]);
+    let voc = Voc::parse(&buf).unwrap();
+    assert_eq!(voc.sample_rate, 10000);
+    assert_eq!(voc.samples, vec![0, 127 << 8, -128 << 8, 64 << 8]);
+}
+
+#[test]
+fn test_voc_extended_stereo() {
+    // 块 8：22050Hz 立体声，作用于后面的块 1
+    let time_constant = 65536 - 256000000 / (2 * 22050);
+    let mut ext = (time_constant as u16).to_le_bytes().to_vec();
+    ext.extend_from_slice(&[0, 1]);
+    let buf = voc_file(&[
+        (8, ext),
+        (1, vec![0, 0, 0x80, 0xc0, 0x00, 0x80]),
+    ]);
+    let voc = Voc::parse(&buf).unwrap();
+    assert!((voc.sample_rate as i32 - 22050).abs() < 10);
+    assert_eq!(voc.samples, vec![32 << 8, -64 << 8]);
+}
+
+#[test]
+fn test_voc_new_format() {
+    let mut block = 8000u32.to_le_bytes().to_vec();
+    block.extend_from_slice(&[16, 1]);
+    block.extend_from_slice(&4u16.to_le_bytes());
+    block.extend_from_slice(&[0; 4]);
+    for sample in [1000i16, -1000] {
+        block.extend_from_slice(&sample.to_le_bytes());
+    }
+    let voc = Voc::parse(&voc_file(&[(9, block)])).unwrap();
+    assert_eq!(voc.sample_rate, 8000);
+    assert_eq!(voc.samples, vec![1000, -1000]);
+}
+
+#[test]
+fn test_voc_reject_invalid() {
+    assert!(Voc::parse(b"RIFF").is_err());
+    assert!(Voc::parse(&voc_file(&[])).is_err());
+    assert!(Voc::parse(&voc_file(&[(1, vec![156, 2, 0x80])])).is_err());
+}
+
+#[test]
+fn test_resample() {
+    let samples = resample(&[0, 1000, 2000, 3000], 11025, 44100);
+    assert_eq!(samples.len(), 16);
+    assert_eq!(samples[..5], [0, 250, 500, 750, 1000]);
+    assert_eq!(resample(&[5, 6], 8000, 8000), vec![5, 6]);
+}
+
+#[test]
+fn test_mix_sounds() {
+    let mut audio = Audio::new(SAMPLE_RATE);
+    let a: Arc<[i16]> = vec![10000; 4].into();
+    let b: Arc<[i16]> = vec![30000; 2].into();
+    assert_eq!(audio.play_sound(a, 1.0), 0);
+    assert_eq!(audio.play_sound(b, 0.5), 1);
+
+    let mut out = [0i16; 6];
+    audio.render(&mut out);
+    assert_eq!(out, [25000, 25000, 10000, 10000, 0, 0]);
+    assert!(!audio.is_sound_playing(0));
+    assert!(!audio.is_sound_playing(1));
+
+    // 叠加溢出时截断
+    audio.play_sound(vec![30000; 1].into(), 1.0);
+    audio.play_sound(vec![30000; 1].into(), 1.0);
+    audio.render(&mut out[..1]);
+    assert_eq!(out[0], i16::MAX);
+}
+
+#[test]
+fn test_sound_channel_reuse() {
+    let mut audio = Audio::new(SAMPLE_RATE);
+    for i in 0..SOUND_CHANNELS {
+        assert_eq!(audio.play_sound(vec![1; 100].into(), 1.0), i);
+    }
+    // 通道用完时替换最早开始的
+    assert_eq!(audio.play_sound(vec![1; 100].into(), 1.0), 0);
+    assert_eq!(audio.play_sound(vec![1; 100].into(), 1.0), 1);
+
+    audio.stop_sound(3);
+    assert_eq!(audio.play_sound(vec![1; 100].into(), 1.0), 3);
+
+    audio.set_sound_volume(2, 0.0);
+    for i in 0..SOUND_CHANNELS {
+        if i != 2 {
+            audio.stop_sound(i);
+        }
+    }
+    let mut out = [1i16; 4];
+    audio.render(&mut out);
+    assert_eq!(out, [0; 4]);
+}